@@ -0,0 +1,283 @@
+use super::*;
+use std::fmt::{Debug, Formatter};
+
+/// Bounding volume hierarchy over a list of items, built with SAH
+///
+/// Only indices are stored, so the same structure serves both the scene (over primitives)
+/// and meshes (over triangles).
+#[derive(Clone)]
+pub struct Bvh {
+    /// Flattened depth-first, the first child of an interior node follows it immediately
+    nodes: Vec<LinearNode>,
+    /// Item indices, leaves refer to contiguous ranges of it
+    indices: Vec<u32>,
+    /// Items without finite bounds, always tested
+    unbounded: Vec<u32>,
+}
+
+#[derive(Debug, Copy, Clone)]
+struct LinearNode {
+    bounds: Bounds3f,
+    /// Leaf: first item in `indices`; interior: the second child
+    offset: u32,
+    /// Number of items, 0 for interior nodes
+    count: u32,
+    /// Split axis of interior nodes
+    axis: u8,
+}
+
+enum BuildNode {
+    Leaf { bounds: Bounds3f, first: usize, count: usize },
+    Interior { bounds: Bounds3f, axis: usize, children: Box<(BuildNode, BuildNode)> },
+}
+
+#[derive(Copy, Clone)]
+struct BuildItem {
+    index: u32,
+    bounds: Bounds3f,
+    centroid: Point3f,
+}
+
+const N_BUCKETS: usize = 12;
+const MAX_LEAF_SIZE: usize = 4;
+/// Deeper subtrees become leaves, so that the traversal stack never overflows
+const MAX_DEPTH: usize = 60;
+/// Subtrees with more items are built in parallel
+const PARALLEL_THRESHOLD: usize = 1024;
+
+impl Bvh {
+    /// Build over items with the given bounds, the i-th item is referred to by index `i`
+    pub fn build(bounds: &[Bounds3f]) -> Self {
+        let (mut items, unbounded): (Vec<_>, Vec<_>) = bounds.iter().enumerate()
+            .map(|(i, b)| BuildItem { index: i as u32, bounds: *b, centroid: b.centroid() })
+            .partition(|item| item.bounds.is_finite());
+        let unbounded = unbounded.into_iter().map(|item| item.index).collect();
+        if items.is_empty() {
+            return Self { nodes: Vec::new(), indices: Vec::new(), unbounded };
+        }
+        let root = build_recursive(&mut items, 0, 0);
+        let mut nodes = Vec::with_capacity(2 * items.len());
+        flatten(&root, &mut nodes);
+        Self { nodes, indices: items.iter().map(|item| item.index).collect(), unbounded }
+    }
+
+    /// Bounds of all the bounded items
+    pub fn bounds(&self) -> Bounds3f {
+        self.nodes.first().map_or_else(Bounds3f::empty, |root| root.bounds)
+    }
+
+    /// Find the nearest hit along the ray
+    ///
+    /// `intersect(i, ray)` tests item `i`, returning the hit time and payload.
+    /// The `ray` passed in is shortened to the nearest hit found so far.
+    pub fn nearest<H>(&self, ray: &Ray, mut intersect: impl FnMut(usize, &Ray) -> Option<(Float, H)>) -> Option<H> {
+        let mut ray = ray.clone();
+        let mut hit = None;
+        self.traverse(&mut ray, |i, ray| {
+            if let Some((t, h)) = intersect(i, ray) {
+                if ray.in_range(t) {
+                    ray.t_max = t;
+                    hit = Some(h);
+                }
+            }
+            false
+        });
+        hit
+    }
+
+    /// Is there any hit along the ray? Stop at the first one found
+    ///
+    /// `intersect_p(i, ray)` tests item `i`
+    pub fn any(&self, ray: &Ray, mut intersect_p: impl FnMut(usize, &Ray) -> bool) -> bool {
+        let mut ray = ray.clone();
+        self.traverse(&mut ray, |i, ray| intersect_p(i, ray))
+    }
+
+    /// Visit the items whose bounds overlap the ray, near to far, until `visit` returns true
+    fn traverse(&self, ray: &mut Ray, mut visit: impl FnMut(usize, &mut Ray) -> bool) -> bool {
+        for &i in &self.unbounded {
+            if visit(i as usize, ray) { return true; }
+        }
+        if self.nodes.is_empty() { return false; }
+
+        let inv_dir = vec3(1. / ray.dir.x, 1. / ray.dir.y, 1. / ray.dir.z);
+        let dir_is_neg = [inv_dir.x < 0., inv_dir.y < 0., inv_dir.z < 0.];
+        let mut stack = [0usize; MAX_DEPTH + 4];
+        let mut sp = 0;
+        let mut current = 0;
+        loop {
+            let node = &self.nodes[current];
+            if node.bounds.intersect_p(ray, inv_dir) {
+                if node.count > 0 { // leaf
+                    let first = node.offset as usize;
+                    for &i in &self.indices[first..first + node.count as usize] {
+                        if visit(i as usize, ray) { return true; }
+                    }
+                } else { // visit the nearer child first
+                    let (near, far) = if dir_is_neg[node.axis as usize] {
+                        (node.offset as usize, current + 1)
+                    } else {
+                        (current + 1, node.offset as usize)
+                    };
+                    stack[sp] = far;
+                    sp += 1;
+                    current = near;
+                    continue;
+                }
+            }
+            if sp == 0 { return false; }
+            sp -= 1;
+            current = stack[sp];
+        }
+    }
+}
+
+impl Debug for Bvh {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Bvh")
+            .field("bounds", &self.bounds())
+            .field("n_nodes", &self.nodes.len())
+            .field("n_items", &(self.indices.len() + self.unbounded.len()))
+            .finish()
+    }
+}
+
+/// `offset` is the position of `items` in the whole item list
+fn build_recursive(items: &mut [BuildItem], offset: usize, depth: usize) -> BuildNode {
+    let bounds = items.iter().fold(Bounds3f::empty(), |b, item| b.union(&item.bounds));
+    let leaf = BuildNode::Leaf { bounds, first: offset, count: items.len() };
+    if items.len() == 1 { return leaf; }
+    let centroid_bounds = Bounds3f::from_points(items.iter().map(|item| item.centroid));
+    let axis = centroid_bounds.max_extent();
+    let (c_min, c_max) = (centroid_bounds.min[axis], centroid_bounds.max[axis]);
+    if c_min == c_max || depth >= MAX_DEPTH { // cannot split by centroids
+        return leaf;
+    }
+
+    // SAH over buckets along the axis
+    let bucket_of = |item: &BuildItem| {
+        let b = (N_BUCKETS as Float * (item.centroid[axis] - c_min) / (c_max - c_min)) as usize;
+        b.min(N_BUCKETS - 1)
+    };
+    let mut counts = [0usize; N_BUCKETS];
+    let mut bucket_bounds = [Bounds3f::empty(); N_BUCKETS];
+    for item in items.iter() {
+        let b = bucket_of(item);
+        counts[b] += 1;
+        bucket_bounds[b] = bucket_bounds[b].union(&item.bounds);
+    }
+    // cost of splitting after bucket i, relative to a unit intersection cost
+    let mut best = (Float::infinity(), 0);
+    for i in 0..N_BUCKETS - 1 {
+        let (mut b0, mut b1) = (Bounds3f::empty(), Bounds3f::empty());
+        let (mut n0, mut n1) = (0, 0);
+        for j in 0..=i {
+            b0 = b0.union(&bucket_bounds[j]);
+            n0 += counts[j];
+        }
+        for j in i + 1..N_BUCKETS {
+            b1 = b1.union(&bucket_bounds[j]);
+            n1 += counts[j];
+        }
+        let cost = 0.125 + (n0 as Float * b0.surface_area() + n1 as Float * b1.surface_area()) / bounds.surface_area();
+        if cost < best.0 { best = (cost, i); }
+    }
+    if items.len() <= MAX_LEAF_SIZE && best.0 >= items.len() as Float {
+        return leaf;
+    }
+    let mid = partition(items, |item| bucket_of(item) <= best.1);
+    if mid == 0 || mid == items.len() {
+        return split_middle(items, offset, depth, bounds, axis);
+    }
+    build_children(items, mid, offset, depth, bounds, axis)
+}
+
+/// Fall back to splitting into equal halves along the axis
+fn split_middle(items: &mut [BuildItem], offset: usize, depth: usize, bounds: Bounds3f, axis: usize) -> BuildNode {
+    let mid = items.len() / 2;
+    items.sort_unstable_by(|a, b| a.centroid[axis].partial_cmp(&b.centroid[axis]).unwrap());
+    build_children(items, mid, offset, depth, bounds, axis)
+}
+
+fn build_children(items: &mut [BuildItem], mid: usize, offset: usize, depth: usize, bounds: Bounds3f, axis: usize) -> BuildNode {
+    let parallel = items.len() > PARALLEL_THRESHOLD;
+    let (left, right) = items.split_at_mut(mid);
+    let children = if parallel {
+        rayon::join(|| build_recursive(left, offset, depth + 1),
+                    || build_recursive(right, offset + mid, depth + 1))
+    } else {
+        (build_recursive(left, offset, depth + 1), build_recursive(right, offset + mid, depth + 1))
+    };
+    BuildNode::Interior { bounds, axis, children: Box::new(children) }
+}
+
+/// Move items satisfying `pred` to the front, return their count
+fn partition(items: &mut [BuildItem], pred: impl Fn(&BuildItem) -> bool) -> usize {
+    let mut mid = 0;
+    for i in 0..items.len() {
+        if pred(&items[i]) {
+            items.swap(i, mid);
+            mid += 1;
+        }
+    }
+    mid
+}
+
+fn flatten(node: &BuildNode, nodes: &mut Vec<LinearNode>) {
+    match node {
+        BuildNode::Leaf { bounds, first, count } => nodes.push(LinearNode {
+            bounds: *bounds,
+            offset: *first as u32,
+            count: *count as u32,
+            axis: 0,
+        }),
+        BuildNode::Interior { bounds, axis, children } => {
+            let this = nodes.len();
+            nodes.push(LinearNode { bounds: *bounds, offset: 0, count: 0, axis: *axis as u8 });
+            flatten(&children.0, nodes);
+            nodes[this].offset = nodes.len() as u32;
+            flatten(&children.1, nodes);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::random;
+
+    #[test]
+    fn empty() {
+        let bvh = Bvh::build(&[]);
+        assert!(bvh.bounds().is_empty());
+        let ray = Ray::new(pt3(0., 0., 0.), vec3(1., 0., 0.));
+        assert_eq!(bvh.nearest(&ray, |_, _| Some((1., ()))), None);
+        assert!(!bvh.any(&ray, |_, _| true));
+    }
+
+    #[test]
+    fn boxes() {
+        // unit boxes along the x axis, hit by their min x
+        let bounds: Vec<_> = (0..1000).map(|i| {
+            let x = i as Float * 2.;
+            Bounds3f::new(pt3(x, 0., 0.), pt3(x + 1., 1., 1.))
+        }).collect();
+        let bvh = Bvh::build(&bounds);
+        assert_eq!(bvh.bounds(), Bounds3f::new(pt3(0., 0., 0.), pt3(1999., 1., 1.)));
+        for _ in 0..100 {
+            let x = random::<Float>() * 2000.;
+            let ray = Ray::new(pt3(x, 0.5, 0.5), vec3(1., 0., 0.));
+            let hit = bvh.nearest(&ray, |i, ray| {
+                let t = bounds[i].min.x - x;
+                if ray.in_range(t) { Some((t, i)) } else { None }
+            });
+            // a box starting right at the origin is not hit, t = 0 is out of range
+            let expected = (x / 2.).floor() as usize + 1;
+            assert_eq!(hit, if expected < 1000 { Some(expected) } else { None });
+            // blocked only if the nearest box is within the segment
+            let segment = Ray::new_with_range(ray.org, ray.dir, 0., 1.);
+            let blocked = bvh.any(&segment, |i, ray| ray.in_range(bounds[i].min.x - x));
+            assert_eq!(blocked, expected < 1000 && expected as Float * 2. - x < 1.);
+        }
+    }
+}
@@ -0,0 +1,5 @@
+use crate::core::*;
+
+mod bvh;
+
+pub use bvh::Bvh;
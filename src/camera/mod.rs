@@ -9,19 +9,22 @@ mod orthogonal;
 #[derive(Debug, Clone)]
 pub struct Camera<C: CameraInner> {
     inner: C,
-    world_to_local: Matrix4f,
-    local_to_world: Matrix4f,
+    /// local_to_world
+    transform: Transform,
+    /// Overrides `transform` when moving
+    animation: Option<AnimatedTransform>,
+    /// Open and close times, rays are spread over them
+    shutter: (Float, Float),
 }
 
 impl<C> Camera<C> where C: CameraInner {
     /// Construct a Camera with the observer's parameters given
     pub fn new(inner: C, eye: Point3f, gaze: Point3f, up: Vector3f) -> Self {
-        let world_to_local = Matrix4::look_at(eye, gaze, up);
         Self {
             inner,
-            world_to_local,
-            local_to_world: world_to_local.inverse_transform()
-                .unwrap_or_else(|| panic!(format!("Singular transform when initializing camera: eye = {:?} gaze = {:?} up = {:?}", eye, gaze, up))),
+            transform: Transform::new(look_at_transform(eye, gaze, up)),
+            animation: None,
+            shutter: (0., 0.),
         }
     }
 
@@ -29,30 +32,62 @@ impl<C> Camera<C> where C: CameraInner {
     ///
     /// Notice: the screen coordinate y is inverse to physical coordinate!
     pub fn generate_ray(&self, x: u32, y: u32, aperture_samp: Point2f) -> (Ray, Float) {
+        self.generate_ray_with_time(x, y, aperture_samp, 0.)
+    }
+
+    /// Like `generate_ray`, at the time `time_samp` in `[0, 1)` of the way through the shutter interval
+    pub fn generate_ray_with_time(&self, x: u32, y: u32, aperture_samp: Point2f, time_samp: Float) -> (Ray, Float) {
         let (ray, pdf) = self.inner.generate_ray(x, y, aperture_samp);
         debug_assert_approx!(ray.dir.magnitude(), 1.0);
-        (self.local_to_world.transform(&ray), pdf)
+        let ray = ray.with_time(lerp(self.shutter.0, self.shutter.1, time_samp));
+        match &self.animation {
+            None => (self.transform.transform(&ray), pdf),
+            Some(animation) => (animation.transform_at(ray.time).transform(&ray), pdf),
+        }
+    }
+
+    #[inline]
+    pub fn shutter(&self) -> (Float, Float) { self.shutter }
+
+    pub fn set_shutter(&mut self, open: Float, close: Float) {
+        debug_assert!(open <= close);
+        self.shutter = (open, close);
     }
 
-    pub fn transform(&self) -> Matrix4f {
-        self.local_to_world
+    /// Move the camera with a keyframed local_to_world transform, see `look_at_transform`
+    ///
+    /// The fixed transform is set to the one at the start
+    pub fn set_animation(&mut self, animation: AnimatedTransform) {
+        self.set_transform(animation.at(animation.time_range().0));
+        self.animation = Some(animation).filter(|a| a.is_animated());
     }
 
+    /// The fixed local_to_world transform
+    pub fn transform(&self) -> &Transform {
+        &self.transform
+    }
+
+    /// The camera stops moving if it was animated
     pub fn set_transform(&mut self, transform: Matrix4f) {
-        self.local_to_world = transform;
-        self.world_to_local = transform.inverse_transform()
-            .unwrap_or_else(|| panic!(format!("Singular transform {:?}", transform)));
+        self.transform = Transform::new(transform);
+        self.animation = None;
     }
 
     pub fn translate(&mut self, translation: Vector3f) {
-        self.set_transform(self.local_to_world * Matrix4::from_translation(translation))
+        self.set_transform(self.transform.matrix() * Matrix4::from_translation(translation))
     }
 
     pub fn rotate<A: Into<Radf>>(&mut self, axis: Vector3f, angle: A) {
-        self.set_transform(self.local_to_world * Matrix4::from_axis_angle(axis, angle));
+        self.set_transform(self.transform.matrix() * Matrix4::from_axis_angle(axis, angle));
     }
 }
 
+/// The local_to_world transform of a camera at `eye` looking at `gaze`, e.g. to build keyframes
+pub fn look_at_transform(eye: Point3f, gaze: Point3f, up: Vector3f) -> Matrix4f {
+    Matrix4::look_at(eye, gaze, up).inverse_transform()
+        .unwrap_or_else(|| panic!("Singular transform: eye = {:?} gaze = {:?} up = {:?}", eye, gaze, up))
+}
+
 pub trait CameraInner: Clone + Debug + Send + Sync + 'static {
     /// Sample a ray at **image** screen pixel coordinate (x, y), return the local ray and its pdf
     fn generate_ray(&self, x: u32, y: u32, aperture_samp: Point2f) -> (Ray, Float);
@@ -95,4 +130,30 @@ mod test {
         assert_eq!(ray.dir, ray2.dir);
         assert_eq!(ray.org + vec3(0., 0.5, 0.), ray2.org);
     }
+
+    #[test]
+    fn motion() {
+        let mut camera = Camera::new(
+            Perspective::new(10, 10, Deg(90.)),
+            pt3(0., 0., 0.),
+            pt3(0., 0., 1.),
+            vec3(0., 1., 0.),
+        );
+        camera.set_shutter(1., 2.);
+        camera.set_animation(AnimatedTransform::between(
+            1., look_at_transform(pt3(0., 0., 0.), pt3(0., 0., 1.), vec3(0., 1., 0.)),
+            2., look_at_transform(pt3(4., 0., 0.), pt3(4., 0., 1.), vec3(0., 1., 0.)),
+        ));
+        let mut samp = sampler::Fake;
+        let (ray, _) = camera.generate_ray_with_time(5, 5, samp.next2d(), 0.25);
+        assert_eq!(ray.time, 1.25);
+        assert_approx!((ray.org - pt3(1., 0., 0.)).magnitude(), 0.);
+        assert_approx!((ray.dir - vec3(0., 0., 1.)).magnitude(), 0.);
+        // the start of the shutter
+        let (ray, _) = camera.generate_ray(5, 5, samp.next2d());
+        assert_eq!(ray.time, 1.);
+        assert_approx!((ray.org - pt3(0., 0., 0.)).magnitude(), 0.);
+        camera.translate(vec3(0., 1., 0.));
+        assert_eq!(camera.generate_ray_with_time(5, 5, samp.next2d(), 0.5).0.org, pt3(0., 1., 0.));
+    }
 }
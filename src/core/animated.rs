@@ -0,0 +1,249 @@
+use super::*;
+
+/// Transform interpolated between keyframes, for motion blur
+///
+/// Each keyframe is decomposed into translation, rotation and scale, which are interpolated separately
+/// (rotations along the shortest arc), so that rigid motions stay rigid in between.
+/// Times outside the keyframes are clamped.
+#[derive(Debug, Clone)]
+pub struct AnimatedTransform {
+    /// Sorted by time
+    keyframes: Vec<Keyframe>,
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Keyframe {
+    time: Float,
+    matrix: Matrix4f,
+    /// `matrix` with its inverse
+    transform: Transform,
+    translation: Vector3f,
+    rotation: Quaternion<Float>,
+    /// What is left after the rotation is factored out, symmetric
+    scale: Matrix3f,
+}
+
+/// Number of steps between two keyframes for bounding the motion
+const N_BOUND_SAMPLES: usize = 64;
+
+impl AnimatedTransform {
+    /// Keyframes as `(time, transform)`, in any order
+    pub fn new(keyframes: impl IntoIterator<Item=(Float, Matrix4f)>) -> Self {
+        let mut keyframes: Vec<_> = keyframes.into_iter().map(|(time, m)| Keyframe::decompose(time, m)).collect();
+        assert!(!keyframes.is_empty(), "No keyframe is given!");
+        keyframes.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap());
+        // q and -q are the same rotation, pick the one nearer to the previous keyframe
+        for i in 1..keyframes.len() {
+            if keyframes[i - 1].rotation.dot(keyframes[i].rotation) < 0. {
+                keyframes[i].rotation = -keyframes[i].rotation;
+            }
+        }
+        Self { keyframes }
+    }
+
+    /// The same transform at any time
+    pub fn fixed(transform: Matrix4f) -> Self { Self::new(Some((0., transform))) }
+
+    /// Move linearly from `start` to `end` during `[t0, t1]`
+    pub fn between(t0: Float, start: Matrix4f, t1: Float, end: Matrix4f) -> Self {
+        Self::new(vec![(t0, start), (t1, end)])
+    }
+
+    pub fn is_animated(&self) -> bool {
+        self.keyframes.windows(2).any(|w| w[0].matrix != w[1].matrix)
+    }
+
+    /// Times of the first and last keyframes
+    pub fn time_range(&self) -> (Float, Float) {
+        (self.keyframes[0].time, self.keyframes[self.keyframes.len() - 1].time)
+    }
+
+    /// The transform at `time`
+    pub fn at(&self, time: Float) -> Matrix4f {
+        match self.segment(time) {
+            Err(k) => k.matrix,
+            Ok((k0, k1, s)) => {
+                let (translation, rotation, scale) = k0.interpolate(k1, s);
+                Matrix4::from_translation(translation) * Matrix4::from(rotation * scale)
+            }
+        }
+    }
+
+    /// The transform at `time` with its inverse, put together from the inverted parts
+    pub fn transform_at(&self, time: Float) -> Transform {
+        let (k0, k1, s) = match self.segment(time) {
+            Err(k) => return k.transform,
+            Ok(segment) => segment,
+        };
+        let (translation, rotation, scale) = k0.interpolate(k1, s);
+        let scale_inv = scale.invert()
+            .unwrap_or_else(|| panic!("Singular transform at time {}", time));
+        Transform::with_inverse(
+            Matrix4::from_translation(translation) * Matrix4::from(rotation * scale),
+            Matrix4::from(scale_inv * rotation.transpose()) * Matrix4::from_translation(-translation),
+        )
+    }
+
+    /// The keyframes around `time` and how far it is between them, or the keyframe it is clamped to
+    fn segment(&self, time: Float) -> Result<(&Keyframe, &Keyframe, Float), &Keyframe> {
+        let first = &self.keyframes[0];
+        if time <= first.time { return Err(first); }
+        match self.keyframes.iter().position(|k| k.time > time) {
+            None => Err(&self.keyframes[self.keyframes.len() - 1]),
+            Some(i) => {
+                let (k0, k1) = (&self.keyframes[i - 1], &self.keyframes[i]);
+                Ok((k0, k1, (time - k0.time) / (k1.time - k0.time)))
+            }
+        }
+    }
+
+    /// Bound `bounds` over the whole motion
+    ///
+    /// The poses are sampled between keyframes. Between two samples, a point of the box stays within
+    /// `4 sin(dθ / 4) |S p|` of the straight path between its two poses, where `dθ` is the rotation between
+    /// the samples and `S p` the point scaled, so the union of the posed boxes is grown by that much.
+    pub fn motion_bounds(&self, bounds: &Bounds3f) -> Bounds3f {
+        let mut result = self.keyframes.iter().fold(Bounds3f::empty(), |b, k| b.union(&k.matrix.transform(bounds)));
+        if !bounds.is_finite() { return result; }
+        for w in self.keyframes.windows(2) {
+            let (k0, k1) = (&w[0], &w[1]);
+            if k0.matrix == k1.matrix { continue; }
+            // slerp turns at a constant rate, around a fixed axis
+            let theta = 2. * k0.rotation.dot(k1.rotation).min(1.).acos();
+            let swing = 4. * (theta / (4. * N_BOUND_SAMPLES as Float)).sin();
+            // the scale is interpolated linearly, so the scaled lengths are the largest at the keyframes
+            let radius = (0..8)
+                .map(|i| bounds.corner(i).to_vec())
+                .map(|p| (k0.scale * p).magnitude().max((k1.scale * p).magnitude()))
+                .fold(0., Float::max);
+            let mut prev = k0.matrix.transform(bounds);
+            for i in 1..=N_BOUND_SAMPLES {
+                let next = if i == N_BOUND_SAMPLES {
+                    k1.matrix.transform(bounds)
+                } else {
+                    self.at(lerp(k0.time, k1.time, i as Float / N_BOUND_SAMPLES as Float)).transform(bounds)
+                };
+                result = result.union(&prev.union(&next).expand(swing * radius));
+                prev = next;
+            }
+        }
+        result
+    }
+}
+
+impl Keyframe {
+    fn decompose(time: Float, matrix: Matrix4f) -> Self {
+        let translation = matrix.w.truncate();
+        let m = Matrix3::from_cols(matrix.x.truncate(), matrix.y.truncate(), matrix.z.truncate());
+        // polar decomposition m = R S, by averaging R with its inverse transpose until converged
+        let mut r = m;
+        for _ in 0..100 {
+            let r_it = r.invert()
+                .unwrap_or_else(|| panic!("Singular keyframe transform {:?}", matrix))
+                .transpose();
+            let next = (r + r_it) * 0.5;
+            let diff = next - r;
+            r = next;
+            if diff.x.magnitude2() + diff.y.magnitude2() + diff.z.magnitude2() < 1e-12 { break; }
+        }
+        if r.determinant() < 0. { r = -r; } // mirroring goes to the scale
+        let scale = r.transpose() * m;
+        Self { time, matrix, transform: Transform::new(matrix), translation, rotation: Quaternion::from(r).normalize(), scale }
+    }
+
+    /// Translation, rotation and scale at `s` of the way to `next`
+    fn interpolate(&self, next: &Keyframe, s: Float) -> (Vector3f, Matrix3f, Matrix3f) {
+        (
+            lerp(self.translation, next.translation, s),
+            Matrix3::from(self.rotation.slerp(next.rotation, s).normalize()),
+            lerp(self.scale, next.scale, s),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::macros::*;
+
+    fn assert_matrix_approx(a: Matrix4f, b: Matrix4f) {
+        for i in 0..4 {
+            assert_approx!((a[i] - b[i]).magnitude(), 0.);
+        }
+    }
+
+    #[test]
+    fn decompose() {
+        let m = Matrix4::from_translation(vec3(1., 2., 3.)) *
+            Matrix4::from_axis_angle(vec3(1., 1., 0.).normalize(), Deg(70.)) *
+            Matrix4::from_nonuniform_scale(1., 2., 0.5);
+        let anim = AnimatedTransform::new(vec![(0., m), (1., m)]);
+        assert!(!anim.is_animated());
+        assert_matrix_approx(anim.at(0.5), m);
+        let k = &anim.keyframes[0];
+        assert_approx!((k.scale.diagonal() - vec3(1., 2., 0.5)).magnitude(), 0.);
+        let mirror = Matrix4::from_nonuniform_scale(-1., 1., 1.);
+        assert_matrix_approx(AnimatedTransform::between(0., mirror, 1., mirror).at(0.3), mirror);
+    }
+
+    #[test]
+    fn interpolate() {
+        let anim = AnimatedTransform::new(vec![
+            (1., Matrix4::from_translation(vec3(10., 0., 0.)) * Matrix4::from_angle_z(Deg(90.))),
+            (0., Matrix4::identity()),
+            (2., Matrix4::from_translation(vec3(10., 0., 0.)) * Matrix4::from_angle_z(Deg(90.)) * Matrix4::from_scale(3.)),
+        ]);
+        assert!(anim.is_animated());
+        assert_eq!(anim.time_range(), (0., 2.));
+        assert_matrix_approx(anim.at(-1.), Matrix4::identity());
+        assert_matrix_approx(anim.at(0.5), Matrix4::from_translation(vec3(5., 0., 0.)) * Matrix4::from_angle_z(Deg(45.)));
+        assert_matrix_approx(anim.at(1.5), Matrix4::from_translation(vec3(10., 0., 0.)) * Matrix4::from_angle_z(Deg(90.)) * Matrix4::from_scale(2.));
+        assert_matrix_approx(anim.at(5.), anim.keyframes[2].matrix);
+        // the shortest arc, not through 270 degrees
+        let anim = AnimatedTransform::between(0., Matrix4::from_angle_z(Deg(-10.)), 1., Matrix4::from_angle_z(Deg(350.)));
+        assert_matrix_approx(anim.at(0.5), Matrix4::from_angle_z(Deg(-10.)));
+    }
+
+    #[test]
+    fn transform_at() {
+        let anim = AnimatedTransform::between(
+            0., Matrix4::from_translation(vec3(1., 2., 3.)) * Matrix4::from_nonuniform_scale(1., 2., 0.5),
+            1., Matrix4::from_axis_angle(vec3(1., 1., 0.).normalize(), Deg(120.)) * Matrix4::from_scale(3.),
+        );
+        for &time in &[-1., 0., 0.3, 0.7, 1., 2.] {
+            let t = anim.transform_at(time);
+            assert_matrix_approx(*t.matrix(), anim.at(time));
+            assert_matrix_approx(t.matrix() * t.inverse_matrix(), Matrix4::identity());
+        }
+    }
+
+    #[test]
+    fn motion_bounds() {
+        let b = Bounds3f::new(pt3(-1., -1., -1.), pt3(1., 1., 1.));
+        let anim = AnimatedTransform::fixed(Matrix4::from_translation(vec3(1., 0., 0.)));
+        assert_eq!(anim.motion_bounds(&b), Bounds3f::new(pt3(0., -1., -1.), pt3(2., 1., 1.)));
+        // a quarter turn around the origin
+        let anim = AnimatedTransform::between(
+            0., Matrix4::from_translation(vec3(10., 0., 0.)),
+            1., Matrix4::from_angle_z(Deg(90.)) * Matrix4::from_translation(vec3(10., 0., 0.)),
+        );
+        let mb = anim.motion_bounds(&b);
+        for i in 0..=100 {
+            let c = anim.at(i as Float / 100.).transform_point(Point3::origin());
+            assert!(mb.contains(c));
+        }
+        assert_lt!(mb.min.x, 0.);
+        assert_gt!(mb.max.x, 10.);
+        assert_gt!(mb.max.y, 10.);
+        // swinging around the local origin, the corners sweep arcs bulging out between the samples
+        let anim = AnimatedTransform::between(0., Matrix4::identity(), 1., Matrix4::from_angle_z(Deg(100.)));
+        let needle = Bounds3f::new(pt3(9., -0.1, -0.1), pt3(10., 0.1, 0.1));
+        let mb = anim.motion_bounds(&needle);
+        for i in 0..=1000 {
+            let b = anim.at(i as Float / 1000.).transform(&needle);
+            assert!(mb.contains(b.min) && mb.contains(b.max), "{} {:?} {:?}", i, b, mb);
+        }
+        // but not much looser
+        assert_lt!(mb.max.y, needle.max.x.hypot(needle.max.y) + 0.5);
+    }
+}
@@ -0,0 +1,196 @@
+use super::*;
+
+/// Axis-aligned bounding box
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Bounds3f {
+    pub min: Point3f,
+    pub max: Point3f,
+}
+
+impl Bounds3f {
+    /// The smallest box containing both points
+    pub fn new(a: Point3f, b: Point3f) -> Self {
+        Self {
+            min: pt3(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z)),
+            max: pt3(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z)),
+        }
+    }
+    /// Contains nothing, the identity of `union`
+    pub fn empty() -> Self {
+        Self {
+            min: pt3(Float::infinity(), Float::infinity(), Float::infinity()),
+            max: pt3(Float::neg_infinity(), Float::neg_infinity(), Float::neg_infinity()),
+        }
+    }
+    /// Contains everything
+    pub fn infinite() -> Self {
+        Self {
+            min: pt3(Float::neg_infinity(), Float::neg_infinity(), Float::neg_infinity()),
+            max: pt3(Float::infinity(), Float::infinity(), Float::infinity()),
+        }
+    }
+    pub fn from_point(p: Point3f) -> Self { Self { min: p, max: p } }
+    pub fn from_points(points: impl IntoIterator<Item=Point3f>) -> Self {
+        points.into_iter().fold(Self::empty(), |b, p| b.union_point(p))
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        Self {
+            min: pt3(self.min.x.min(other.min.x), self.min.y.min(other.min.y), self.min.z.min(other.min.z)),
+            max: pt3(self.max.x.max(other.max.x), self.max.y.max(other.max.y), self.max.z.max(other.max.z)),
+        }
+    }
+    pub fn union_point(&self, p: Point3f) -> Self { self.union(&Self::from_point(p)) }
+    /// Grown by `delta` on every side
+    pub fn expand(&self, delta: Float) -> Self {
+        let d = vec3(delta, delta, delta);
+        Self { min: self.min - d, max: self.max + d }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+    pub fn is_finite(&self) -> bool {
+        self.min.x.is_finite() && self.min.y.is_finite() && self.min.z.is_finite() &&
+            self.max.x.is_finite() && self.max.y.is_finite() && self.max.z.is_finite()
+    }
+    /// One of the 8 corners, bit `k` of `i` picks `max` over `min` along axis `k`
+    pub fn corner(&self, i: usize) -> Point3f {
+        pt3(
+            if i & 1 == 0 { self.min.x } else { self.max.x },
+            if i & 2 == 0 { self.min.y } else { self.max.y },
+            if i & 4 == 0 { self.min.z } else { self.max.z },
+        )
+    }
+    pub fn diagonal(&self) -> Vector3f { self.max - self.min }
+    pub fn centroid(&self) -> Point3f { self.min.midpoint(self.max) }
+    pub fn surface_area(&self) -> Float {
+        if self.is_empty() { return 0.; }
+        let d = self.diagonal();
+        2. * (d.x * d.y + d.y * d.z + d.z * d.x)
+    }
+    /// Index of the longest axis
+    pub fn max_extent(&self) -> usize {
+        let d = self.diagonal();
+        if d.x > d.y && d.x > d.z { 0 } else if d.y > d.z { 1 } else { 2 }
+    }
+    /// Position of `p` relative to the box, `min` is 0 and `max` is 1
+    pub fn offset(&self, p: Point3f) -> Vector3f {
+        let mut o = p - self.min;
+        for i in 0..3 {
+            if self.max[i] > self.min[i] { o[i] /= self.max[i] - self.min[i]; }
+        }
+        o
+    }
+    /// Center and radius of a sphere enclosing the box
+    pub fn bounding_sphere(&self) -> (Point3f, Float) {
+        if self.is_empty() { return (Point3::origin(), 0.); }
+        let center = self.centroid();
+        (center, (self.max - center).magnitude())
+    }
+    pub fn contains(&self, p: Point3f) -> bool {
+        p.x >= self.min.x && p.x <= self.max.x &&
+            p.y >= self.min.y && p.y <= self.max.y &&
+            p.z >= self.min.z && p.z <= self.max.z
+    }
+
+    /// The overlap of two boxes, empty if they are disjoint
+    pub fn intersection(&self, other: &Self) -> Self {
+        Self {
+            min: pt3(self.min.x.max(other.min.x), self.min.y.max(other.min.y), self.min.z.max(other.min.z)),
+            max: pt3(self.max.x.min(other.max.x), self.max.y.min(other.max.y), self.max.z.min(other.max.z)),
+        }
+    }
+
+    /// Slab test against the ray segment `(ray.t_min, ray.t_max)`, with `inv_dir` = 1 / ray.dir precomputed
+    #[inline]
+    pub fn intersect_p(&self, ray: &Ray, inv_dir: Vector3f) -> bool {
+        self.clip(ray, inv_dir).is_some()
+    }
+
+    /// Clip the ray segment `(ray.t_min, ray.t_max)` to the part inside the box, return `(t0, t1)`
+    #[inline]
+    pub fn clip(&self, ray: &Ray, inv_dir: Vector3f) -> Option<(Float, Float)> {
+        let mut t0 = ray.t_min;
+        let mut t1 = ray.t_max;
+        for i in 0..3 {
+            let mut t_near = (self.min[i] - ray.org[i]) * inv_dir[i];
+            let mut t_far = (self.max[i] - ray.org[i]) * inv_dir[i];
+            if t_near > t_far { std::mem::swap(&mut t_near, &mut t_far); }
+            // NaN (0 * inf) fails the comparisons and keeps the old value
+            if t_near > t0 { t0 = t_near; }
+            if t_far < t1 { t1 = t_far; }
+            if t0 > t1 { return None; }
+        }
+        Some((t0, t1))
+    }
+}
+
+impl TransformAny<Bounds3f> for Matrix4f {
+    /// Bound the transformed 8 corners
+    fn transform(&self, src: &Bounds3f) -> Bounds3f {
+        if !src.is_finite() { return if src.is_empty() { *src } else { Bounds3f::infinite() }; }
+        Bounds3f::from_points((0..8).map(|i| self.transform_point(src.corner(i))))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn union() {
+        let a = Bounds3f::new(pt3(1., 0., 0.), pt3(0., 1., 1.));
+        assert_eq!(a.min, pt3(0., 0., 0.));
+        assert_eq!(a.union(&Bounds3f::empty()), a);
+        assert!(Bounds3f::empty().is_empty());
+        let b = a.union_point(pt3(-1.5, 2., 0.5));
+        assert_eq!(b, Bounds3f::new(pt3(-1.5, 0., 0.), pt3(1., 2., 1.)));
+        assert_eq!(b.max_extent(), 0);
+        assert_eq!(b.surface_area(), 2. * (5. + 2. + 2.5));
+        assert_eq!(b.centroid(), pt3(-0.25, 1., 0.5));
+        assert_eq!(b.offset(pt3(0., 2., 0.)), vec3(0.6, 1., 0.));
+        assert!(b.contains(pt3(0., 0., 0.)) && !b.contains(pt3(0., 0., 2.)));
+        assert_eq!(b.corner(0), b.min);
+        assert_eq!(b.corner(5), pt3(1., 0., 1.));
+        assert_eq!(a.expand(0.5), Bounds3f::new(pt3(-0.5, -0.5, -0.5), pt3(1.5, 1.5, 1.5)));
+        assert!(Bounds3f::empty().expand(1.).is_empty());
+        let (c, r) = a.bounding_sphere();
+        assert_eq!(c, pt3(0.5, 0.5, 0.5));
+        assert_approx!(r, (0.75 as Float).sqrt());
+    }
+
+    #[test]
+    fn transform() {
+        let a = Bounds3f::new(pt3(-1., -1., -1.), pt3(1., 1., 1.));
+        let m = Matrix4::from_translation(vec3(1., 2., 3.)) * Matrix4::from_angle_z(Deg(45.));
+        let b = m.transform(&a);
+        let s = (2. as Float).sqrt();
+        assert_approx!((b.min - pt3(1. - s, 2. - s, 2.)).magnitude(), 0.);
+        assert_approx!((b.max - pt3(1. + s, 2. + s, 4.)).magnitude(), 0.);
+        assert_eq!(m.transform(&Bounds3f::empty()), Bounds3f::empty());
+        let half_space = Bounds3f::new(pt3(Float::neg_infinity(), 0., 0.), pt3(0., 1., 1.));
+        assert_eq!(m.transform(&half_space), Bounds3f::infinite());
+    }
+
+    #[test]
+    fn intersect_p() {
+        let a = Bounds3f::new(pt3(0., 0., 0.), pt3(1., 1., 1.));
+        let hit = |org: Point3f, dir: Vector3f, t_max: Float| {
+            let ray = Ray::new_with_range(org, dir.normalize(), 0., t_max);
+            let inv_dir = vec3(1. / ray.dir.x, 1. / ray.dir.y, 1. / ray.dir.z);
+            a.intersect_p(&ray, inv_dir)
+        };
+        assert!(hit(pt3(-1., 0.5, 0.5), vec3(1., 0., 0.), Float::infinity()));
+        assert!(!hit(pt3(-1., 0.5, 0.5), vec3(1., 0., 0.), 0.5));
+        assert!(!hit(pt3(-1., 0.5, 0.5), vec3(-1., 0., 0.), Float::infinity()));
+        assert!(hit(pt3(0.5, 0.5, 0.5), vec3(0., 1., 0.), Float::infinity())); // from inside
+        assert!(!hit(pt3(-1., 1.5, 0.5), vec3(1., 0., 0.), Float::infinity()));
+        assert!(hit(pt3(-1., -1., -1.), vec3(1., 1., 1.), Float::infinity()));
+        let ray = Ray::new(pt3(-1., 0.5, 0.5), vec3(1., 0., 0.));
+        assert_eq!(a.clip(&ray, vec3(1., Float::infinity(), Float::infinity())), Some((1., 2.)));
+        let b = Bounds3f::new(pt3(0.5, -1., 0.5), pt3(2., 0.5, 2.));
+        assert_eq!(a.intersection(&b), Bounds3f::new(pt3(0.5, 0., 0.5), pt3(1., 0.5, 1.)));
+        assert!(a.intersection(&Bounds3f::from_point(pt3(3., 3., 3.))).is_empty());
+    }
+}
@@ -15,10 +15,45 @@ pub struct GeometryIntersection {
     pub t: Float,
     /// The ray is inside or outside the primitive?
     pub side: Side,
-    // todo transform?
+    /// Surface parameterization
     pub uv: Point2f,
+    /// Partial derivatives of the position w.r.t. `uv`, not normalized
+    pub dpdu: Vector3f,
+    pub dpdv: Vector3f,
+    /// Conservative bound of the absolute floating-point error in `pos`
+    pub p_error: Vector3f,
 }
 
+impl GeometryIntersection {
+    /// Orthonormal shading frame: z-axis is the normal, x-axis follows `dpdu`
+    ///
+    /// Fall back to an arbitrary tangent if `dpdu` is degenerate, e.g. at the poles of a sphere
+    pub fn shading_frame(&self) -> Matrix3f {
+        let tangent = self.dpdu - self.normal * dot(self.normal, self.dpdu);
+        if tangent.magnitude2() < 1e-12 {
+            return onb(self.normal);
+        }
+        let ex = tangent.normalize();
+        Matrix3::from_cols(ex, self.normal.cross(ex), self.normal)
+    }
+
+    /// A ray leaving the surface toward `dir`, its origin is offset so that it cannot hit the surface here again
+    pub fn spawn_ray(&self, dir: Vector3f) -> Ray {
+        Ray::new(offset_ray_origin(self.pos, self.p_error, self.normal, dir), dir)
+    }
+
+    /// A ray segment from the surface to `target`, e.g. a shadow ray, stopping just short of it
+    pub fn spawn_ray_to(&self, target: Point3f) -> Ray {
+        let org = offset_ray_origin(self.pos, self.p_error, self.normal, target - self.pos);
+        let d = target - org;
+        let dist = d.magnitude();
+        Ray::new_with_range(org, d / dist, 0., dist * (1. - SHADOW_EPSILON))
+    }
+}
+
+/// Relative distance short of the target in `spawn_ray_to`
+const SHADOW_EPSILON: Float = 1e-4;
+
 impl<'a, G, B, T> Intersection<'a, G, B, T> where G: Geometry, B: BSDF, T: Texture {
     /// Get the albedo at the intersection pos
     pub fn albedo(&self) -> &Spectrum {
@@ -32,6 +67,8 @@ impl<'a, G, B, T> Intersection<'a, G, B, T> where G: Geometry, B: BSDF, T: Textu
         rec
     }
     pub fn pos(&self) -> Point3f { self.0.pos }
+    /// See `GeometryIntersection::spawn_ray`
+    pub fn spawn_ray(&self, dir: Vector3f) -> Ray { self.0.spawn_ray(dir) }
     pub fn normal(&self) -> Vector3f { self.0.normal }
 }
 
@@ -41,20 +78,35 @@ pub enum Side {
     Inside,
 }
 
-impl TransformAny<GeometryIntersection> for Matrix4f {
+impl TransformAny<GeometryIntersection> for Transform {
+    /// `t` is rescaled to the length of the transformed ray, see `TransformAny<Ray>`
     fn transform(&self, src: &GeometryIntersection) -> GeometryIntersection {
+        // the error carried over, plus the rounding of the transform itself
+        let (m, p, e) = (self.matrix(), src.pos, src.p_error);
+        let abs_row = |i: usize| vec3(m.x[i].abs(), m.y[i].abs(), m.z[i].abs());
+        let p_abs = p.to_vec().map(Float::abs);
+        let p_error = vec3(
+            (gamma(3) + 1.) * dot(abs_row(0), e) + gamma(3) * (dot(abs_row(0), p_abs) + m.w.x.abs()),
+            (gamma(3) + 1.) * dot(abs_row(1), e) + gamma(3) * (dot(abs_row(1), p_abs) + m.w.y.abs()),
+            (gamma(3) + 1.) * dot(abs_row(2), e) + gamma(3) * (dot(abs_row(2), p_abs) + m.w.z.abs()),
+        );
+        let wi = self.transform_vector(src.wi);
+        let scale = wi.magnitude();
         GeometryIntersection {
             pos: self.transform_point(src.pos),
-            normal: self.transform_vector(src.normal),
-            wi: src.wi,
-            t: src.t,
+            normal: self.transform_normal(src.normal).normalize(),
+            wi: wi / scale,
+            t: src.t * scale,
             side: src.side,
             uv: src.uv,
+            dpdu: self.transform_vector(src.dpdu),
+            dpdv: self.transform_vector(src.dpdv),
+            p_error,
         }
     }
 }
 
-impl<'a, G, B, T> TransformAny<Intersection<'a, G, B, T>> for Matrix4f where G: Geometry, B: BSDF, T: Texture {
+impl<'a, G, B, T> TransformAny<Intersection<'a, G, B, T>> for Transform where G: Geometry, B: BSDF, T: Texture {
     #[inline]
     fn transform(&self, src: &Intersection<'a, G, B, T>) -> Intersection<'a, G, B, T> {
         Intersection(self.transform(&src.0), src.1)
@@ -1,10 +1,17 @@
 pub use cgmath::*;
+// the trait's methods stay in scope, its name goes to our `Transform`
+pub use cgmath::Transform as _;
 pub use num_traits::float::{FloatConst, FloatCore};
 
+pub use animated::AnimatedTransform;
+pub use bounds::Bounds3f;
 pub use film::*;
 pub use intersection::*;
 pub use ray::Ray;
+pub use rounding::*;
 pub use spectrum::Spectrum;
+pub use transform::Transform;
+pub(crate) use roots::{solve_quadratic, solve_quartic};
 
 use std::ops::{Add, Sub, Mul};
 use std::cell::UnsafeCell;
@@ -13,6 +20,11 @@ mod ray;
 mod intersection;
 mod spectrum;
 mod film;
+mod bounds;
+mod roots;
+mod animated;
+mod rounding;
+mod transform;
 
 /// Global floating point precision
 #[cfg(feature = "float32")]
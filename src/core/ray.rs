@@ -4,12 +4,29 @@ use super::*;
 pub struct Ray {
     pub org: Point3f,
     pub dir: Vector3f,
+    /// Only hits with `t_min < t < t_max` count
+    pub t_min: Float,
+    pub t_max: Float,
+    /// When the ray is traced, for motion blur
+    pub time: Float,
 }
 
 impl Ray {
     pub fn new(org: Point3f, dir: Vector3f) -> Self {
+        Self::new_with_range(org, dir, 0., Float::infinity())
+    }
+
+    /// Construct a ray segment, e.g. a shadow ray toward a light
+    pub fn new_with_range(org: Point3f, dir: Vector3f, t_min: Float, t_max: Float) -> Self {
         debug_assert_approx!(dir.magnitude(), 1.0);
-        Self { org, dir }
+        debug_assert!(t_min <= t_max);
+        Self { org, dir, t_min, t_max, time: 0. }
+    }
+
+    /// Set the time, e.g. carry it over to secondary rays
+    pub fn with_time(mut self, time: Float) -> Self {
+        self.time = time;
+        self
     }
 
     /// Compute the position after the ray transports `t`
@@ -17,11 +34,19 @@ impl Ray {
 
     /// Move the ray by `t`
     pub fn forward(&mut self, t: Float) { self.org += self.dir * t }
+
+    /// Is `t` within `(t_min, t_max)`?
+    #[inline]
+    pub fn in_range(&self, t: Float) -> bool { t > self.t_min && t < self.t_max }
 }
 
-impl TransformAny<Ray> for Matrix4f {
+impl TransformAny<Ray> for Transform {
+    /// The direction is renormalized, `t_min` and `t_max` are scaled along to cover the same segment
     #[inline]
     fn transform(&self, src: &Ray) -> Ray {
-        Ray::new(self.transform_point(src.org), self.transform_vector(src.dir))
+        let dir = self.transform_vector(src.dir);
+        let scale = dir.magnitude();
+        Ray::new_with_range(self.transform_point(src.org), dir / scale, src.t_min * scale, src.t_max * scale)
+            .with_time(src.time)
     }
 }
@@ -0,0 +1,134 @@
+//! Real roots of low order polynomials, evaluated in `f64` since the coefficients of
+//! ray-surface equations lose precision quickly
+
+/// Roots of `a t^2 + b t + c`, ascending
+pub(crate) fn solve_quadratic(a: f64, b: f64, c: f64) -> Option<(f64, f64)> {
+    if a == 0. { // linear
+        return if b == 0. { None } else { Some((-c / b, -c / b)) };
+    }
+    let discrim = b * b - 4. * a * c;
+    if discrim < 0. { return None; }
+    let root = discrim.sqrt();
+    // avoid cancellation between -b and the root
+    let q = if b < 0. { -0.5 * (b - root) } else { -0.5 * (b + root) };
+    if q == 0. { return Some((0., 0.)); } // b == c == 0
+    let (t0, t1) = (q / a, c / q);
+    Some(if t0 <= t1 { (t0, t1) } else { (t1, t0) })
+}
+
+/// The largest real root of `t^3 + a t^2 + b t + c`
+fn max_cubic_root(a: f64, b: f64, c: f64) -> f64 {
+    let q = (a * a - 3. * b) / 9.;
+    let r = (2. * a * a * a - 9. * a * b + 27. * c) / 54.;
+    let mut t = if r * r < q * q * q { // three real roots, take the largest
+        let theta = (r / (q * q * q).sqrt()).acos();
+        -2. * q.sqrt() * ((theta + 2. * std::f64::consts::PI) / 3.).cos() - a / 3.
+    } else { // one real root
+        let s = -r.signum() * (r.abs() + (r * r - q * q * q).sqrt()).cbrt();
+        s + if s != 0. { q / s } else { 0. } - a / 3.
+    };
+    // polish
+    for _ in 0..2 {
+        let f = ((t + a) * t + b) * t + c;
+        let df = (3. * t + 2. * a) * t + b;
+        if df != 0. { t -= f / df; }
+    }
+    t
+}
+
+/// Roots of `c[0] t^4 + c[1] t^3 + c[2] t^2 + c[3] t + c[4]` by Ferrari's method,
+/// return them ascending in the first `n` slots as `(roots, n)`
+pub(crate) fn solve_quartic(c: [f64; 5]) -> ([f64; 4], usize) {
+    let mut roots = [0.; 4];
+    let mut n = 0;
+    if c[0] == 0. { // degenerated
+        if let Some((t0, t1)) = solve_quadratic(c[2], c[3], c[4]) {
+            roots[0] = t0;
+            roots[1] = t1;
+            n = 2;
+        }
+        return (roots, n);
+    }
+    let (b, cc, d, e) = (c[1] / c[0], c[2] / c[0], c[3] / c[0], c[4] / c[0]);
+    // depressed quartic y^4 + p y^2 + q y + r, with t = y - b / 4
+    let b2 = b * b;
+    let p = cc - 0.375 * b2;
+    let q = d - 0.5 * b * cc + 0.125 * b2 * b;
+    let r = e - 0.25 * b * d + b2 * cc / 16. - 3. * b2 * b2 / 256.;
+    let mut push_quadratic = |qb: f64, qc: f64| {
+        if let Some((y0, y1)) = solve_quadratic(1., qb, qc) {
+            roots[n] = y0;
+            roots[n + 1] = y1;
+            n += 2;
+        }
+    };
+    if q.abs() < 1e-12 { // biquadratic, z = y^2
+        if let Some((z0, z1)) = solve_quadratic(1., p, r) {
+            for &z in &[z0, z1] {
+                if z >= 0. { push_quadratic(0., -z); }
+            }
+        }
+    } else {
+        // y^4 + p y^2 + q y + r = (y^2 + p / 2 + m)^2 - 2m (y - q / 4m)^2, with m > 0 from the resolvent cubic
+        let m = max_cubic_root(p, 0.25 * p * p - r, -0.125 * q * q);
+        if m <= 0. { return (roots, 0); }
+        let s = (2. * m).sqrt();
+        push_quadratic(-s, 0.5 * p + m + 0.5 * q / s);
+        push_quadratic(s, 0.5 * p + m - 0.5 * q / s);
+    }
+    for t in &mut roots[..n] {
+        *t -= 0.25 * b;
+        // polish on the original polynomial
+        for _ in 0..2 {
+            let f = (((c[0] * *t + c[1]) * *t + c[2]) * *t + c[3]) * *t + c[4];
+            let df = ((4. * c[0] * *t + 3. * c[1]) * *t + 2. * c[2]) * *t + c[3];
+            if df != 0. { *t -= f / df; }
+        }
+    }
+    roots[..n].sort_unstable_by(|a, b| a.partial_cmp(b).unwrap());
+    (roots, n)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn quadratic() {
+        assert_eq!(solve_quadratic(1., -3., 2.), Some((1., 2.)));
+        assert_eq!(solve_quadratic(-1., 3., -2.), Some((1., 2.)));
+        assert_eq!(solve_quadratic(1., 0., 1.), None);
+        assert_eq!(solve_quadratic(0., 2., -1.), Some((0.5, 0.5)));
+        assert_eq!(solve_quadratic(0., 0., 1.), None);
+    }
+
+    #[test]
+    fn quartic() {
+        let check = |expected: &[f64]| {
+            // expand prod (t - r_i)
+            let mut c = vec![1.];
+            for &r in expected {
+                let mut next = vec![0.; c.len() + 1];
+                for (i, &ci) in c.iter().enumerate() {
+                    next[i] += ci;
+                    next[i + 1] -= ci * r;
+                }
+                c = next;
+            }
+            let (roots, n) = solve_quartic([c[0], c[1], c[2], c[3], c[4]]);
+            assert_eq!(n, 4);
+            for (a, b) in roots.iter().zip(expected) {
+                assert!((a - b).abs() < 1e-6, "{:?} != {:?}", roots, expected);
+            }
+        };
+        check(&[-3., -1., 2., 5.]);
+        check(&[-2., -1., 1., 2.]); // biquadratic
+        check(&[0.1, 0.2, 7., 30.]);
+        check(&[-100., 0.5, 0.6, 1000.]);
+        // (t^2 + 1) (t - 1) (t - 2) = t^4 - 3t^3 + 3t^2 - 3t + 2
+        let (roots, n) = solve_quartic([1., -3., 3., -3., 2.]);
+        assert_eq!(n, 2);
+        assert!((roots[0] - 1.).abs() < 1e-9 && (roots[1] - 2.).abs() < 1e-9);
+        assert_eq!(solve_quartic([1., 0., 2., 0., 1.]).1, 0); // (t^2 + 1)^2
+    }
+}
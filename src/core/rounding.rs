@@ -0,0 +1,77 @@
+//! Floating-point error bounds, for spawning rays without hitting the surface they leave
+
+use super::*;
+
+/// Half of `Float::epsilon()`, bounds the relative error of one rounding
+pub const MACHINE_EPSILON: Float = Float::EPSILON * 0.5;
+
+/// Bound of the relative error after `n` roundings, `n eps / (1 - n eps)`
+#[inline]
+pub fn gamma(n: i32) -> Float {
+    let n_eps = n as Float * MACHINE_EPSILON;
+    n_eps / (1. - n_eps)
+}
+
+/// The smallest float greater than `v`
+#[inline]
+pub fn next_float_up(v: Float) -> Float {
+    if v.is_infinite() && v > 0. { return v; }
+    let v = if v == -0. { 0. } else { v };
+    let bits = v.to_bits();
+    Float::from_bits(if v >= 0. { bits + 1 } else { bits - 1 })
+}
+
+/// The greatest float less than `v`
+#[inline]
+pub fn next_float_down(v: Float) -> Float {
+    if v.is_infinite() && v < 0. { return v; }
+    let v = if v == 0. { -0. } else { v };
+    let bits = v.to_bits();
+    Float::from_bits(if v > 0. { bits - 1 } else { bits + 1 })
+}
+
+/// Move `p`, whose components are off by at most `p_error`, along the normal `n` to the side of `w`,
+/// far enough that a ray from it toward `w` cannot hit the surface at `p` again
+pub fn offset_ray_origin(p: Point3f, p_error: Vector3f, n: Vector3f, w: Vector3f) -> Point3f {
+    let d = dot(n.map(Float::abs), p_error);
+    let offset = if dot(w, n) < 0. { -n * d } else { n * d };
+    let mut po = p + offset;
+    // round away from p
+    for i in 0..3 {
+        if offset[i] > 0. {
+            po[i] = next_float_up(po[i]);
+        } else if offset[i] < 0. {
+            po[i] = next_float_down(po[i]);
+        }
+    }
+    po
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn next_float() {
+        assert_eq!(next_float_up(1.), 1. + Float::epsilon());
+        assert_eq!(next_float_down(1.), 1. - Float::epsilon() / 2.);
+        assert!(next_float_up(0.) > 0.);
+        assert!(next_float_down(0.) < 0.);
+        assert!(next_float_up(-0.) > 0.);
+        assert!(next_float_up(-1.) > -1.);
+        assert!(next_float_down(-1.) < -1.);
+        assert_eq!(next_float_up(Float::infinity()), Float::infinity());
+        assert!(gamma(3) > 3. * MACHINE_EPSILON);
+    }
+
+    #[test]
+    fn offset() {
+        let n = vec3(0., 0., 1.);
+        let p = offset_ray_origin(pt3(1., 1., 0.), vec3(1e-3, 1e-3, 1e-3), n, vec3(0., 1., 1.).normalize());
+        assert_eq!((p.x, p.y), (1., 1.));
+        assert!(p.z > 1e-3);
+        let p = offset_ray_origin(pt3(1., 1., 0.), vec3(1e-3, 1e-3, 1e-3), n, vec3(0., 1., -1.).normalize());
+        assert!(p.z < -1e-3);
+        assert_eq!(offset_ray_origin(pt3(1., 1., 1.), Vector3::zero(), n, n), pt3(1., 1., 1.));
+    }
+}
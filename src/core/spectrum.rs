@@ -16,6 +16,8 @@ impl Spectrum {
     pub fn max(&self) -> Float { self.r.max(self.g).max(self.b) }
     pub fn min(&self) -> Float { self.r.min(self.g).min(self.b) }
     pub fn sum(&self) -> Float { self.r + self.g + self.b }
+    /// Component-wise exponential, e.g. of an absorption
+    pub fn exp(&self) -> Self { Self::new(self.r.exp(), self.g.exp(), self.b.exp()) }
 }
 
 impl From<Vector3f> for Spectrum {
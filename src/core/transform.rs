@@ -0,0 +1,126 @@
+use super::*;
+
+/// Affine transform with its inverse cached
+///
+/// Points and vectors go through the matrix, normals through its inverse transpose,
+/// so that they stay perpendicular to the surface under non-uniform scaling.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Transform {
+    m: Matrix4f,
+    m_inv: Matrix4f,
+}
+
+impl Transform {
+    /// Panic if `m` is singular
+    pub fn new(m: Matrix4f) -> Self {
+        let m_inv = m.inverse_transform()
+            .unwrap_or_else(|| panic!("Singular transform {:?}", m));
+        Self { m, m_inv }
+    }
+
+    /// From a matrix and its inverse known by other means, e.g. put together from simple parts
+    pub fn with_inverse(m: Matrix4f, m_inv: Matrix4f) -> Self {
+        Self { m, m_inv }
+    }
+
+    pub fn identity() -> Self {
+        Self { m: Matrix4::identity(), m_inv: Matrix4::identity() }
+    }
+
+    #[inline]
+    pub fn matrix(&self) -> &Matrix4f { &self.m }
+
+    #[inline]
+    pub fn inverse_matrix(&self) -> &Matrix4f { &self.m_inv }
+
+    /// The inverse transform, without inverting again
+    #[inline]
+    pub fn inverse(&self) -> Self {
+        Self { m: self.m_inv, m_inv: self.m }
+    }
+
+    #[inline]
+    pub fn transform_point(&self, p: Point3f) -> Point3f { self.m.transform_point(p) }
+
+    #[inline]
+    pub fn transform_vector(&self, v: Vector3f) -> Vector3f { self.m.transform_vector(v) }
+
+    /// Apply the inverse transpose, the result is not normalized
+    #[inline]
+    pub fn transform_normal(&self, n: Vector3f) -> Vector3f {
+        let m = &self.m_inv;
+        vec3(
+            m.x.x * n.x + m.x.y * n.y + m.x.z * n.z,
+            m.y.x * n.x + m.y.y * n.y + m.y.z * n.z,
+            m.z.x * n.x + m.z.y * n.y + m.z.z * n.z,
+        )
+    }
+}
+
+impl From<Matrix4f> for Transform {
+    #[inline]
+    fn from(m: Matrix4f) -> Self { Self::new(m) }
+}
+
+impl Mul for Transform {
+    type Output = Transform;
+
+    /// Apply `rhs` first
+    #[inline]
+    fn mul(self, rhs: Transform) -> Transform {
+        Self { m: self.m * rhs.m, m_inv: rhs.m_inv * self.m_inv }
+    }
+}
+
+impl TransformAny<Point3f> for Transform {
+    #[inline]
+    fn transform(&self, src: &Point3f) -> Point3f { self.transform_point(*src) }
+}
+
+impl TransformAny<Vector3f> for Transform {
+    #[inline]
+    fn transform(&self, src: &Vector3f) -> Vector3f { self.transform_vector(*src) }
+}
+
+impl TransformAny<Bounds3f> for Transform {
+    #[inline]
+    fn transform(&self, src: &Bounds3f) -> Bounds3f { self.m.transform(src) }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::macros::*;
+
+    #[test]
+    fn inverse() {
+        let m = Matrix4::from_translation(vec3(1., 2., 3.)) *
+            Matrix4::from_angle_y(Deg(30.)) *
+            Matrix4::from_nonuniform_scale(2., 1., 0.5);
+        let t = Transform::new(m);
+        let p = pt3(0.3, -2., 5.);
+        assert_approx!((t.inverse().transform_point(t.transform_point(p)) - p).magnitude(), 0.);
+        let tt = t * t.inverse();
+        assert_approx!((tt.transform_point(p) - p).magnitude(), 0.);
+        assert_approx!((tt.inverse_matrix().transform_point(p) - p).magnitude(), 0.);
+        assert_eq!(Transform::identity().transform(&p), p);
+    }
+
+    #[test]
+    fn normal() {
+        // the plane x + y = 0, squashed along x
+        let t = Transform::new(Matrix4::from_nonuniform_scale(0.5, 1., 1.));
+        let n = t.transform_normal(vec3(1., 1., 0.)).normalize();
+        let tangent = t.transform_vector(vec3(1., -1., 0.));
+        assert_approx!(dot(n, tangent), 0.);
+        assert_approx!((n - vec3(2., 1., 0.).normalize()).magnitude(), 0.);
+        // a plain vector would not be perpendicular
+        assert_gt!(dot(t.transform_vector(vec3(1., 1., 0.)), tangent).abs(), 0.1);
+    }
+
+    #[test]
+    #[should_panic]
+    fn singular() {
+        Transform::new(Matrix4::from_nonuniform_scale(1., 0., 1.));
+    }
+}
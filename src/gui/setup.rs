@@ -32,17 +32,28 @@ pub fn setup_window() -> Window {
 //  };
 
 pub fn setup_scene_cornell() -> Scene<impl Geometry, impl BSDF, impl Texture> {
-    let radius = [1e3, 1e3, 1e3, 1e3, 1e3, 1e3, 16.5, 16.5, 600.];
-    let position = [
-        [1e3 + 1., 40.8, 81.6],
-        [-1e3 + 99., 40.8, 81.6],
-        [50., 40.8, 1e3],
-        [50., 40.8, -1e3 + 370.],
-        [50., 1e3, 81.6],
-        [50., -1e3 + 81.6, 81.6],
-        [27., 16.5, 47.],
-        [73., 16.5, 78.],
-        [50., 681.6 - 0.27, 81.6], // Light
+    // walls are slightly oversized to leave no gaps at the corners
+    let geometry: [DynamicGeometry; 9] = [
+        Rectangle::new(372., 83.).into(),
+        Rectangle::new(372., 83.).into(),
+        Rectangle::new(100., 83.).into(),
+        Rectangle::new(100., 83.).into(),
+        Rectangle::new(100., 372.).into(),
+        Rectangle::new(100., 372.).into(),
+        Sphere::new(16.5).into(),
+        Sphere::new(16.5).into(),
+        Disk::new(18.).into(),
+    ];
+    let transform = [
+        Matrix4::from_translation(vec3(1., 40.8, 185.)) * Matrix4::from_angle_y(Deg(90.)),
+        Matrix4::from_translation(vec3(99., 40.8, 185.)) * Matrix4::from_angle_y(Deg(90.)),
+        Matrix4::from_translation(vec3(50., 40.8, 0.)),
+        Matrix4::from_translation(vec3(50., 40.8, 370.)),
+        Matrix4::from_translation(vec3(50., 0., 185.)) * Matrix4::from_angle_x(Deg(90.)),
+        Matrix4::from_translation(vec3(50., 81.6, 185.)) * Matrix4::from_angle_x(Deg(90.)),
+        Matrix4::from_translation(vec3(27., 16.5, 47.)),
+        Matrix4::from_translation(vec3(73., 16.5, 78.)),
+        Matrix4::from_translation(vec3(50., 81.6 - 0.01, 81.6)) * Matrix4::from_angle_x(Deg(90.)), // Light, facing down
     ];
     let emission = [
         [0., 0., 0.],
@@ -80,17 +91,18 @@ pub fn setup_scene_cornell() -> Scene<impl Geometry, impl BSDF, impl Texture> {
     ];
 
     let mut scene = Scene::new();
-    for i in 0..radius.len() {
+    for i in 0..geometry.len() {
         scene.push(Primitive::new(
-            Sphere::new(radius[i]),
+            geometry[i].clone(),
             Arc::new(Material {
                 bsdf: mater[i].clone(),
                 texture: texture::Uniform(Spectrum::new(color[i][0], color[i][1], color[i][2])),
                 emission: Spectrum::new(emission[i][0], emission[i][1], emission[i][2]),
             }),
-            Matrix4::from_translation(position[i].into()),
+            transform[i],
         ))
     };
+    scene.build_bvh();
     scene
 }
 
@@ -123,6 +135,7 @@ pub fn setup_scene() -> Scene<impl Geometry, impl BSDF, impl Texture> {
         Sphere::new(0.1),
         Arc::new(Material { bsdf: bsdf::Simple::default(), texture: texture::Uniform(Spectrum::new(1., 1., 1.)), emission: Spectrum::new(0.5, 0.2, 0.5) }),
         Matrix4::from_translation(vec3(0., 0., 0.))));
+    scene.build_bvh();
     scene
 }
 
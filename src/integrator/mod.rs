@@ -43,7 +43,7 @@ impl<D> Integrator for SampleIntegrator<D> where D: SampleIntegratorDelegate + D
                 let film = &mut *(*film.get_raw_mut() as *mut Film); // todo: this is too ugly...
                 for x in 0..width {
                     let acc = film.at_unchecked_mut(x, y);
-                    let (ray, pdf) = camera.generate_ray(x, y, sampler.next2d());
+                    let (ray, pdf) = camera.generate_ray_with_time(x, y, sampler.next2d(), sampler.next());
                     let mut radiance = self.delegate.Li(ray, scene, &mut sampler);
                     radiance /= pdf;
                     // accumulate pixel value
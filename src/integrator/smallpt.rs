@@ -21,6 +21,9 @@ impl SampleIntegratorDelegate for SmallPT {
                     radiance += &throughput * its.emission();
                     // do bsdf sampling:
                     let b_rec = its.sample_bsdf(sampler.next2d());
+                    if b_rec.pdf <= 0. { // e.g. grazing, nothing to carry on
+                        break;
+                    }
                     throughput *= b_rec.weight / b_rec.pdf;
 
                     let P = throughput.max();
@@ -32,9 +35,8 @@ impl SampleIntegratorDelegate for SmallPT {
                         }
                     }
 
-                    // forward ray to the next intersection
-                    ray = Ray::new(its.pos(), b_rec.wo);
-                    ray.forward(Float::epsilon());
+                    // leave the surface toward the next intersection
+                    ray = its.spawn_ray(b_rec.wo).with_time(ray.time);
                     depth += 1;
                 },
             }
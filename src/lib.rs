@@ -2,15 +2,18 @@
 mod macros;
 
 mod core;
+mod accel;
 mod primitive;
 mod camera;
 pub mod utils;
 mod scene;
 pub mod sampler;
 pub mod integrator;
+pub mod loader;
 mod gui;
 
 pub use self::core::*;
+pub use accel::*;
 pub use scene::*;
 pub use camera::*;
 pub use primitive::*;
@@ -0,0 +1,52 @@
+//! Grayscale images as terrain, 8 or 16 bits per channel, in any format the `image` crate reads
+
+use super::*;
+use image::{DynamicImage, GenericImageView, ImageError};
+
+/// Load a grayscale image as a heightfield spanning `extent`, see `Heightfield`
+///
+/// The top row of the image is at `y = extent.y`, so the terrain looks like the image seen from above,
+/// and white is at `z = extent.z`. Colored images are converted to luma.
+pub fn load_heightfield(path: impl AsRef<Path>, extent: Vector3f) -> LoadResult<Heightfield> {
+    let path = path.as_ref();
+    let img = image::open(path).map_err(|e| match e {
+        ImageError::IoError(e) => LoadError::Io(path.to_owned(), e),
+        e => LoadError::Parse { source: path.display().to_string(), line: 0, message: e.to_string() },
+    })?;
+    heightfield_from_image(&img, extent).map_err(|message| LoadError::Parse { source: path.display().to_string(), line: 0, message })
+}
+
+/// Heights from the luma of `img`, normalized to `[0, 1]`
+pub fn heightfield_from_image(img: &DynamicImage, extent: Vector3f) -> Result<Heightfield, String> {
+    let (nx, ny) = (img.width() as usize, img.height() as usize);
+    if nx < 2 || ny < 2 {
+        return Err(format!("heightfield needs at least 2x2 pixels, got {}x{}", nx, ny));
+    }
+    Ok(Heightfield::new(nx, ny, texture::FloatImage::from_image(img).into_values(), extent))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn load() {
+        let path = std::env::temp_dir().join("pharosa-heightfield-test.png");
+        // a ramp rising toward the top of the image
+        image::GrayImage::from_fn(3, 5, |_, y| image::Luma([(255 - y * 60) as u8])).save(&path).unwrap();
+        let hf = load_heightfield(&path, vec3(2., 4., 1.)).unwrap();
+        assert_eq!((hf.nx(), hf.ny()), (3, 5));
+        assert_eq!(hf.height(1, 4), 1.);
+        assert_eq!(hf.height(1, 0), 15. / 255.);
+        let its = hf.intersect(&Ray::new(pt3(1., 4., 2.), vec3(0., 0., -1.))).unwrap();
+        assert_approx!(its.pos.z, 1.);
+
+        let path = std::env::temp_dir().join("pharosa-heightfield-test-tiny.png");
+        image::GrayImage::new(1, 5).save(&path).unwrap();
+        match load_heightfield(&path, vec3(1., 1., 1.)) {
+            Err(LoadError::Parse { message, .. }) => assert!(message.contains("2x2")),
+            r => panic!("{:?}", r.map(|_| ())),
+        }
+        assert!(matches!(load_heightfield("/nonexistent.png", vec3(1., 1., 1.)), Err(LoadError::Io(..))));
+    }
+}
@@ -0,0 +1,84 @@
+//! Import scenes and geometries from external file formats
+
+use crate::core::*;
+use crate::primitive::*;
+use crate::scene::Scene;
+use std::fmt::{self, Display, Formatter};
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub mod obj;
+pub mod ply;
+pub mod heightfield;
+pub mod strands;
+
+pub use obj::{load_obj, load_obj_subdivided};
+pub use ply::load_ply;
+pub use heightfield::load_heightfield;
+pub use strands::load_strands;
+
+#[derive(Debug)]
+pub enum LoadError {
+    /// Failed to open or read a file
+    Io(PathBuf, io::Error),
+    /// Malformed content at a 1-based `line` of `source`, `line == 0` if unknown (e.g. binary data)
+    Parse { source: String, line: usize, message: String },
+}
+
+pub type LoadResult<T> = Result<T, LoadError>;
+
+impl Display for LoadError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            LoadError::Parse { source, line: 0, message } => write!(f, "{}: {}", source, message),
+            LoadError::Parse { source, line, message } => write!(f, "{}:{}: {}", source, line, message),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LoadError::Io(_, e) => Some(e),
+            LoadError::Parse { .. } => None,
+        }
+    }
+}
+
+/// Tracks the current position for line-numbered errors
+pub(crate) struct Cursor<'a> {
+    pub source: &'a str,
+    pub line: usize,
+    /// Prefixed to error messages, e.g. the element being read
+    context: String,
+}
+
+impl<'a> Cursor<'a> {
+    pub fn new(source: &'a str) -> Self { Self { source, line: 0, context: String::new() } }
+
+    pub fn set_context(&mut self, context: String) { self.context = context; }
+
+    pub fn error<T>(&self, message: impl Into<String>) -> LoadResult<T> {
+        let message = if self.context.is_empty() {
+            message.into()
+        } else {
+            format!("{}: {}", self.context, message.into())
+        };
+        Err(LoadError::Parse { source: self.source.into(), line: self.line, message })
+    }
+
+    /// Parse the next token as a number
+    pub fn parse_next<'t, N: std::str::FromStr>(&self, tokens: &mut impl Iterator<Item=&'t str>, what: &str) -> LoadResult<N> {
+        match tokens.next() {
+            None => self.error(format!("missing {}", what)),
+            Some(tok) => tok.parse().or_else(|_| self.error(format!("invalid {} '{}'", what, tok))),
+        }
+    }
+}
+
+pub(crate) fn open(path: &Path) -> LoadResult<io::BufReader<std::fs::File>> {
+    std::fs::File::open(path)
+        .map(io::BufReader::new)
+        .map_err(|e| LoadError::Io(path.to_owned(), e))
+}
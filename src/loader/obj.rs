@@ -0,0 +1,451 @@
+//! Wavefront OBJ + MTL
+//!
+//! Each `o`/`g` group and `usemtl` section becomes one `Mesh` primitive. Materials map onto `bsdf::Simple`:
+//!
+//! - `illum 3, 5`: `Specular`, tinted by `Ks`
+//! - `illum 4, 6, 7`: `Dielectric` with `Ni`, tinted by `Tf`
+//! - otherwise: `Diffuse`, tinted by `Kd`
+//!
+//! and `Ke` is taken as the emission.
+//!
+//! Meshes can be smoothed by Loop subdivision on loading, see `Mesh::subdivide`. The faces are then joined by
+//! their positions alone, the normals of the file are dropped, and so are the texture coordinates if they have
+//! seams. The edges between objects or materials are open boundaries.
+
+use super::*;
+use bsdf::simple::*;
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::sync::Arc;
+
+pub type ObjScene = Scene<Mesh, Simple, texture::Uniform>;
+pub type ObjMaterial = Material<Simple, texture::Uniform>;
+
+/// Load an `.obj` file together with the `.mtl` libraries it refers to
+pub fn load_obj(path: impl AsRef<Path>) -> LoadResult<ObjScene> {
+    load_obj_subdivided(path, 0)
+}
+
+/// Load an `.obj` file as control meshes, each smoothed by `levels` steps of subdivision
+pub fn load_obj_subdivided(path: impl AsRef<Path>, levels: u32) -> LoadResult<ObjScene> {
+    let path = path.as_ref();
+    let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+    parse_obj_subdivided(open(path)?, &path.display().to_string(), base_dir, levels)
+}
+
+/// Parse OBJ content, `mtllib`s are resolved relative to `base_dir`
+pub fn parse_obj(reader: impl BufRead, source: &str, base_dir: &Path) -> LoadResult<ObjScene> {
+    parse_obj_subdivided(reader, source, base_dir, 0)
+}
+
+/// Parse OBJ content, subdividing each mesh `levels` times, no subdivision if 0
+pub fn parse_obj_subdivided(reader: impl BufRead, source: &str, base_dir: &Path, levels: u32) -> LoadResult<ObjScene> {
+    let mut cur = Cursor::new(source);
+    let mut positions: Vec<Point3f> = Vec::new();
+    let mut normals: Vec<Vector3f> = Vec::new();
+    let mut uvs: Vec<Point2f> = Vec::new();
+    let mut materials: HashMap<String, Arc<ObjMaterial>> = HashMap::new();
+    let default_material = Arc::new(MtlDesc::default().into_material());
+
+    let mut scene = Scene::new();
+    let mut builder = MeshBuilder::new("default".into(), "default".into(), default_material);
+
+    for line in reader.lines() {
+        cur.line += 1;
+        let line = line.map_err(|e| LoadError::Io(source.into(), e))?;
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            None => continue,
+            Some(k) if k.starts_with('#') => continue,
+            Some(k) => k,
+        };
+        match keyword {
+            "v" => positions.push(pt3(
+                cur.parse_next(&mut tokens, "x")?,
+                cur.parse_next(&mut tokens, "y")?,
+                cur.parse_next(&mut tokens, "z")?,
+            )),
+            "vn" => normals.push(vec3(
+                cur.parse_next(&mut tokens, "x")?,
+                cur.parse_next(&mut tokens, "y")?,
+                cur.parse_next(&mut tokens, "z")?,
+            )),
+            "vt" => uvs.push(pt2(
+                cur.parse_next(&mut tokens, "u")?,
+                tokens.next().map_or(Ok(0.), |v| v.parse().or_else(|_| cur.error(format!("invalid v '{}'", v))))?,
+            )),
+            "f" => {
+                let mut corners = Vec::with_capacity(4);
+                for tok in tokens {
+                    corners.push(parse_corner(&cur, tok, positions.len(), uvs.len(), normals.len())?);
+                }
+                if corners.len() < 3 {
+                    return cur.error(format!("face has {} vertices, at least 3 expected", corners.len()));
+                }
+                let corners: Vec<u32> = corners.into_iter()
+                    .map(|c| builder.vertex(c, &positions, &uvs, &normals))
+                    .collect();
+                for i in 1..corners.len() - 1 { // triangulate as a fan
+                    builder.indices.push([corners[0], corners[i], corners[i + 1]]);
+                }
+            }
+            "o" | "g" => {
+                let name = tokens.collect::<Vec<_>>().join(" ");
+                let material = builder.material.clone();
+                let mtl_name = builder.mtl_name.clone();
+                builder.flush_into(&mut scene, levels);
+                builder = MeshBuilder::new(name, mtl_name, material);
+            }
+            "usemtl" => {
+                let mtl_name = tokens.collect::<Vec<_>>().join(" ");
+                let material = match materials.get(&mtl_name) {
+                    None => return cur.error(format!("undefined material '{}'", mtl_name)),
+                    Some(m) => m.clone(),
+                };
+                let name = builder.name.clone();
+                builder.flush_into(&mut scene, levels);
+                builder = MeshBuilder::new(name, mtl_name, material);
+            }
+            "mtllib" => {
+                for file in tokens {
+                    let path = base_dir.join(file);
+                    materials.extend(parse_mtl(open(&path)?, &path.display().to_string())?);
+                }
+            }
+            _ => {} // s, l, p, curves, ... are not supported
+        }
+    }
+    builder.flush_into(&mut scene, levels);
+    scene.build_bvh();
+    Ok(scene)
+}
+
+/// Parse MTL content into named materials
+pub fn parse_mtl(reader: impl BufRead, source: &str) -> LoadResult<HashMap<String, Arc<ObjMaterial>>> {
+    let mut cur = Cursor::new(source);
+    let mut descs: Vec<(String, MtlDesc)> = Vec::new();
+    for line in reader.lines() {
+        cur.line += 1;
+        let line = line.map_err(|e| LoadError::Io(source.into(), e))?;
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            None => continue,
+            Some(k) if k.starts_with('#') => continue,
+            Some(k) => k,
+        };
+        if keyword == "newmtl" {
+            let name = tokens.collect::<Vec<_>>().join(" ");
+            if name.is_empty() { return cur.error("missing material name"); }
+            descs.push((name, MtlDesc::default()));
+            continue;
+        }
+        let desc = match descs.last_mut() {
+            None => return cur.error(format!("'{}' before any 'newmtl'", keyword)),
+            Some((_, desc)) => desc,
+        };
+        match keyword {
+            "Kd" => desc.kd = parse_color(&cur, &mut tokens)?,
+            "Ks" => desc.ks = parse_color(&cur, &mut tokens)?,
+            "Ke" => desc.ke = parse_color(&cur, &mut tokens)?,
+            "Tf" => desc.tf = parse_color(&cur, &mut tokens)?,
+            "Ni" => desc.ni = cur.parse_next(&mut tokens, "Ni")?,
+            "illum" => desc.illum = cur.parse_next(&mut tokens, "illum")?,
+            _ => {} // Ka, Ns, d, map_*, ... are not supported
+        }
+    }
+    Ok(descs.into_iter().map(|(name, desc)| (name, Arc::new(desc.into_material()))).collect())
+}
+
+/// `r [g b]`, a single value means gray
+fn parse_color<'t>(cur: &Cursor, tokens: &mut impl Iterator<Item=&'t str>) -> LoadResult<Spectrum> {
+    let r = cur.parse_next(tokens, "r")?;
+    match tokens.next() {
+        None => Ok(Spectrum::uniform(r)),
+        Some(g) => Ok(Spectrum::new(
+            r,
+            g.parse().or_else(|_| cur.error(format!("invalid g '{}'", g)))?,
+            cur.parse_next(tokens, "b")?,
+        )),
+    }
+}
+
+/// Zero-based `(v, vt, vn)` of a face corner `v[/vt][/vn]`
+type Corner = (usize, Option<usize>, Option<usize>);
+
+fn parse_corner(cur: &Cursor, tok: &str, n_v: usize, n_vt: usize, n_vn: usize) -> LoadResult<Corner> {
+    let resolve = |s: &str, n: usize, what: &str| -> LoadResult<usize> {
+        let i: i64 = s.parse().or_else(|_| cur.error(format!("invalid {} index '{}'", what, s)))?;
+        // 1-based, negative ones are relative to the end
+        let i = if i < 0 { n as i64 + i } else { i - 1 };
+        if i < 0 || i >= n as i64 {
+            cur.error(format!("{} index {} out of range [1, {}]", what, s, n))
+        } else {
+            Ok(i as usize)
+        }
+    };
+    let mut parts = tok.split('/');
+    let v = resolve(parts.next().unwrap(), n_v, "vertex")?;
+    let vt = match parts.next() {
+        None | Some("") => None,
+        Some(s) => Some(resolve(s, n_vt, "texture")?),
+    };
+    let vn = match parts.next() {
+        None | Some("") => None,
+        Some(s) => Some(resolve(s, n_vn, "normal")?),
+    };
+    if parts.next().is_some() {
+        return cur.error(format!("invalid face vertex '{}'", tok));
+    }
+    Ok((v, vt, vn))
+}
+
+#[derive(Debug, Clone)]
+struct MtlDesc {
+    kd: Spectrum,
+    ks: Spectrum,
+    ke: Spectrum,
+    tf: Spectrum,
+    ni: Float,
+    illum: u32,
+}
+
+impl Default for MtlDesc {
+    fn default() -> Self {
+        Self {
+            kd: Spectrum::uniform(0.8),
+            ks: Spectrum::black(),
+            ke: Spectrum::black(),
+            tf: Spectrum::white(),
+            ni: 1.5,
+            illum: 2,
+        }
+    }
+}
+
+impl MtlDesc {
+    fn into_material(self) -> ObjMaterial {
+        let (bsdf, color) = match self.illum {
+            3 | 5 => (Specular.into(), self.ks),
+            4 | 6 | 7 => (Dielectric { n: self.ni }.into(), self.tf),
+            _ => (Diffuse.into(), self.kd),
+        };
+        Material { bsdf, texture: texture::Uniform(color), emission: self.ke }
+    }
+}
+
+/// Collects the faces of one object + material pair
+struct MeshBuilder {
+    name: String,
+    mtl_name: String,
+    material: Arc<ObjMaterial>,
+    positions: Vec<Point3f>,
+    /// OBJ position index of each local vertex
+    position_ids: Vec<usize>,
+    uvs: Vec<Option<Point2f>>,
+    normals: Vec<Option<Vector3f>>,
+    indices: Vec<[u32; 3]>,
+    /// OBJ corner to local vertex index
+    vertex_map: HashMap<Corner, u32>,
+}
+
+impl MeshBuilder {
+    fn new(name: String, mtl_name: String, material: Arc<ObjMaterial>) -> Self {
+        Self {
+            name,
+            mtl_name,
+            material,
+            positions: Vec::new(),
+            position_ids: Vec::new(),
+            uvs: Vec::new(),
+            normals: Vec::new(),
+            indices: Vec::new(),
+            vertex_map: HashMap::new(),
+        }
+    }
+
+    fn vertex(&mut self, corner: Corner, positions: &[Point3f], uvs: &[Point2f], normals: &[Vector3f]) -> u32 {
+        let next = self.positions.len() as u32;
+        let index = *self.vertex_map.entry(corner).or_insert(next);
+        if index == next {
+            let (v, vt, vn) = corner;
+            self.positions.push(positions[v]);
+            self.position_ids.push(v);
+            self.uvs.push(vt.map(|i| uvs[i]));
+            self.normals.push(vn.map(|i| normals[i]));
+        }
+        index
+    }
+
+    /// Push the collected faces as a primitive, if any, subdivided `levels` times
+    fn flush_into(self, scene: &mut ObjScene, levels: u32) {
+        if self.indices.is_empty() { return; }
+        let mesh = if levels > 0 {
+            // weld the corners split by their uvs or normals, not to tear the surface apart
+            let mut welded = HashMap::new();
+            let mut positions = Vec::new();
+            let local_to_welded: Vec<u32> = self.position_ids.iter().zip(&self.positions).map(|(&id, &p)| {
+                *welded.entry(id).or_insert_with(|| {
+                    positions.push(p);
+                    positions.len() as u32 - 1
+                })
+            }).collect();
+            // the uvs are kept if there are no seams
+            let mut uvs = vec![None; positions.len()];
+            let mut seamless = true;
+            for (&w, &uv) in local_to_welded.iter().zip(&self.uvs) {
+                match uv {
+                    None => seamless = false,
+                    Some(uv) => seamless &= *uvs[w as usize].get_or_insert(uv) == uv,
+                }
+            }
+            let uvs: Option<Vec<Point2f>> = if seamless { uvs.into_iter().collect() } else { None };
+            let indices: Vec<_> = self.indices.iter().map(|face| face.map(|i| local_to_welded[i as usize])).collect();
+            Mesh::subdivide(&positions, uvs.as_deref(), &indices, &[], levels)
+        } else {
+            let mut mesh = Mesh::new(self.positions, self.indices);
+            // attributes are kept only when every vertex has them
+            if let Some(uvs) = self.uvs.into_iter().collect() {
+                mesh.set_uvs(uvs);
+            }
+            if let Some(normals) = self.normals.into_iter().collect() {
+                mesh.set_normals(normals);
+            }
+            mesh
+        };
+        let label = format!("{} ({})", self.name, self.mtl_name);
+        scene.push(Primitive::new_with_label(label, mesh, self.material, Matrix4::identity()));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const CUBE_OBJ: &str = "
+# unit cube, with quads
+mtllib cube.mtl
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+v 0 0 1
+v 1 0 1
+v 1 1 1
+v 0 1 1
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+vn 0 0 -1
+o cube
+usemtl red
+f 1/1/1 4/4/1 3/3/1 2/2/1
+g sides
+f 5 6 7 8
+f 1 2 6 5
+usemtl light
+f -8 -4 -1 -5
+";
+
+    const CUBE_MTL: &str = "
+newmtl red
+Kd 0.75 0.25 0.25
+illum 2
+
+newmtl light
+Kd 0
+Ke 12 12 12
+
+newmtl glass
+illum 7
+Ni 1.33
+";
+
+    fn write_cube() -> PathBuf {
+        let dir = std::env::temp_dir().join("pharosa-obj-test");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("cube.obj"), CUBE_OBJ).unwrap();
+        std::fs::write(dir.join("cube.mtl"), CUBE_MTL).unwrap();
+        dir.join("cube.obj")
+    }
+
+    #[test]
+    fn load_cube() {
+        let scene = load_obj(write_cube()).unwrap();
+        assert_eq!(scene.len(), 3); // cube (red) + sides (red) + sides (light)
+        assert!(scene.has_bvh());
+        assert_eq!(scene[0].geometry.n_triangles(), 2);
+        assert!(scene[0].geometry.uvs().is_some());
+        assert!(scene[0].geometry.normals().is_some());
+        assert_eq!(scene[1].geometry.n_triangles(), 4);
+        assert!(scene[1].geometry.uvs().is_none());
+        assert_eq!(scene[2].material.emission, Spectrum::uniform(12.));
+        assert_eq!(scene[2].label, "sides (light)");
+        assert!(Arc::ptr_eq(&scene[0].material, &scene[1].material));
+
+        let its = scene.nearest_hit(&Ray::new(pt3(0.5, 0.5, -1.), vec3(0., 0., 1.))).unwrap();
+        assert_approx!(its.0.t, 1.);
+        assert_eq!(its.albedo(), &Spectrum::new(0.75, 0.25, 0.25));
+        let its = scene.nearest_hit(&Ray::new(pt3(-1., 0.5, 0.5), vec3(1., 0., 0.))).unwrap();
+        assert_eq!(its.emission(), &Spectrum::uniform(12.));
+    }
+
+    #[test]
+    fn subdivided_cube() {
+        // every face with its own uvs, so that no two faces share a vertex until welded
+        let mut obj = String::from("v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nv 0 0 1\nv 1 0 1\nv 1 1 1\nv 0 1 1\n");
+        obj += "vt 0 0\nvt 1 0\nvt 1 1\nvt 0 1\n";
+        for face in &[[1, 4, 3, 2], [5, 6, 7, 8], [1, 2, 6, 5], [2, 3, 7, 6], [3, 4, 8, 7], [4, 1, 5, 8]] {
+            obj += &format!("f {}/1 {}/2 {}/3 {}/4\n", face[0], face[1], face[2], face[3]);
+        }
+        let scene = parse_obj_subdivided(obj.as_bytes(), "cube.obj", Path::new(""), 2).unwrap();
+        assert_eq!(scene.len(), 1);
+        let mesh = &scene[0].geometry;
+        assert_eq!(mesh.n_triangles(), 12 * 4 * 4);
+        assert!(mesh.uvs().is_none());
+        assert!(mesh.normals().is_some());
+        // rounded off, inside the cage
+        for p in mesh.positions() {
+            assert!([p.x, p.y, p.z].iter().all(|&x| 0. < x && x < 1.), "{:?}", p);
+        }
+        // closed: every ray from the center leaves through the inside
+        for &dir in &[vec3(1., 0., 0.), vec3(0., -1., 0.), vec3(0.3, 0.5, 0.8).normalize(), vec3(-1., -1., -1.).normalize()] {
+            let its = mesh.intersect(&Ray::new(pt3(0.5, 0.5, 0.5), dir)).unwrap();
+            assert_eq!(its.side, Side::Inside);
+        }
+        // seamless uvs are smoothed along
+        let quad = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nvt 0 0\nvt 1 0\nvt 1 1\nvt 0 1\nf 1/1 2/2 3/3 4/4\n";
+        let scene = parse_obj_subdivided(quad.as_bytes(), "quad.obj", Path::new(""), 2).unwrap();
+        let mesh = &scene[0].geometry;
+        for (p, uv) in mesh.positions().iter().zip(mesh.uvs().unwrap()) {
+            assert_approx!(p.x, uv.x);
+            assert_approx!(p.y, uv.y);
+        }
+    }
+
+    #[test]
+    fn mtl_mapping() {
+        let materials = parse_mtl(CUBE_MTL.as_bytes(), "cube.mtl").unwrap();
+        assert!(matches!(materials["red"].bsdf, Simple::Diffuse(_)));
+        match &materials["glass"].bsdf {
+            Simple::Dielectric(d) => assert_eq!(d.n, 1.33),
+            other => panic!("unexpected bsdf {:?}", other),
+        }
+        assert_eq!(materials["light"].texture.0, Spectrum::black());
+    }
+
+    #[test]
+    fn line_numbered_errors() {
+        let err = |obj: &str| match parse_obj(obj.as_bytes(), "test.obj", Path::new("")) {
+            Err(LoadError::Parse { line, .. }) => line,
+            other => panic!("expected a parse error, got {:?}", other.map(|s| s.len())),
+        };
+        assert_eq!(err("v 0 0 0\nv 1 0\n"), 2);
+        assert_eq!(err("v 0 0 0\nv 1 0 0\nv 0 1 0\n\nf 1 2 4\n"), 5);
+        assert_eq!(err("v 0 0 0\nv 1 0 0\nf 1 2\n"), 3);
+        assert_eq!(err("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1/a 2 3\n"), 4);
+        assert_eq!(err("usemtl missing\n"), 1);
+        let e = parse_mtl("Kd 1 1 1\n".as_bytes(), "test.mtl").unwrap_err();
+        assert_eq!(e.to_string(), "test.mtl:1: 'Kd' before any 'newmtl'");
+    }
+}
@@ -0,0 +1,390 @@
+//! Stanford PLY, in ASCII or binary (little / big endian)
+//!
+//! Reads `x y z`, optional `nx ny nz`, `u v` (or `s t`, `texture_u texture_v`) and `red green blue`
+//! from the `vertex` element and `vertex_indices` from the `face` element. Other elements are skipped.
+//! The colors are kept in `Mesh::colors` but don't show up in renders, the material's texture does.
+
+use super::*;
+use std::io::BufRead;
+
+/// Load a `.ply` file as a mesh
+pub fn load_ply(path: impl AsRef<Path>) -> LoadResult<Mesh> {
+    let path = path.as_ref();
+    parse_ply(open(path)?, &path.display().to_string())
+}
+
+/// Parse PLY content as a mesh
+pub fn parse_ply(mut reader: impl BufRead, source: &str) -> LoadResult<Mesh> {
+    let mut cur = Cursor::new(source);
+    let header = Header::parse(&mut reader, &mut cur)?;
+    if header.format != Format::Ascii { cur.line = 0; } // no lines in binary data
+    let mut body = Body { reader, format: header.format, cur, tokens: Vec::new() };
+
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut uvs = Vec::new();
+    let mut colors = Vec::new();
+    let mut indices = Vec::new();
+    let mut has_faces = false;
+    for element in &header.elements {
+        match element.name.as_str() {
+            "vertex" => {
+                let find = |names: &[&str]| names.iter()
+                    .map(|name| element.properties.iter().position(|p| p.name() == *name))
+                    .collect::<Option<Vec<_>>>();
+                let pos = match find(&["x", "y", "z"]) {
+                    None => return body.cur.error("vertex element without 'x y z'"),
+                    Some(pos) => pos,
+                };
+                let normal = find(&["nx", "ny", "nz"]);
+                let uv = [["u", "v"], ["s", "t"], ["texture_u", "texture_v"], ["texture_s", "texture_t"]].iter()
+                    .find_map(|names| find(names));
+                let color = find(&["red", "green", "blue"]);
+                let color_scale = match color.as_ref().map(|c| &element.properties[c[0]]) {
+                    Some(Property::Scalar(ScalarType::U8, _)) => 1. / 255.,
+                    Some(Property::Scalar(ScalarType::U16, _)) => 1. / 65535.,
+                    _ => 1.,
+                };
+                let mut values = vec![0.; element.properties.len()];
+                for i in 0..element.count {
+                    body.cur.set_context(format!("vertex {}", i));
+                    for (value, prop) in values.iter_mut().zip(&element.properties) {
+                        *value = body.read_property(prop)? as Float;
+                    }
+                    positions.push(pt3(values[pos[0]], values[pos[1]], values[pos[2]]));
+                    if let Some(n) = &normal {
+                        normals.push(vec3(values[n[0]], values[n[1]], values[n[2]]));
+                    }
+                    if let Some(uv) = &uv {
+                        uvs.push(pt2(values[uv[0]], values[uv[1]]));
+                    }
+                    if let Some(c) = &color {
+                        colors.push(Spectrum::new(values[c[0]], values[c[1]], values[c[2]]) * color_scale);
+                    }
+                }
+            }
+            "face" => {
+                has_faces = true;
+                let list = element.properties.iter()
+                    .position(|p| p.name() == "vertex_indices" || p.name() == "vertex_index");
+                for i in 0..element.count {
+                    body.cur.set_context(format!("face {}", i));
+                    let mut polygon = Vec::new();
+                    for (j, prop) in element.properties.iter().enumerate() {
+                        match prop {
+                            Property::List(count_ty, item_ty, _) => {
+                                let n = body.read(*count_ty)?;
+                                for _ in 0..n as usize {
+                                    let index = body.read(*item_ty)?;
+                                    if Some(j) == list { polygon.push(index); }
+                                }
+                            }
+                            Property::Scalar(ty, _) => { body.read(*ty)?; }
+                        }
+                    }
+                    if list.is_none() { continue; }
+                    if polygon.len() < 3 {
+                        return body.cur.error(format!("face has {} vertices, at least 3 expected", polygon.len()));
+                    }
+                    if let Some(&index) = polygon.iter().find(|&&index| index < 0. || index as usize >= positions.len()) {
+                        return body.cur.error(format!("vertex index {} out of range [0, {})", index, positions.len()));
+                    }
+                    for k in 1..polygon.len() - 1 { // triangulate as a fan
+                        indices.push([polygon[0] as u32, polygon[k] as u32, polygon[k + 1] as u32]);
+                    }
+                }
+            }
+            _ => {
+                for i in 0..element.count {
+                    body.cur.set_context(format!("{} {}", element.name, i));
+                    for prop in &element.properties {
+                        body.read_property(prop)?;
+                    }
+                }
+            }
+        }
+    }
+    body.cur.set_context(String::new());
+    if positions.is_empty() || !has_faces {
+        return body.cur.error("no 'vertex' or 'face' element");
+    }
+    let mut mesh = Mesh::new(positions, indices);
+    if !normals.is_empty() { mesh.set_normals(normals); }
+    if !uvs.is_empty() { mesh.set_uvs(uvs); }
+    if !colors.is_empty() { mesh.set_colors(colors); }
+    Ok(mesh)
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum ScalarType { I8, U8, I16, U16, I32, U32, F32, F64 }
+
+impl ScalarType {
+    fn parse(name: &str) -> Option<Self> {
+        use ScalarType::*;
+        Some(match name {
+            "char" | "int8" => I8,
+            "uchar" | "uint8" => U8,
+            "short" | "int16" => I16,
+            "ushort" | "uint16" => U16,
+            "int" | "int32" => I32,
+            "uint" | "uint32" => U32,
+            "float" | "float32" => F32,
+            "double" | "float64" => F64,
+            _ => return None,
+        })
+    }
+    fn size(self) -> usize {
+        use ScalarType::*;
+        match self {
+            I8 | U8 => 1,
+            I16 | U16 => 2,
+            I32 | U32 | F32 => 4,
+            F64 => 8,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Property {
+    Scalar(ScalarType, String),
+    /// Count type, item type, name
+    List(ScalarType, ScalarType, String),
+}
+
+impl Property {
+    fn name(&self) -> &str {
+        match self {
+            Property::Scalar(_, name) | Property::List(_, _, name) => name,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+#[derive(Debug)]
+struct Header {
+    format: Format,
+    elements: Vec<Element>,
+}
+
+impl Header {
+    fn parse(reader: &mut impl BufRead, cur: &mut Cursor) -> LoadResult<Self> {
+        let mut format = None;
+        let mut elements: Vec<Element> = Vec::new();
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if reader.read_line(&mut line).map_err(|e| LoadError::Io(cur.source.into(), e))? == 0 {
+                return cur.error("unexpected end of file in header");
+            }
+            cur.line += 1;
+            let mut tokens = line.split_whitespace();
+            let keyword = tokens.next().unwrap_or("");
+            if cur.line == 1 {
+                if keyword != "ply" { return cur.error("missing 'ply' magic number"); }
+                continue;
+            }
+            match keyword {
+                "format" => {
+                    format = Some(match tokens.next() {
+                        Some("ascii") => Format::Ascii,
+                        Some("binary_little_endian") => Format::BinaryLittleEndian,
+                        Some("binary_big_endian") => Format::BinaryBigEndian,
+                        other => return cur.error(format!("unknown format {:?}", other.unwrap_or(""))),
+                    });
+                }
+                "element" => {
+                    let name = match tokens.next() {
+                        None => return cur.error("missing element name"),
+                        Some(name) => name.to_owned(),
+                    };
+                    let count = cur.parse_next(&mut tokens, "element count")?;
+                    elements.push(Element { name, count, properties: Vec::new() });
+                }
+                "property" => {
+                    let scalar = |tokens: &mut std::str::SplitWhitespace| match tokens.next() {
+                        None => cur.error("missing property type"),
+                        Some(ty) => ScalarType::parse(ty).map_or_else(|| cur.error(format!("unknown property type '{}'", ty)), Ok),
+                    };
+                    let property = if line.split_whitespace().nth(1) == Some("list") {
+                        tokens.next();
+                        let count_ty = scalar(&mut tokens)?;
+                        let item_ty = scalar(&mut tokens)?;
+                        Property::List(count_ty, item_ty, tokens.next().unwrap_or("").to_owned())
+                    } else {
+                        let ty = scalar(&mut tokens)?;
+                        Property::Scalar(ty, tokens.next().unwrap_or("").to_owned())
+                    };
+                    match elements.last_mut() {
+                        None => return cur.error("property before any element"),
+                        Some(element) => element.properties.push(property),
+                    }
+                }
+                "end_header" => break,
+                "comment" | "obj_info" | "" => {}
+                other => return cur.error(format!("unknown header keyword '{}'", other)),
+            }
+        }
+        match format {
+            None => cur.error("missing format"),
+            Some(format) => Ok(Self { format, elements }),
+        }
+    }
+}
+
+/// Reads scalars out of the data section
+struct Body<'a, R: BufRead> {
+    reader: R,
+    format: Format,
+    cur: Cursor<'a>,
+    /// Remaining tokens of the current ASCII line, reversed
+    tokens: Vec<String>,
+}
+
+impl<'a, R: BufRead> Body<'a, R> {
+    fn read_property(&mut self, prop: &Property) -> LoadResult<f64> {
+        match prop {
+            Property::Scalar(ty, _) => self.read(*ty),
+            Property::List(count_ty, item_ty, _) => {
+                let n = self.read(*count_ty)?;
+                for _ in 0..n as usize { self.read(*item_ty)?; }
+                Ok(n)
+            }
+        }
+    }
+
+    fn read(&mut self, ty: ScalarType) -> LoadResult<f64> {
+        use ScalarType::*;
+        if self.format == Format::Ascii {
+            while self.tokens.is_empty() {
+                let mut line = String::new();
+                if self.reader.read_line(&mut line).map_err(|e| LoadError::Io(self.cur.source.into(), e))? == 0 {
+                    return self.cur.error("unexpected end of file");
+                }
+                self.cur.line += 1;
+                self.tokens = line.split_whitespace().rev().map(str::to_owned).collect();
+            }
+            let tok = self.tokens.pop().unwrap();
+            return tok.parse().or_else(|_| self.cur.error(format!("invalid number '{}'", tok)));
+        }
+        let mut buf = [0u8; 8];
+        let buf = &mut buf[..ty.size()];
+        if let Err(e) = self.reader.read_exact(buf) {
+            return match e.kind() {
+                io::ErrorKind::UnexpectedEof => self.cur.error("unexpected end of file"),
+                _ => Err(LoadError::Io(self.cur.source.into(), e)),
+            };
+        }
+        macro_rules! decode {
+            ($T: ty) => {{
+                let mut bytes = [0u8; std::mem::size_of::<$T>()];
+                bytes.copy_from_slice(buf);
+                (if self.format == Format::BinaryLittleEndian { <$T>::from_le_bytes(bytes) } else { <$T>::from_be_bytes(bytes) }) as f64
+            }};
+        }
+        Ok(match ty {
+            I8 => decode!(i8),
+            U8 => decode!(u8),
+            I16 => decode!(i16),
+            U16 => decode!(u16),
+            I32 => decode!(i32),
+            U32 => decode!(u32),
+            F32 => decode!(f32),
+            F64 => decode!(f64),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const HEADER: &str = "ply
+format {}
+comment a unit quad
+element vertex 4
+property float x
+property float y
+property float z
+property float nx
+property float ny
+property float nz
+property uchar red
+property uchar green
+property uchar blue
+element face 1
+property list uchar int vertex_indices
+end_header
+";
+
+    fn vertices() -> Vec<([f32; 6], [u8; 3])> {
+        vec![
+            ([0., 0., 0., 0., 0., 1.], [255, 0, 0]),
+            ([1., 0., 0., 0., 0., 1.], [0, 255, 0]),
+            ([1., 1., 0., 0., 0., 1.], [0, 0, 255]),
+            ([0., 1., 0., 0., 0., 1.], [255, 255, 255]),
+        ]
+    }
+
+    fn check_quad(mesh: &Mesh) {
+        assert_eq!(mesh.positions().len(), 4);
+        assert_eq!(mesh.indices(), &[[0, 1, 2], [0, 2, 3]]);
+        assert_eq!(mesh.normals().unwrap()[2], vec3(0., 0., 1.));
+        assert_eq!(mesh.colors().unwrap()[1], Spectrum::new(0., 1., 0.));
+        assert!(mesh.uvs().is_none());
+        let its = mesh.intersect(&Ray::new(pt3(0.2, 0.7, 1.), vec3(0., 0., -1.))).unwrap();
+        assert_eq!(its.t, 1.);
+    }
+
+    #[test]
+    fn ascii() {
+        let mut ply = HEADER.replace("{}", "ascii 1.0");
+        for (v, c) in vertices() {
+            ply += &format!("{} {} {} {} {} {} {} {} {}\n", v[0], v[1], v[2], v[3], v[4], v[5], c[0], c[1], c[2]);
+        }
+        ply += "4 0 1 2 3\n";
+        check_quad(&parse_ply(ply.as_bytes(), "quad.ply").unwrap());
+    }
+
+    #[test]
+    fn binary() {
+        for &(format, little) in &[("binary_little_endian 1.0", true), ("binary_big_endian 1.0", false)] {
+            let mut ply = HEADER.replace("{}", format).into_bytes();
+            for (v, c) in vertices() {
+                for x in v.iter() {
+                    ply.extend_from_slice(&if little { x.to_le_bytes() } else { x.to_be_bytes() });
+                }
+                ply.extend_from_slice(&c);
+            }
+            ply.push(4);
+            for i in 0..4i32 {
+                ply.extend_from_slice(&if little { i.to_le_bytes() } else { i.to_be_bytes() });
+            }
+            check_quad(&parse_ply(&ply[..], "quad.ply").unwrap());
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let err = |ply: &str| parse_ply(ply.as_bytes(), "bad.ply").unwrap_err().to_string();
+        assert_eq!(err("plx\n"), "bad.ply:1: missing 'ply' magic number");
+        assert_eq!(err("ply\nformat ascii 1.0\nelement vertex 1\nproperty half x\n"),
+                   "bad.ply:4: unknown property type 'half'");
+        let ply = HEADER.replace("{}", "ascii 1.0") + "0 0 0 0 0 1 0 0 0\n1 0 0 0 0 1 0 0 0\n";
+        assert_eq!(err(&ply), "bad.ply:18: vertex 2: unexpected end of file");
+        let ply = HEADER.replace("{}", "ascii 1.0") + &"0 0 0 0 0 1 0 0 0\n".repeat(4) + "3 0 1 4\n";
+        assert_eq!(err(&ply), "bad.ply:21: face 0: vertex index 4 out of range [0, 4)");
+    }
+}
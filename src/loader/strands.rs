@@ -0,0 +1,84 @@
+//! Plain-text hair strands
+//!
+//! One strand per line as the coordinates of its points, `x y z x y z ...`, at least 2 points.
+//! Blank lines and lines starting with `#` are skipped. The strands are smoothed by `Curves::strand`.
+
+use super::*;
+use std::io::BufRead;
+
+/// Load the strands of a file as `Curves`, tapering from `root_width` to `tip_width`
+pub fn load_strands(path: impl AsRef<Path>, root_width: Float, tip_width: Float, ty: CurveType) -> LoadResult<Curves> {
+    let path = path.as_ref();
+    parse_strands(open(path)?, &path.display().to_string(), root_width, tip_width, ty)
+}
+
+/// Parse strands content, see the module doc
+pub fn parse_strands(reader: impl BufRead, source: &str, root_width: Float, tip_width: Float, ty: CurveType) -> LoadResult<Curves> {
+    let mut cur = Cursor::new(source);
+    let mut curves = Vec::new();
+    for line in reader.lines() {
+        cur.line += 1;
+        let line = line.map_err(|e| LoadError::Io(source.into(), e))?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') { continue; }
+        let mut tokens = line.split_whitespace().peekable();
+        let mut points = Vec::new();
+        while tokens.peek().is_some() {
+            points.push(pt3(
+                cur.parse_next(&mut tokens, "x")?,
+                cur.parse_next(&mut tokens, "y")?,
+                cur.parse_next(&mut tokens, "z")?,
+            ));
+        }
+        if points.len() < 2 {
+            return cur.error(format!("strand has {} points, at least 2 expected", points.len()));
+        }
+        curves.extend(Curves::strand(&points, root_width, tip_width, ty));
+    }
+    if curves.is_empty() {
+        return cur.error("no strands");
+    }
+    Ok(Curves::new(curves))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn load() {
+        let path = std::env::temp_dir().join("pharosa-strands-test.txt");
+        std::fs::write(&path, "# two strands\n0 0 0  0 0 1  0 0 2\n\n1 0 0 1 0 1\n").unwrap();
+        let curves = load_strands(&path, 0.1, 0.02, CurveType::Ribbon).unwrap();
+        assert_eq!(curves.curves().len(), 3);
+        assert_eq!(curves.curves()[2].control_points()[0], pt3(1., 0., 0.));
+        let its = curves.intersect(&Ray::new(pt3(0., 5., 1.5), vec3(0., -1., 0.))).unwrap();
+        // on the second segment of the first strand
+        assert!(its.uv.x > 0.5 && its.uv.x < 1., "{}", its.uv.x);
+        assert!(matches!(load_strands("/nonexistent.txt", 0.1, 0.1, CurveType::Ribbon), Err(LoadError::Io(..))));
+    }
+
+    #[test]
+    fn line_numbered_errors() {
+        let parse = |s: &str| parse_strands(s.as_bytes(), "test", 0.1, 0.1, CurveType::Cylinder).map(|_| ());
+        match parse("0 0 0 1 1 1\n0 0 0 1 1\n") {
+            Err(LoadError::Parse { line, message, .. }) => {
+                assert_eq!(line, 2);
+                assert!(message.contains("missing z"), "{}", message);
+            }
+            r => panic!("{:?}", r),
+        }
+        match parse("\n0 0 x 1 1 1\n") {
+            Err(LoadError::Parse { line, message, .. }) => {
+                assert_eq!(line, 2);
+                assert!(message.contains("invalid z 'x'"), "{}", message);
+            }
+            r => panic!("{:?}", r),
+        }
+        match parse("0 0 0\n") {
+            Err(LoadError::Parse { line: 1, message, .. }) => assert!(message.contains("at least 2")),
+            r => panic!("{:?}", r),
+        }
+        assert!(matches!(parse("# nothing\n"), Err(LoadError::Parse { .. })));
+    }
+}
@@ -0,0 +1,84 @@
+use super::*;
+use crate::macros::*;
+
+#[derive(Debug, Clone)]
+/// Open cone around the z-axis with its base on `z = 0` and apex at `z = height`, clipped to `phi <= phi_max`
+pub struct Cone {
+    radius: Float,
+    height: Float,
+    phi_max: Float,
+}
+
+impl Cone {
+    pub fn new(radius: Float, height: Float) -> Self {
+        debug_assert_gt!(radius, 0.);
+        debug_assert_gt!(height, 0.);
+        Self { radius, height, phi_max: 2. * Float::PI() }
+    }
+    /// Keep only the sweep `0 <= phi <= phi_max` around the z-axis
+    pub fn with_phi_max<A>(mut self, phi_max: A) -> Self where A: Into<Radf> {
+        self.phi_max = num_traits::clamp(phi_max.into().0, 0., 2. * Float::PI());
+        self
+    }
+    pub fn radius(&self) -> Float { self.radius }
+    pub fn height(&self) -> Float { self.height }
+    pub fn area(&self) -> Float {
+        0.5 * self.phi_max * self.radius * (self.radius * self.radius + self.height * self.height).sqrt()
+    }
+}
+
+impl Intersect for Cone {
+    /// `u = phi / phi_max`, `v` goes from the base to the apex
+    fn intersect(&self, ray: &Ray) -> Option<GeometryIntersection> {
+        let (ox, oy, oz) = (ray.org.x as f64, ray.org.y as f64, ray.org.z as f64 - self.height as f64);
+        let (dx, dy, dz) = (ray.dir.x as f64, ray.dir.y as f64, ray.dir.z as f64);
+        let k = (self.radius as f64 / self.height as f64).powi(2);
+        let a = dx * dx + dy * dy - k * dz * dz;
+        let b = 2. * (dx * ox + dy * oy - k * dz * oz);
+        let c = ox * ox + oy * oy - k * oz * oz;
+        nearest_quadric_hit(ray, a, b, c, |t, pos| {
+            let phi = azimuth(pos);
+            if pos.z < 0. || pos.z > self.height || phi > self.phi_max { return None; }
+            let (sin_phi, cos_phi) = phi.sin_cos();
+            let normal = vec3(self.height * cos_phi, self.height * sin_phi, self.radius).normalize();
+            Some(facing_ray(
+                ray, t, pos, normal,
+                pt2(phi / self.phi_max, pos.z / self.height),
+                vec3(-pos.y, pos.x, 0.) * self.phi_max,
+                vec3(-self.radius * cos_phi, -self.radius * sin_phi, self.height),
+            ))
+        })
+    }
+}
+
+impl Geometry for Cone {
+    fn bounds(&self) -> Bounds3f {
+        Bounds3f::new(pt3(-self.radius, -self.radius, 0.), pt3(self.radius, self.radius, self.height))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn intersect() {
+        let cone = Cone::new(1., 2.);
+        let its = cone.intersect(&Ray::new(pt3(5., 0., 1.), vec3(-1., 0., 0.))).unwrap();
+        assert_approx!(its.t, 4.5);
+        assert_approx!(its.uv.y, 0.5);
+        assert_approx!((its.normal - vec3(2., 0., 1.).normalize()).magnitude(), 0.);
+        assert_eq!(its.side, Side::Outside);
+        assert_gt!(dot(its.dpdu.cross(its.dpdv), its.normal), 0.);
+        // the other nappe of the double cone is not part of the surface
+        assert_eq!(cone.intersect(&Ray::new(pt3(5., 0., 3.), vec3(-1., 0., 0.))), None);
+        // into the base from below
+        let its = cone.intersect(&Ray::new(pt3(0.5, 0., -1.), vec3(0., 0., 1.))).unwrap();
+        assert_approx!(its.pos.z, 1.);
+        assert_eq!(its.side, Side::Inside);
+        // a quarter cone
+        let quarter = cone.with_phi_max(Deg(90.));
+        assert!(quarter.intersect(&Ray::new(pt3(0.3, 0.3, -1.), vec3(0., 0., 1.))).is_some());
+        assert!(!quarter.intersect_p(&Ray::new(pt3(-0.3, 0.3, -1.), vec3(0., 0., 1.))));
+    }
+}
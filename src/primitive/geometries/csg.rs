@@ -0,0 +1,195 @@
+use super::*;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CsgOp {
+    /// Inside any child
+    Union,
+    /// Inside all children
+    Intersection,
+    /// Inside the first child, but none of the others
+    Difference,
+}
+
+impl CsgOp {
+    #[inline]
+    fn combine(self, inside: &[bool]) -> bool {
+        match self {
+            CsgOp::Union => inside.iter().any(|&b| b),
+            CsgOp::Intersection => inside.iter().all(|&b| b),
+            CsgOp::Difference => inside[0] && !inside[1..].iter().any(|&b| b),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Constructive solid geometry, a boolean combination of two or more solids
+///
+/// Children are closed surfaces reporting `Side` consistently, each placed by its own transform.
+/// The ray starts inside a child if its first hit leaves it. Hits of the children are walked front to back,
+/// the first one that changes the combined inside/outside state is a hit of the combined solid.
+/// Children of different kinds can be mixed through `DynamicGeometry`.
+pub struct Csg<G: Geometry> {
+    op: CsgOp,
+    /// Child geometries and their local_to_csg transforms
+    children: Vec<(G, Transform)>,
+    bounds: Bounds3f,
+}
+
+impl<G: Geometry> Csg<G> {
+    /// `children`: geometries, each with a matrix to transform it to where it is in the combined solid
+    pub fn new(op: CsgOp, children: impl IntoIterator<Item=(G, Matrix4f)>) -> Self {
+        let children: Vec<_> = children.into_iter().map(|(g, m)| (g, Transform::new(m))).collect();
+        assert!(children.len() >= 2, "CSG needs at least 2 children");
+        let child_bounds: Vec<_> = children.iter().map(|(g, m)| m.transform(&g.bounds())).collect();
+        let bounds = match op {
+            CsgOp::Union => child_bounds.iter().fold(Bounds3f::empty(), |b, c| b.union(c)),
+            CsgOp::Intersection => child_bounds[1..].iter().fold(child_bounds[0], |b, c| b.intersection(c)),
+            CsgOp::Difference => child_bounds[0],
+        };
+        Self { op, children, bounds }
+    }
+    pub fn union(children: impl IntoIterator<Item=(G, Matrix4f)>) -> Self { Self::new(CsgOp::Union, children) }
+    pub fn intersection(children: impl IntoIterator<Item=(G, Matrix4f)>) -> Self { Self::new(CsgOp::Intersection, children) }
+    /// The first child with the others cut away
+    pub fn difference(children: impl IntoIterator<Item=(G, Matrix4f)>) -> Self { Self::new(CsgOp::Difference, children) }
+
+    pub fn op(&self) -> CsgOp { self.op }
+    pub fn children(&self) -> &[(G, Transform)] { &self.children }
+
+    /// Walk the hits of all children, return the first one changing the combined state
+    fn first_transition(&self, ray: &Ray) -> Option<GeometryIntersection> {
+        // rays in the children's spaces, without t_max so that the inside state stays known
+        let rays: Vec<_> = self.children.iter()
+            .map(|(_, m)| m.inverse().transform(&Ray::new_with_range(ray.org, ray.dir, ray.t_min, Float::infinity()).with_time(ray.time)))
+            .collect();
+        // the next local hit of each child, and the same in the csg space
+        let hit = |i: usize, t_min: Float| {
+            let local_ray = Ray { t_min, ..rays[i].clone() };
+            self.children[i].0.intersect(&local_ray).map(|its| (its.t, self.children[i].1.transform(&its)))
+        };
+        let mut next: Vec<_> = (0..self.children.len()).map(|i| hit(i, rays[i].t_min)).collect();
+        let mut inside: Vec<_> = next.iter().map(|h| matches!(h, Some((_, its)) if its.side == Side::Inside)).collect();
+        let mut was_inside = self.op.combine(&inside);
+        loop {
+            let (i, (t_local, its)) = next.iter().enumerate()
+                .filter_map(|(i, h)| h.as_ref().map(|h| (i, h)))
+                .min_by(|a, b| (a.1).1.t.partial_cmp(&(b.1).1.t).unwrap())?;
+            if !ray.in_range(its.t) { return None; }
+            inside[i] = !inside[i];
+            let is_inside = self.op.combine(&inside);
+            if is_inside != was_inside {
+                let side = if was_inside { Side::Inside } else { Side::Outside };
+                return Some(GeometryIntersection { side, ..its.clone() });
+            }
+            was_inside = is_inside;
+            // continue in the local parameter, which is exact
+            next[i] = hit(i, *t_local);
+        }
+    }
+}
+
+impl<G: Geometry> Intersect for Csg<G> {
+    /// Position, normal and `uv` are the ones of the child hit, `side` is of the combined solid
+    fn intersect(&self, ray: &Ray) -> Option<GeometryIntersection> {
+        let inv_dir = vec3(1. / ray.dir.x, 1. / ray.dir.y, 1. / ray.dir.z);
+        if !self.bounds.intersect_p(ray, inv_dir) { return None; }
+        self.first_transition(ray)
+    }
+}
+
+impl<G: Geometry> Geometry for Csg<G> {
+    fn bounds(&self) -> Bounds3f { self.bounds }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::random;
+
+    fn ball(x: Float, r: Float) -> (Sphere, Matrix4f) {
+        (Sphere::new(r), Matrix4::from_translation(vec3(x, 0., 0.)))
+    }
+
+    #[test]
+    fn lens() {
+        // two unit spheres 1 apart overlap in [-0.5, 0.5] along x
+        let lens = Csg::intersection(vec![ball(-0.5, 1.), ball(0.5, 1.)]);
+        assert_eq!(lens.bounds(), Bounds3f::new(pt3(-0.5, -1., -1.), pt3(0.5, 1., 1.)));
+        let its = lens.intersect(&Ray::new(pt3(-5., 0., 0.), vec3(1., 0., 0.))).unwrap();
+        assert_approx!(its.pos.x, -0.5);
+        assert_approx!(its.t, 4.5);
+        assert_eq!(its.side, Side::Outside);
+        assert_eq!(its.normal, vec3(-1., 0., 0.));
+        // leaving it from inside
+        let its = lens.intersect(&Ray::new(pt3(0., 0., 0.), vec3(1., 0., 0.))).unwrap();
+        assert_approx!(its.pos.x, 0.5);
+        assert_eq!(its.side, Side::Inside);
+        // within one sphere only
+        assert_eq!(lens.intersect(&Ray::new(pt3(-1.2, 0., -5.), vec3(0., 0., 1.))), None);
+        assert!(!lens.intersect_p(&Ray::new_with_range(pt3(-5., 0., 0.), vec3(1., 0., 0.), 0., 4.)));
+    }
+
+    #[test]
+    fn hollow() {
+        let shell = Csg::difference(vec![ball(0., 2.), ball(0., 1.)]);
+        let ray = Ray::new(pt3(-5., 0., 0.), vec3(1., 0., 0.));
+        let its = shell.intersect(&ray).unwrap();
+        assert_approx!(its.t, 3.);
+        assert_eq!(its.side, Side::Outside);
+        // out into the cavity: the inner sphere is hit from its outside, but it is an exit of the shell
+        let its = shell.intersect(&Ray::new_with_range(ray.org, ray.dir, 3.5, Float::infinity())).unwrap();
+        assert_approx!(its.t, 4.);
+        assert_eq!(its.side, Side::Inside);
+        assert_eq!(its.normal, vec3(-1., 0., 0.));
+        // and back in
+        let its = shell.intersect(&Ray::new(Point3::origin(), vec3(1., 0., 0.))).unwrap();
+        assert_approx!(its.t, 1.);
+        assert_eq!(its.side, Side::Outside);
+        // the union has no inner surface
+        let solid = Csg::union(vec![ball(0., 2.), ball(0., 1.)]);
+        let its = solid.intersect(&Ray::new_with_range(ray.org, ray.dir, 3.5, Float::infinity())).unwrap();
+        assert_approx!(its.t, 7.);
+    }
+
+    #[test]
+    fn scaled_child() {
+        // a squashed ball cut out of a box-like ellipsoid, hit along the scaled axis
+        let csg = Csg::difference(vec![
+            (Sphere::new(1.), Matrix4::from_nonuniform_scale(4., 1., 1.)),
+            (Sphere::new(1.), Matrix4::from_nonuniform_scale(2., 0.5, 0.5)),
+        ]);
+        let its = csg.intersect(&Ray::new_with_range(pt3(-10., 0., 0.), vec3(1., 0., 0.), 7., Float::infinity())).unwrap();
+        assert_approx!(its.t, 8.);
+        assert_eq!(its.side, Side::Inside);
+    }
+
+    #[test]
+    fn matches_point_membership() {
+        let balls = [(-0.6, 1.), (0.6, 1.), (0., 0.7)];
+        let inside = |p: Point3f, i: usize| (p - pt3(balls[i].0, 0., 0.)).magnitude() < balls[i].1;
+        for &op in &[CsgOp::Union, CsgOp::Intersection, CsgOp::Difference] {
+            let csg = Csg::new(op, balls.iter().map(|&(x, r)| ball(x, r)));
+            for _ in 0..200 {
+                let org = pt3(random::<Float>() - 0.5, random::<Float>() - 0.5, random::<Float>() - 0.5) * 6.;
+                let dir = vec3(random::<Float>() - 0.5, random::<Float>() - 0.5, random::<Float>() - 0.5).normalize();
+                let in_csg = |t: Float| {
+                    let p = org + dir * t;
+                    op.combine(&[inside(p, 0), inside(p, 1), inside(p, 2)])
+                };
+                match csg.intersect(&Ray::new(org, dir)) {
+                    Some(its) => {
+                        // the solid changes across the hit, and not before it
+                        assert_ne!(in_csg(its.t - 1e-3), in_csg(its.t + 1e-3));
+                        assert_eq!(its.side == Side::Inside, in_csg(its.t - 1e-3));
+                        for k in 1..20 {
+                            assert_eq!(in_csg(its.t * k as Float / 20.), in_csg(0.));
+                        }
+                    }
+                    None => for k in 0..100 {
+                        assert_eq!(in_csg(k as Float * 0.1), in_csg(0.));
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,297 @@
+use super::*;
+use crate::accel::Bvh;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CurveType {
+    /// Flat strip always facing the ray
+    Ribbon,
+    /// Tube, whose normal bends around the curve across the width
+    Cylinder,
+}
+
+#[derive(Debug, Clone)]
+/// Cubic Bézier segment with a width varying linearly along it, e.g. a piece of a hair strand
+///
+/// `u` runs along the length over `u_range`, `v` goes across the width from 0 to 1.
+/// Intersected by subdividing the curve until the pieces are nearly straight.
+pub struct Curve {
+    cp: [Point3f; 4],
+    width: [Float; 2],
+    u_range: [Float; 2],
+    ty: CurveType,
+}
+
+/// Best hit so far in the ray space: `(t, u)`, `u` in `[0, 1]` over the segment
+type CurveHit = (Float, Float);
+
+impl Curve {
+    /// `width0` and `width1` at the start and the end of the segment
+    pub fn new(cp: [Point3f; 4], width0: Float, width1: Float, ty: CurveType) -> Self {
+        debug_assert!(width0 > 0. && width1 > 0.);
+        Self { cp, width: [width0, width1], u_range: [0., 1.], ty }
+    }
+    /// Map the segment to `[u0, u1]` of the whole strand
+    pub fn with_u_range(mut self, u0: Float, u1: Float) -> Self {
+        self.u_range = [u0, u1];
+        self
+    }
+    pub fn control_points(&self) -> &[Point3f; 4] { &self.cp }
+    pub fn curve_type(&self) -> CurveType { self.ty }
+    /// Width at `u` in `[0, 1]` over the segment
+    #[inline]
+    pub fn width(&self, u: Float) -> Float { lerp(self.width[0], self.width[1], u) }
+
+    /// Nearest hit of the segment `[u0, u1]` with control points `cp` in the ray space, where the ray goes along +z from the origin
+    fn recursive_intersect(&self, ray: &Ray, cp: &[Point3f; 4], u0: Float, u1: Float, depth: u32, hit: &mut Option<CurveHit>) {
+        if depth > 0 {
+            let halves = split_bezier(cp);
+            let u_mid = 0.5 * (u0 + u1);
+            for &(cp, u0, u1) in &[([halves[0], halves[1], halves[2], halves[3]], u0, u_mid), ([halves[3], halves[4], halves[5], halves[6]], u_mid, u1)] {
+                let half_width = 0.5 * self.width(u0).max(self.width(u1));
+                let b = Bounds3f::from_points(cp.iter().cloned());
+                if b.min.x > half_width || b.max.x < -half_width || b.min.y > half_width || b.max.y < -half_width
+                    || b.max.z + half_width < ray.t_min || b.min.z - half_width > hit.map_or(ray.t_max, |h| h.0) {
+                    continue;
+                }
+                self.recursive_intersect(ray, &cp, u0, u1, depth - 1, hit);
+            }
+            return;
+        }
+        // the ray must pass between the lines perpendicular to the ends
+        if (cp[1].y - cp[0].y) * -cp[0].y + cp[0].x * (cp[0].x - cp[1].x) < 0. { return; }
+        if (cp[2].y - cp[3].y) * -cp[3].y + cp[3].x * (cp[3].x - cp[2].x) < 0. { return; }
+        // closest point to the ray on the nearly straight piece, in xy
+        let seg = vec2(cp[3].x - cp[0].x, cp[3].y - cp[0].y);
+        let denom = seg.magnitude2();
+        if denom == 0. { return; }
+        let w = num_traits::clamp(-(cp[0].x * seg.x + cp[0].y * seg.y) / denom, 0., 1.);
+        let u = lerp(u0, u1, w);
+        let (pc, _) = eval_bezier(cp, w);
+        let half_width = 0.5 * self.width(u);
+        if pc.x * pc.x + pc.y * pc.y > half_width * half_width { return; }
+        if pc.z <= ray.t_min || pc.z >= hit.map_or(ray.t_max, |h| h.0) { return; }
+        *hit = Some((pc.z, u));
+    }
+}
+
+impl Intersect for Curve {
+    fn intersect(&self, ray: &Ray) -> Option<GeometryIntersection> {
+        // the ray space: ray.dir is +z, ray.org is the origin
+        let frame = onb(ray.dir).transpose();
+        let to_ray = |p: Point3f| Point3::from_vec(frame * (p - ray.org));
+        let cp = [to_ray(self.cp[0]), to_ray(self.cp[1]), to_ray(self.cp[2]), to_ray(self.cp[3])];
+        let half_width = 0.5 * self.width[0].max(self.width[1]);
+        let b = Bounds3f::from_points(cp.iter().cloned());
+        if b.min.x > half_width || b.max.x < -half_width || b.min.y > half_width || b.max.y < -half_width
+            || b.max.z + half_width < ray.t_min || b.min.z - half_width > ray.t_max {
+            return None;
+        }
+        // subdivide until a piece deviates from its chord by a small fraction of the width
+        let l0 = (0..2).map(|i| {
+            let d = cp[i].to_vec() - cp[i + 1].to_vec() * 2. + cp[i + 2].to_vec();
+            d.x.abs().max(d.y.abs()).max(d.z.abs())
+        }).fold(0., Float::max);
+        let eps = 0.05 * self.width[0].max(self.width[1]);
+        let depth = num_traits::clamp((Float::SQRT_2() * 6. * l0 / (8. * eps)).log2() as i32 / 2, 0, 10) as u32;
+        let mut hit = None;
+        self.recursive_intersect(ray, &cp, 0., 1., depth, &mut hit);
+        let (t, u) = hit?;
+
+        let (p_axis, deriv) = eval_bezier(&self.cp, u);
+        let width = self.width(u);
+        let dpdu = deriv / (self.u_range[1] - self.u_range[0]);
+        let across = deriv.cross(ray.dir);
+        let across = if across.magnitude2() > 0. { across.normalize() } else { onb(deriv.normalize()).x };
+        let facing = deriv.cross(across).normalize();
+        // signed distance from the axis across the width, in [-1, 1]
+        let s = num_traits::clamp(2. * dot(ray.transport(t) - p_axis, across) / width, -1., 1.);
+        let (t, pos, normal) = match self.ty {
+            CurveType::Ribbon => (t, ray.transport(t), facing),
+            CurveType::Cylinder => {
+                let normal = facing * (1. - s * s).sqrt() + across * s;
+                let pos = p_axis + normal * (0.5 * width);
+                (dot(pos - ray.org, ray.dir), pos, normal)
+            }
+        };
+        if !ray.in_range(t) { return None; }
+        Some(GeometryIntersection {
+            pos,
+            normal,
+            wi: -ray.dir,
+            t,
+            side: Side::Outside,
+            uv: pt2(lerp(self.u_range[0], self.u_range[1], u), 0.5 + 0.5 * s),
+            dpdu,
+            dpdv: across * width,
+            // the surface is only approximated, within its width
+            p_error: vec3(width, width, width),
+        })
+    }
+}
+
+impl Geometry for Curve {
+    fn bounds(&self) -> Bounds3f {
+        let half_width = 0.5 * self.width[0].max(self.width[1]);
+        let b = Bounds3f::from_points(self.cp.iter().cloned());
+        Bounds3f::new(b.min - vec3(half_width, half_width, half_width), b.max + vec3(half_width, half_width, half_width))
+    }
+}
+
+/// Point and derivative at `u` by de Casteljau's algorithm
+fn eval_bezier(cp: &[Point3f; 4], u: Float) -> (Point3f, Vector3f) {
+    let mix = |a: Point3f, b: Point3f| a + (b - a) * u;
+    let cp1 = [mix(cp[0], cp[1]), mix(cp[1], cp[2]), mix(cp[2], cp[3])];
+    let cp2 = [mix(cp1[0], cp1[1]), mix(cp1[1], cp1[2])];
+    let deriv = if (cp2[1] - cp2[0]).magnitude2() > 0. { (cp2[1] - cp2[0]) * 3. } else { cp[3] - cp[0] };
+    (mix(cp2[0], cp2[1]), deriv)
+}
+
+/// Control points of the two halves, sharing the middle one
+fn split_bezier(cp: &[Point3f; 4]) -> [Point3f; 7] {
+    let [p0, p1, p2, p3] = [cp[0].to_vec(), cp[1].to_vec(), cp[2].to_vec(), cp[3].to_vec()];
+    [
+        cp[0],
+        Point3::from_vec((p0 + p1) / 2.),
+        Point3::from_vec((p0 + p1 * 2. + p2) / 4.),
+        Point3::from_vec((p0 + p1 * 3. + p2 * 3. + p3) / 8.),
+        Point3::from_vec((p1 + p2 * 2. + p3) / 4.),
+        Point3::from_vec((p2 + p3) / 2.),
+        cp[3],
+    ]
+}
+
+#[derive(Debug, Clone)]
+/// Many curve segments, e.g. the strands of fur, over a bvh like `Mesh`
+pub struct Curves {
+    curves: Vec<Curve>,
+    bvh: Bvh,
+}
+
+impl Curves {
+    pub fn new(curves: Vec<Curve>) -> Self {
+        let bounds: Vec<_> = curves.iter().map(|c| c.bounds()).collect();
+        Self { bvh: Bvh::build(&bounds), curves }
+    }
+
+    /// Smooth curves through the points of a polyline, from `root_width` at the first point to `tip_width` at the last
+    ///
+    /// The segments are Catmull-Rom splines, `u` runs over `[0, 1]` along the whole strand
+    pub fn strand(points: &[Point3f], root_width: Float, tip_width: Float, ty: CurveType) -> Vec<Curve> {
+        assert!(points.len() >= 2, "a strand needs at least 2 points");
+        let n = points.len() - 1;
+        let at = |i: isize| points[num_traits::clamp(i, 0, n as isize) as usize];
+        (0..n as isize).map(|i| {
+            let (p0, p1) = (at(i), at(i + 1));
+            let cp = [p0, p0 + (p1 - at(i - 1)) / 6., p1 - (at(i + 2) - p0) / 6., p1];
+            let (u0, u1) = (i as Float / n as Float, (i + 1) as Float / n as Float);
+            Curve::new(cp, lerp(root_width, tip_width, u0), lerp(root_width, tip_width, u1), ty).with_u_range(u0, u1)
+        }).collect()
+    }
+
+    pub fn curves(&self) -> &[Curve] { &self.curves }
+}
+
+impl Intersect for Curves {
+    fn intersect(&self, ray: &Ray) -> Option<GeometryIntersection> {
+        self.bvh.nearest(ray, |i, ray| self.curves[i].intersect(ray).map(|its| (its.t, its)))
+    }
+
+    fn intersect_p(&self, ray: &Ray) -> bool {
+        self.bvh.any(ray, |i, ray| self.curves[i].intersect_p(ray))
+    }
+}
+
+impl Geometry for Curves {
+    fn bounds(&self) -> Bounds3f { self.bvh.bounds() }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::macros::*;
+    use rand::random;
+
+    #[test]
+    fn straight() {
+        // along the x-axis, 0.2 wide
+        let cp = [pt3(0., 0., 0.), pt3(1., 0., 0.), pt3(2., 0., 0.), pt3(3., 0., 0.)];
+        let ribbon = Curve::new(cp, 0.2, 0.2, CurveType::Ribbon);
+        assert_eq!(ribbon.bounds(), Bounds3f::new(pt3(-0.1, -0.1, -0.1), pt3(3.1, 0.1, 0.1)));
+        let its = ribbon.intersect(&Ray::new(pt3(1.5, 0.05, 5.), vec3(0., 0., -1.))).unwrap();
+        assert_approx!(its.t, 5.);
+        assert_approx!(its.uv.x, 0.5);
+        assert_approx!((its.uv.y - 0.5).abs(), 0.25);
+        assert_eq!(its.normal, vec3(0., 0., 1.));
+        assert_eq!(its.side, Side::Outside);
+        assert_approx!((its.dpdu - vec3(3., 0., 0.)).magnitude(), 0.);
+        assert_gt!(dot(its.dpdu.cross(its.dpdv), its.normal), 0.);
+        // v goes along dpdv
+        assert_gt!(dot(its.pos - pt3(1.5, 0., 0.), its.dpdv) * (its.uv.y - 0.5), 0.);
+        assert_eq!(ribbon.intersect(&Ray::new(pt3(1.5, 0.15, 5.), vec3(0., 0., -1.))), None);
+        assert_eq!(ribbon.intersect(&Ray::new(pt3(3.5, 0., 5.), vec3(0., 0., -1.))), None);
+
+        let tube = Curve::new(cp, 0.2, 0.2, CurveType::Cylinder);
+        let its = tube.intersect(&Ray::new(pt3(1.5, 0.05, 5.), vec3(0., 0., -1.))).unwrap();
+        assert_approx!((its.pos.to_vec() - vec3(1.5, 0.05, (0.01 as Float - 0.0025).sqrt())).magnitude(), 0.);
+        assert_approx!((its.normal - vec3(0., 0.5, (0.75 as Float).sqrt())).magnitude(), 0.);
+        assert_approx!(its.t, 5. - its.pos.z);
+        // tapering to the end
+        let thin_end = Curve::new(cp, 0.2, 0.02, CurveType::Ribbon).with_u_range(0.5, 1.);
+        assert!(thin_end.intersect(&Ray::new(pt3(0.2, 0.09, 5.), vec3(0., 0., -1.))).is_some());
+        let its = thin_end.intersect(&Ray::new(pt3(2.9, 0., 5.), vec3(0., 0., -1.))).unwrap();
+        assert_approx!(its.uv.x, 0.5 + 0.5 * 2.9 / 3.);
+        assert_eq!(thin_end.intersect(&Ray::new(pt3(2.9, 0.09, 5.), vec3(0., 0., -1.))), None);
+    }
+
+    #[test]
+    fn matches_sampled_distance() {
+        let cp = [pt3(0., 0., 0.), pt3(1., 2., 0.5), pt3(2., -1., -0.5), pt3(3., 1., 0.)];
+        let curve = Curve::new(cp, 0.1, 0.1, CurveType::Ribbon);
+        let mut n_hits = 0;
+        for _ in 0..1000 {
+            let target = pt3(random::<Float>() * 3., random::<Float>() * 2. - 1., random::<Float>() - 0.5);
+            let org = target + vec3(random::<Float>() - 0.5, random::<Float>() - 0.5, random::<Float>() - 0.5).normalize() * 10.;
+            let ray = Ray::new(org, (target - org).normalize());
+            // the distance between the ray line and the curve, sampled densely, and where it is closest
+            let (dist, k) = (0..=2000).map(|i| {
+                let p = eval_bezier(&cp, i as Float / 2000.).0;
+                let d = p - ray.org;
+                ((d - ray.dir * dot(d, ray.dir)).magnitude(), i)
+            }).fold((Float::infinity(), 0), |a, b| if b.0 < a.0 { b } else { a });
+            match curve.intersect(&ray) {
+                Some(its) => {
+                    n_hits += 1;
+                    assert_lt!(dist, 0.05 + 1e-2);
+                    assert_lt!((eval_bezier(&cp, its.uv.x).0 - its.pos).magnitude(), 0.1);
+                }
+                // the ends are cut flat, rays passing just beyond them miss
+                None => assert!(dist > 0.05 - 1e-2 || k == 0 || k == 2000, "missed at distance {}", dist),
+            }
+        }
+        assert_gt!(n_hits, 10);
+    }
+
+    #[test]
+    fn strand() {
+        let points = [pt3(0., 0., 0.), pt3(0., 0., 1.), pt3(0.5, 0., 2.), pt3(1.5, 0., 2.5)];
+        let segments = Curves::strand(&points, 0.1, 0.01, CurveType::Cylinder);
+        assert_eq!(segments.len(), 3);
+        // through the points, and smooth across the joints
+        for (i, s) in segments.iter().enumerate() {
+            assert_eq!(s.control_points()[0], points[i]);
+            assert_eq!(s.control_points()[3], points[i + 1]);
+        }
+        let (_, d0) = eval_bezier(segments[0].control_points(), 1.);
+        let (_, d1) = eval_bezier(segments[1].control_points(), 0.);
+        assert_approx!((d0 - d1).magnitude(), 0.);
+        let curves = Curves::new(segments);
+        let its = curves.intersect(&Ray::new(pt3(0., 5., 0.5), vec3(0., -1., 0.))).unwrap();
+        assert_lt!(its.uv.x, 1. / 3.);
+        assert_gt!(its.normal.y, 0.5);
+        let p = eval_bezier(curves.curves()[2].control_points(), 0.9).0;
+        let its = curves.intersect(&Ray::new(p + vec3(0., 5., 0.), vec3(0., -1., 0.))).unwrap();
+        // u is close to, but not exactly, the curve parameter
+        assert_lt!((its.uv.x - (2. + 0.9) / 3.).abs(), 1e-2);
+        assert!(!curves.intersect_p(&Ray::new(pt3(1.5, 5., 0.), vec3(0., -1., 0.))));
+    }
+}
@@ -0,0 +1,90 @@
+use super::*;
+use crate::macros::*;
+
+#[derive(Debug, Clone)]
+/// Open cylinder around the z-axis, clipped to `z_min <= z <= z_max` and `phi <= phi_max`
+pub struct Cylinder {
+    radius: Float,
+    z_min: Float,
+    z_max: Float,
+    phi_max: Float,
+}
+
+impl Cylinder {
+    pub fn new(radius: Float, z_min: Float, z_max: Float) -> Self {
+        debug_assert_gt!(radius, 0.);
+        debug_assert_lt!(z_min, z_max);
+        Self { radius, z_min, z_max, phi_max: 2. * Float::PI() }
+    }
+    /// Keep only the sweep `0 <= phi <= phi_max` around the z-axis
+    pub fn with_phi_max<A>(mut self, phi_max: A) -> Self where A: Into<Radf> {
+        self.phi_max = num_traits::clamp(phi_max.into().0, 0., 2. * Float::PI());
+        self
+    }
+    pub fn radius(&self) -> Float { self.radius }
+    pub fn area(&self) -> Float { (self.z_max - self.z_min) * self.radius * self.phi_max }
+}
+
+impl Intersect for Cylinder {
+    /// `u = phi / phi_max`, `v` goes from `z_min` to `z_max`
+    fn intersect(&self, ray: &Ray) -> Option<GeometryIntersection> {
+        let (ox, oy) = (ray.org.x as f64, ray.org.y as f64);
+        let (dx, dy) = (ray.dir.x as f64, ray.dir.y as f64);
+        let a = dx * dx + dy * dy;
+        let b = 2. * (dx * ox + dy * oy);
+        let c = ox * ox + oy * oy - (self.radius as f64).powi(2);
+        nearest_quadric_hit(ray, a, b, c, |t, mut pos| {
+            // refine onto the surface
+            let scale = self.radius / (pos.x * pos.x + pos.y * pos.y).sqrt();
+            pos.x *= scale;
+            pos.y *= scale;
+            let phi = azimuth(pos);
+            if pos.z < self.z_min || pos.z > self.z_max || phi > self.phi_max { return None; }
+            Some(facing_ray(
+                ray, t, pos,
+                vec3(pos.x, pos.y, 0.) / self.radius,
+                pt2(phi / self.phi_max, (pos.z - self.z_min) / (self.z_max - self.z_min)),
+                vec3(-pos.y, pos.x, 0.) * self.phi_max,
+                vec3(0., 0., self.z_max - self.z_min),
+            ))
+        })
+    }
+}
+
+impl Geometry for Cylinder {
+    fn bounds(&self) -> Bounds3f {
+        Bounds3f::new(pt3(-self.radius, -self.radius, self.z_min), pt3(self.radius, self.radius, self.z_max))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn intersect() {
+        let cyl = Cylinder::new(1., -1., 1.);
+        let its = cyl.intersect(&Ray::new(pt3(5., 0., 0.5), vec3(-1., 0., 0.))).unwrap();
+        assert_approx!(its.t, 4.);
+        assert_eq!(its.normal, vec3(1., 0., 0.));
+        assert_eq!(its.side, Side::Outside);
+        assert_approx!(its.uv.y, 0.75);
+        assert_gt!(dot(its.dpdu.cross(its.dpdv), its.normal), 0.);
+        // from inside
+        let its = cyl.intersect(&Ray::new(pt3(0., 0., 0.), vec3(0., -1., 0.))).unwrap();
+        assert_approx!(its.uv.x, 0.75);
+        assert_eq!(its.side, Side::Inside);
+        assert_approx!((its.normal - vec3(0., 1., 0.)).magnitude(), 0.);
+        // clipped by height, then hits the far wall
+        assert_eq!(cyl.intersect(&Ray::new(pt3(5., 0., 2.), vec3(-1., 0., 0.))), None);
+        let its = cyl.intersect(&Ray::new(pt3(-3., 0., 3.5), vec3(0.8, 0., -0.6))).unwrap();
+        assert_approx!(its.t, 5.);
+        assert_eq!(its.side, Side::Inside);
+        assert_eq!(cyl.intersect(&Ray::new(pt3(0., 0., 5.), vec3(0., 0., -1.))), None); // parallel to the axis
+        // half cylinder, the front half is cut away
+        let half = cyl.with_phi_max(Deg(180.));
+        let its = half.intersect(&Ray::new(pt3(0., -5., 0.), vec3(0., 1., 0.))).unwrap();
+        assert_approx!(its.pos.y, 1.);
+        assert_eq!(its.side, Side::Inside);
+    }
+}
@@ -0,0 +1,76 @@
+use super::*;
+use super::plane::{intersect_plane, plane_side};
+use crate::macros::*;
+
+#[derive(Debug, Clone)]
+/// Disk (or annulus) on `z = 0` centered at the origin, facing +z
+pub struct Disk {
+    radius: Float,
+    inner_radius: Float,
+}
+
+impl Disk {
+    pub fn new(radius: Float) -> Self { Self::new_with_inner_radius(radius, 0.) }
+    /// An annulus with a hole of `inner_radius`
+    pub fn new_with_inner_radius(radius: Float, inner_radius: Float) -> Self {
+        debug_assert_gt!(radius, 0.);
+        debug_assert!(inner_radius >= 0. && inner_radius < radius);
+        Self { radius, inner_radius }
+    }
+    pub fn radius(&self) -> Float { self.radius }
+    pub fn inner_radius(&self) -> Float { self.inner_radius }
+    pub fn area(&self) -> Float { Float::PI() * (self.radius * self.radius - self.inner_radius * self.inner_radius) }
+}
+
+impl Intersect for Disk {
+    /// `u = phi / 2pi` around the z-axis, `v` goes from the outer rim (0) to the inner one (1)
+    fn intersect(&self, ray: &Ray) -> Option<GeometryIntersection> {
+        let (t, pos) = intersect_plane(ray)?;
+        let dist2 = pos.x * pos.x + pos.y * pos.y;
+        if dist2 > self.radius * self.radius || dist2 < self.inner_radius * self.inner_radius { return None; }
+        let mut phi = pos.y.atan2(pos.x);
+        if phi < 0. { phi += 2. * Float::PI(); }
+        let r = dist2.sqrt();
+        let dr = self.radius - self.inner_radius;
+        let (normal, side) = plane_side(ray);
+        let dpdv = if r > 0. { vec3(pos.x, pos.y, 0.) * (-dr / r) } else { vec3(-dr, 0., 0.) };
+        Some(GeometryIntersection {
+            pos,
+            normal,
+            wi: -ray.dir,
+            t,
+            side,
+            uv: pt2(phi / (2. * Float::PI()), (self.radius - r) / dr),
+            dpdu: vec3(-pos.y, pos.x, 0.) * (2. * Float::PI()),
+            dpdv,
+            p_error: Vector3f::zero(),
+        })
+    }
+}
+
+impl Geometry for Disk {
+    fn bounds(&self) -> Bounds3f {
+        Bounds3f::new(pt3(-self.radius, -self.radius, 0.), pt3(self.radius, self.radius, 0.))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn intersect() {
+        let disk = Disk::new_with_inner_radius(2., 1.);
+        let its = disk.intersect(&Ray::new(pt3(0., 1.5, 1.), vec3(0., 0., -1.))).unwrap();
+        assert_eq!(its.t, 1.);
+        assert_approx!(its.uv.x, 0.25);
+        assert_approx!(its.uv.y, 0.5);
+        assert_eq!(its.side, Side::Outside);
+        assert_approx!(dot(its.dpdu.cross(its.dpdv).normalize(), vec3(0., 0., 1.)), 1.); // consistent with the normal
+        assert_eq!(disk.intersect(&Ray::new(pt3(0., 0.5, 1.), vec3(0., 0., -1.))), None); // hole
+        assert_eq!(disk.intersect(&Ray::new(pt3(1.5, 1.5, 1.), vec3(0., 0., -1.))), None); // outside
+        let its = Disk::new(1.).intersect(&Ray::new(pt3(0., 0., -1.), vec3(0., 0., 1.))).unwrap();
+        assert_eq!(its.uv.y, 1.);
+        assert_eq!(its.side, Side::Inside);
+    }
+}
@@ -0,0 +1,133 @@
+use super::*;
+use super::mesh::vertex_normals;
+use texture::FloatTexture;
+use std::collections::HashMap;
+
+impl Mesh {
+    /// Move each vertex along its normal by `scale` times the `height` at its uv, as a new mesh
+    ///
+    /// The detail can't be finer than the triangles, so this is meant for dense meshes, e.g. from `Mesh::subdivide`.
+    /// The copies of a point split at uv or normal seams move together, by their averaged height along their
+    /// averaged normal, so the surface doesn't crack there. Without normals, the ones of the faces are averaged.
+    /// The normals are recomputed from the displaced faces around each point, the uvs and colors are kept.
+    /// None if the mesh has no uvs, e.g. a subdivided OBJ whose uvs have seams.
+    pub fn displace(&self, height: &impl FloatTexture, scale: Float) -> Option<Mesh> {
+        let uvs = self.uvs()?;
+        let (points, point_of) = weld(self.positions());
+        let point_indices: Vec<_> = self.indices().iter().map(|face| face.map(|v| point_of[v as usize])).collect();
+        let mut heights = vec![0.; points.len()];
+        let mut counts = vec![0; points.len()];
+        for (&w, &uv) in point_of.iter().zip(uvs) {
+            heights[w as usize] += height.value(uv);
+            counts[w as usize] += 1;
+        }
+        let face_normals = vertex_normals(&points, &point_indices);
+        let normals = match self.normals() {
+            None => face_normals,
+            Some(normals) => {
+                let mut sums = vec![Vector3f::zero(); points.len()];
+                for (&w, &n) in point_of.iter().zip(normals) {
+                    sums[w as usize] += n;
+                }
+                // opposite copies cancel out, fall back to the faces
+                sums.into_iter().zip(face_normals)
+                    .map(|(n, fallback)| if n.magnitude2() > 0. { n.normalize() } else { fallback })
+                    .collect()
+            }
+        };
+        let points: Vec<_> = (0..points.len())
+            .map(|w| points[w] + normals[w] * (scale * heights[w] / counts[w] as Float))
+            .collect();
+        let point_normals = vertex_normals(&points, &point_indices);
+        let mut mesh = Mesh::new(point_of.iter().map(|&w| points[w as usize]).collect(), self.indices().to_vec());
+        mesh.set_normals(point_of.iter().map(|&w| point_normals[w as usize]).collect());
+        mesh.set_uvs(uvs.to_vec());
+        if let Some(colors) = self.colors() {
+            mesh.set_colors(colors.to_vec());
+        }
+        Some(mesh)
+    }
+}
+
+/// The distinct points among `positions`, and the one of each position
+fn weld(positions: &[Point3f]) -> (Vec<Point3f>, Vec<u32>) {
+    let mut points = Vec::new();
+    let mut index = HashMap::new();
+    let point_of = positions.iter().map(|&p| {
+        // -0 and 0 are the same point
+        let key = [(p.x + 0.).to_bits(), (p.y + 0.).to_bits(), (p.z + 0.).to_bits()];
+        *index.entry(key).or_insert_with(|| {
+            points.push(p);
+            points.len() as u32 - 1
+        })
+    }).collect();
+    (points, point_of)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::macros::*;
+
+    /// The unit square with its corners rounded off, uv the same as xy
+    fn square(levels: u32) -> Mesh {
+        let positions = [pt3(0., 0., 0.), pt3(1., 0., 0.), pt3(1., 1., 0.), pt3(0., 1., 0.)];
+        let uvs = [pt2(0., 0.), pt2(1., 0.), pt2(1., 1.), pt2(0., 1.)];
+        Mesh::subdivide(&positions, Some(&uvs), &[[0, 1, 2], [0, 2, 3]], &[], levels)
+    }
+
+    #[test]
+    fn ramp() {
+        let mesh = square(3).displace(&|uv: Point2f| uv.x, 0.5).unwrap();
+        for ((p, uv), n) in mesh.positions().iter().zip(mesh.uvs().unwrap()).zip(mesh.normals().unwrap()) {
+            assert_approx!(p.z, 0.5 * uv.x);
+            assert_approx!((n - vec3(-0.5, 0., 1.).normalize()).magnitude(), 0.);
+        }
+        let its = mesh.intersect(&Ray::new(pt3(0.6, 0.3, 5.), vec3(0., 0., -1.))).unwrap();
+        assert_approx!(its.pos.z, 0.3);
+    }
+
+    #[test]
+    fn seams() {
+        // a quad split along its diagonal, the second triangle with its own vertices and shifted uvs
+        let positions = vec![pt3(0., 0., 0.), pt3(1., 0., 0.), pt3(1., 1., 0.), pt3(0., 0., 0.), pt3(1., 1., 0.), pt3(0., 1., 0.)];
+        let mut quad = Mesh::new(positions.clone(), vec![[0, 1, 2], [3, 4, 5]]);
+        quad.set_uvs(positions.iter().enumerate().map(|(i, p)| pt2(p.x + if i < 3 { 0. } else { 0.5 }, p.y)).collect());
+        let mesh = quad.displace(&|uv: Point2f| uv.x, 1.).unwrap();
+        // the copies on the diagonal stay together, halfway between their heights
+        for &(a, b) in &[(0, 3), (2, 4)] {
+            assert_eq!(mesh.positions()[a], mesh.positions()[b]);
+            assert_eq!(mesh.normals().unwrap()[a], mesh.normals().unwrap()[b]);
+        }
+        assert_approx!(mesh.positions()[0].z, 0.25);
+        assert_approx!(mesh.positions()[2].z, 1.25);
+        // no crack to slip through
+        let its = mesh.intersect(&Ray::new(pt3(0.5, 0.5, 5.), vec3(0., 0., -1.))).unwrap();
+        assert_approx!(its.pos.z, 0.75);
+    }
+
+    #[test]
+    fn without_uvs() {
+        let positions = [pt3(0., 0., 0.), pt3(1., 0., 0.), pt3(1., 1., 0.)];
+        let mesh = Mesh::subdivide(&positions, None, &[[0, 1, 2]], &[], 1);
+        assert!(mesh.displace(&|_: Point2f| 1., 0.5).is_none());
+    }
+
+    #[test]
+    fn bumps() {
+        // a 2 x 2 checker of raised squares, blurred by the bilinear lookup
+        let bricks = texture::FloatImage::new(2, 2, vec![1., 0., 0., 1.]);
+        let flat = square(4);
+        let mesh = flat.displace(&bricks, 0.1).unwrap();
+        assert_eq!(mesh.n_triangles(), flat.n_triangles());
+        for (p, q) in mesh.positions().iter().zip(flat.positions()) {
+            assert_approx!(p.x, q.x);
+            assert_approx!(p.y, q.y);
+            assert_approx!(p.z, 0.1 * bricks.value(pt2(q.x, q.y)));
+        }
+        // the normals lean away from the raised texels
+        let normal_at = |x: Float, y: Float| mesh.intersect(&Ray::new(pt3(x, y, 1.), vec3(0., 0., -1.))).unwrap().normal;
+        assert_gt!(normal_at(0.5, 0.3).x, 0.1);
+        assert_lt!(normal_at(0.5, 0.8).x, -0.1);
+    }
+}
@@ -3,12 +3,27 @@ use derive_more::*;
 
 
 #[derive(Debug, Clone, From)]
-/// Sphere or triangle
+/// Any of the geometries, so that different kinds can share a scene
 ///
+/// `Shared` instances one geometry in many primitives, like `Arc<G>` does for a static type.
 /// Note: Performance can be worse than static ones
 pub enum DynamicGeometry {
     Sphere(Sphere),
     Triangle(Triangle),
+    Plane(Plane),
+    Rectangle(Rectangle),
+    Disk(Disk),
+    Cylinder(Cylinder),
+    Cone(Cone),
+    Paraboloid(Paraboloid),
+    Torus(Torus),
+    Sdf(SdfGeometry),
+    Heightfield(Heightfield),
+    Mesh(Mesh),
+    Csg(Csg<DynamicGeometry>),
+    Curve(Curve),
+    Curves(Curves),
+    Shared(Arc<DynamicGeometry>),
 }
 
 impl Intersect for DynamicGeometry {
@@ -17,10 +32,64 @@ impl Intersect for DynamicGeometry {
         match self {
             DynamicGeometry::Sphere(s) => s.intersect(ray),
             DynamicGeometry::Triangle(t) => t.intersect(ray),
+            DynamicGeometry::Plane(p) => p.intersect(ray),
+            DynamicGeometry::Rectangle(r) => r.intersect(ray),
+            DynamicGeometry::Disk(d) => d.intersect(ray),
+            DynamicGeometry::Cylinder(c) => c.intersect(ray),
+            DynamicGeometry::Cone(c) => c.intersect(ray),
+            DynamicGeometry::Paraboloid(p) => p.intersect(ray),
+            DynamicGeometry::Torus(t) => t.intersect(ray),
+            DynamicGeometry::Sdf(s) => s.intersect(ray),
+            DynamicGeometry::Heightfield(h) => h.intersect(ray),
+            DynamicGeometry::Mesh(m) => m.intersect(ray),
+            DynamicGeometry::Csg(c) => c.intersect(ray),
+            DynamicGeometry::Curve(c) => c.intersect(ray),
+            DynamicGeometry::Curves(c) => c.intersect(ray),
+            DynamicGeometry::Shared(g) => g.intersect(ray),
+        }
+    }
+
+    fn intersect_p(&self, ray: &Ray) -> bool {
+        match self {
+            DynamicGeometry::Sphere(s) => s.intersect_p(ray),
+            DynamicGeometry::Triangle(t) => t.intersect_p(ray),
+            DynamicGeometry::Plane(p) => p.intersect_p(ray),
+            DynamicGeometry::Rectangle(r) => r.intersect_p(ray),
+            DynamicGeometry::Disk(d) => d.intersect_p(ray),
+            DynamicGeometry::Cylinder(c) => c.intersect_p(ray),
+            DynamicGeometry::Cone(c) => c.intersect_p(ray),
+            DynamicGeometry::Paraboloid(p) => p.intersect_p(ray),
+            DynamicGeometry::Torus(t) => t.intersect_p(ray),
+            DynamicGeometry::Sdf(s) => s.intersect_p(ray),
+            DynamicGeometry::Heightfield(h) => h.intersect_p(ray),
+            DynamicGeometry::Mesh(m) => m.intersect_p(ray),
+            DynamicGeometry::Csg(c) => c.intersect_p(ray),
+            DynamicGeometry::Curve(c) => c.intersect_p(ray),
+            DynamicGeometry::Curves(c) => c.intersect_p(ray),
+            DynamicGeometry::Shared(g) => g.intersect_p(ray),
         }
     }
 }
 
 impl Geometry for DynamicGeometry {
-    // todo
+    fn bounds(&self) -> Bounds3f {
+        match self {
+            DynamicGeometry::Sphere(s) => s.bounds(),
+            DynamicGeometry::Triangle(t) => t.bounds(),
+            DynamicGeometry::Plane(p) => p.bounds(),
+            DynamicGeometry::Rectangle(r) => r.bounds(),
+            DynamicGeometry::Disk(d) => d.bounds(),
+            DynamicGeometry::Cylinder(c) => c.bounds(),
+            DynamicGeometry::Cone(c) => c.bounds(),
+            DynamicGeometry::Paraboloid(p) => p.bounds(),
+            DynamicGeometry::Torus(t) => t.bounds(),
+            DynamicGeometry::Sdf(s) => s.bounds(),
+            DynamicGeometry::Heightfield(h) => h.bounds(),
+            DynamicGeometry::Mesh(m) => m.bounds(),
+            DynamicGeometry::Csg(c) => c.bounds(),
+            DynamicGeometry::Curve(c) => c.bounds(),
+            DynamicGeometry::Curves(c) => c.bounds(),
+            DynamicGeometry::Shared(g) => g.bounds(),
+        }
+    }
 }
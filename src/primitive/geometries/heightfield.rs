@@ -0,0 +1,212 @@
+use super::*;
+use super::triangle::{intersect_triangle, barycentric_position};
+
+/// Hit time, the grid points of the triangle hit and their barycentric coordinates
+type CellHit = (Float, [(usize, usize); 3], [Float; 3]);
+
+#[derive(Debug, Clone)]
+/// Terrain over a regular grid of heights
+///
+/// Spans `[0, extent.x] x [0, extent.y]` on the xy-plane, a height `h` of the grid is at `z = h * extent.z`.
+/// Each cell is split into two triangles, intersected by marching the cells along the ray.
+pub struct Heightfield {
+    nx: usize,
+    ny: usize,
+    /// Row-major, `heights[j * nx + i]` is at `(i, j)` along `(x, y)`
+    heights: Vec<Float>,
+    /// Vertex normals from central differences
+    normals: Vec<Vector3f>,
+    extent: Vector3f,
+    bounds: Bounds3f,
+}
+
+impl Heightfield {
+    pub fn new(nx: usize, ny: usize, heights: Vec<Float>, extent: Vector3f) -> Self {
+        assert!(nx >= 2 && ny >= 2, "heightfield needs at least 2x2 samples");
+        assert_eq!(heights.len(), nx * ny);
+        debug_assert!(extent.x > 0. && extent.y > 0.);
+        let (h_min, h_max) = heights.iter().fold((Float::infinity(), Float::neg_infinity()), |(lo, hi), &h| (lo.min(h), hi.max(h)));
+        let bounds = Bounds3f::new(pt3(0., 0., h_min * extent.z), pt3(extent.x, extent.y, h_max * extent.z));
+        let mut hf = Self { nx, ny, heights, normals: Vec::new(), extent, bounds };
+        hf.normals = (0..nx * ny).map(|k| hf.vertex_normal(k % nx, k / nx)).collect();
+        hf
+    }
+
+    pub fn nx(&self) -> usize { self.nx }
+    pub fn ny(&self) -> usize { self.ny }
+    pub fn extent(&self) -> Vector3f { self.extent }
+    /// Unscaled height at grid point `(i, j)`
+    pub fn height(&self, i: usize, j: usize) -> Float { self.heights[j * self.nx + i] }
+
+    #[inline]
+    fn cell_size(&self) -> (Float, Float) {
+        (self.extent.x / (self.nx - 1) as Float, self.extent.y / (self.ny - 1) as Float)
+    }
+
+    #[inline]
+    fn vertex(&self, i: usize, j: usize) -> Point3f {
+        let (cx, cy) = self.cell_size();
+        pt3(i as Float * cx, j as Float * cy, self.height(i, j) * self.extent.z)
+    }
+
+    fn vertex_normal(&self, i: usize, j: usize) -> Vector3f {
+        let (i0, i1) = (i.saturating_sub(1), (i + 1).min(self.nx - 1));
+        let (j0, j1) = (j.saturating_sub(1), (j + 1).min(self.ny - 1));
+        let dzdx = (self.vertex(i1, j).z - self.vertex(i0, j).z) / (self.vertex(i1, j).x - self.vertex(i0, j).x);
+        let dzdy = (self.vertex(i, j1).z - self.vertex(i, j0).z) / (self.vertex(i, j1).y - self.vertex(i, j0).y);
+        vec3(-dzdx, -dzdy, 1.).normalize()
+    }
+
+    /// Test the two triangles of cell `(i, j)`, return the hit time, the vertices and their barycentric coordinates
+    fn intersect_cell(&self, ray: &Ray, i: usize, j: usize) -> Option<CellHit> {
+        let corners = [(i, j), (i + 1, j), (i + 1, j + 1), (i, j + 1)];
+        let mut hit: Option<CellHit> = None;
+        for tri in &[[corners[0], corners[1], corners[2]], [corners[0], corners[2], corners[3]]] {
+            let [a, b, c] = *tri;
+            if let Some((t, bary)) = intersect_triangle(ray, self.vertex(a.0, a.1), self.vertex(b.0, b.1), self.vertex(c.0, c.1)) {
+                if t < hit.map_or(Float::infinity(), |h| h.0) { hit = Some((t, *tri, bary)); }
+            }
+        }
+        hit
+    }
+
+    /// Walk the cells under the ray front to back, stop at the first one hit
+    fn march(&self, ray: &Ray) -> Option<CellHit> {
+        let inv_dir = vec3(1. / ray.dir.x, 1. / ray.dir.y, 1. / ray.dir.z);
+        let (t0, t1) = self.bounds.clip(ray, inv_dir)?;
+        let (cx, cy) = self.cell_size();
+        let start = ray.transport(t0);
+        let cell_of = |x: Float, size: Float, n: usize| num_traits::clamp((x / size).floor() as isize, 0, n as isize - 2);
+        let mut cell = [cell_of(start.x, cx, self.nx), cell_of(start.y, cy, self.ny)];
+        let size = [cx, cy];
+        let n = [self.nx as isize - 1, self.ny as isize - 1];
+        // per axis: step direction, the time to cross the next cell boundary, and to cross a whole cell
+        let mut step = [0; 2];
+        let mut t_next = [Float::infinity(); 2];
+        let mut t_delta = [Float::infinity(); 2];
+        for axis in 0..2 {
+            let d = ray.dir[axis];
+            if d > 0. {
+                step[axis] = 1;
+                t_next[axis] = t0 + ((cell[axis] + 1) as Float * size[axis] - start[axis]) / d;
+                t_delta[axis] = size[axis] / d;
+            } else if d < 0. {
+                step[axis] = -1;
+                t_next[axis] = t0 + (cell[axis] as Float * size[axis] - start[axis]) / d;
+                t_delta[axis] = -size[axis] / d;
+            }
+        }
+        let z_margin = 1e-4 * self.extent.z.abs();
+        let mut t_enter = t0;
+        loop {
+            let t_exit = t_next[0].min(t_next[1]).min(t1);
+            let (i, j) = (cell[0] as usize, cell[1] as usize);
+            // skip the cell if the ray passes entirely above or below it
+            let (za, zb) = (ray.org.z + ray.dir.z * t_enter, ray.org.z + ray.dir.z * t_exit);
+            let zs = [self.vertex(i, j).z, self.vertex(i + 1, j).z, self.vertex(i, j + 1).z, self.vertex(i + 1, j + 1).z];
+            let (z_lo, z_hi) = zs.iter().fold((Float::infinity(), Float::neg_infinity()), |(lo, hi), &z| (lo.min(z), hi.max(z)));
+            if za.min(zb) <= z_hi + z_margin && za.max(zb) >= z_lo - z_margin {
+                if let Some(hit) = self.intersect_cell(ray, i, j) { return Some(hit); }
+            }
+            if t_exit >= t1 { return None; }
+            let axis = if t_next[0] < t_next[1] { 0 } else { 1 };
+            cell[axis] += step[axis];
+            if cell[axis] < 0 || cell[axis] >= n[axis] { return None; }
+            t_enter = t_exit;
+            t_next[axis] += t_delta[axis];
+        }
+    }
+}
+
+impl Intersect for Heightfield {
+    /// `uv` spans `[0, 1]^2` over the terrain
+    fn intersect(&self, ray: &Ray) -> Option<GeometryIntersection> {
+        let (t, verts, bary) = self.march(ray)?;
+        let p = [self.vertex(verts[0].0, verts[0].1), self.vertex(verts[1].0, verts[1].1), self.vertex(verts[2].0, verts[2].1)];
+        let (pos, p_error) = barycentric_position(p, bary);
+        let ng = cross(p[1] - p[0], p[2] - p[0]).normalize(); // facing +z
+        let ns = verts.iter().zip(&bary)
+            .fold(Vector3f::zero(), |n, (&(i, j), &b)| n + self.normals[j * self.nx + i] * b)
+            .normalize();
+        let (normal, side) = if dot(ng, ray.dir) < 0. { (ns, Side::Outside) } else { (-ns, Side::Inside) };
+        Some(GeometryIntersection {
+            pos,
+            normal,
+            wi: -ray.dir,
+            t,
+            side,
+            uv: pt2(pos.x / self.extent.x, pos.y / self.extent.y),
+            dpdu: vec3(self.extent.x, 0., -ng.x / ng.z * self.extent.x),
+            dpdv: vec3(0., self.extent.y, -ng.y / ng.z * self.extent.y),
+            p_error,
+        })
+    }
+
+    fn intersect_p(&self, ray: &Ray) -> bool {
+        self.march(ray).is_some()
+    }
+}
+
+impl Geometry for Heightfield {
+    fn bounds(&self) -> Bounds3f { self.bounds }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::macros::*;
+    use rand::random;
+
+    /// z = (x^2 + y^2) / 8 on [0, 4]^2, sampled at the integers
+    fn bowl() -> Heightfield {
+        let heights = (0..25usize).map(|k| ((k % 5).pow(2) + (k / 5).pow(2)) as Float / 32.).collect();
+        Heightfield::new(5, 5, heights, vec3(4., 4., 4.))
+    }
+
+    #[test]
+    fn intersect() {
+        let hf = bowl();
+        assert_eq!(hf.bounds(), Bounds3f::new(pt3(0., 0., 0.), pt3(4., 4., 4.)));
+        // straight down onto a grid point
+        let its = hf.intersect(&Ray::new(pt3(2., 1., 10.), vec3(0., 0., -1.))).unwrap();
+        assert_approx!(its.pos.z, 5. / 8.);
+        assert_eq!(its.side, Side::Outside);
+        assert_approx!(its.uv.x, 0.5);
+        assert_approx!(its.uv.y, 0.25);
+        // central differences of the bowl are exact
+        assert_approx!((its.normal - vec3(-0.5, -0.25, 1.).normalize()).magnitude(), 0.);
+        assert_gt!(dot(its.dpdu.cross(its.dpdv), vec3(0., 0., 1.)), 0.);
+        // from below
+        let its = hf.intersect(&Ray::new(pt3(0.5, 0.5, -1.), vec3(0., 0., 1.))).unwrap();
+        assert_eq!(its.side, Side::Inside);
+        assert_lt!(its.normal.z, 0.);
+        // outside the grid
+        assert_eq!(hf.intersect(&Ray::new(pt3(5., 1., 10.), vec3(0., 0., -1.))), None);
+        // above, going up
+        assert!(!hf.intersect_p(&Ray::new(pt3(1., 1., 3.), vec3(0., 0.6, 0.8))));
+    }
+
+    #[test]
+    fn matches_brute_force() {
+        let nx = 17;
+        let ny = 9;
+        let heights: Vec<Float> = (0..nx * ny).map(|_| random()).collect();
+        let hf = Heightfield::new(nx, ny, heights, vec3(8., 4., 2.));
+        for _ in 0..500 {
+            let org = pt3(random::<Float>() * 12. - 2., random::<Float>() * 8. - 2., random::<Float>() * 4. - 1.);
+            let dir = vec3(random::<Float>() - 0.5, random::<Float>() - 0.5, random::<Float>() - 0.5).normalize();
+            let ray = Ray::new(org, dir);
+            let mut expected: Option<Float> = None;
+            for j in 0..ny - 1 {
+                for i in 0..nx - 1 {
+                    if let Some((t, _, _)) = hf.intersect_cell(&ray, i, j) {
+                        if expected.map_or(true, |e| t < e) { expected = Some(t); }
+                    }
+                }
+            }
+            let actual = hf.intersect(&ray).map(|its| its.t);
+            assert_eq!(actual, expected);
+            assert_eq!(hf.intersect_p(&ray), expected.is_some());
+        }
+    }
+}
@@ -0,0 +1,244 @@
+use super::*;
+use super::triangle::{intersect_triangle, barycentric_position};
+use crate::accel::Bvh;
+
+#[derive(Debug, Clone)]
+/// Indexed triangle mesh
+///
+/// Vertex attributes are stored in shared buffers, a face is a triple of indices into them
+pub struct Mesh {
+    positions: Vec<Point3f>,
+    /// Per-vertex shading normals, optional
+    normals: Option<Vec<Vector3f>>,
+    /// Per-vertex texture coordinates, optional
+    uvs: Option<Vec<Point2f>>,
+    /// Per-vertex colors, optional. Stored only, the shading doesn't read them
+    colors: Option<Vec<Spectrum>>,
+    /// Counter-clockwise viewed from the outside
+    indices: Vec<[u32; 3]>,
+    /// Over the faces
+    bvh: Bvh,
+}
+
+impl Mesh {
+    pub fn new(positions: Vec<Point3f>, indices: Vec<[u32; 3]>) -> Self {
+        let n = positions.len();
+        assert!(indices.iter().flatten().all(|&i| (i as usize) < n), "Mesh index out of bounds!");
+        let bounds: Vec<_> = indices.iter()
+            .map(|face| Bounds3f::from_points(face.iter().map(|&i| positions[i as usize])))
+            .collect();
+        let bvh = Bvh::build(&bounds);
+        Self { positions, normals: None, uvs: None, colors: None, indices, bvh }
+    }
+    pub fn set_normals(&mut self, normals: Vec<Vector3f>) {
+        assert_eq!(normals.len(), self.positions.len(), "Mismatched normal buffer size!");
+        self.normals = Some(normals.into_iter().map(|n| n.normalize()).collect());
+    }
+    pub fn set_uvs(&mut self, uvs: Vec<Point2f>) {
+        assert_eq!(uvs.len(), self.positions.len(), "Mismatched uv buffer size!");
+        self.uvs = Some(uvs);
+    }
+    /// Keep per-vertex colors with the mesh, e.g. for exporting. They aren't used for rendering, use a texture
+    pub fn set_colors(&mut self, colors: Vec<Spectrum>) {
+        assert_eq!(colors.len(), self.positions.len(), "Mismatched color buffer size!");
+        self.colors = Some(colors);
+    }
+    #[inline]
+    pub fn positions(&self) -> &[Point3f] { &self.positions }
+    #[inline]
+    pub fn normals(&self) -> Option<&[Vector3f]> { self.normals.as_deref() }
+    #[inline]
+    pub fn uvs(&self) -> Option<&[Point2f]> { self.uvs.as_deref() }
+    #[inline]
+    pub fn colors(&self) -> Option<&[Spectrum]> { self.colors.as_deref() }
+    #[inline]
+    pub fn indices(&self) -> &[[u32; 3]] { &self.indices }
+    #[inline]
+    pub fn n_triangles(&self) -> usize { self.indices.len() }
+    /// Get the `i`th face as a standalone triangle
+    pub fn triangle(&self, i: usize) -> Triangle {
+        let [p0, p1, p2] = self.face_positions(i);
+        Triangle::new(p0, p1, p2)
+    }
+
+    #[inline]
+    fn face_positions(&self, i: usize) -> [Point3f; 3] {
+        let [i0, i1, i2] = self.indices[i];
+        [self.positions[i0 as usize], self.positions[i1 as usize], self.positions[i2 as usize]]
+    }
+
+    /// Intersect with the `i`th face, return `(t, barycentric)`
+    #[inline]
+    fn intersect_face(&self, i: usize, ray: &Ray) -> Option<(Float, [Float; 3])> {
+        let [p0, p1, p2] = self.face_positions(i);
+        intersect_triangle(ray, p0, p1, p2)
+    }
+
+    /// Fill in the intersection info of the `i`th face
+    fn face_intersection(&self, i: usize, ray: &Ray, t: Float, b: [Float; 3]) -> GeometryIntersection {
+        let idx = self.indices[i];
+        let [p0, p1, p2] = self.face_positions(i);
+        let interpolate = |v0: Vector3f, v1: Vector3f, v2: Vector3f| v0 * b[0] + v1 * b[1] + v2 * b[2];
+        // geometric normal decides the side
+        let ng = cross(p1 - p0, p2 - p0).normalize();
+        let (ng, side) = if dot(ng, ray.dir) < 0. { (ng, Side::Outside) } else { (-ng, Side::Inside) };
+        // shading normal, flipped onto the same side as the geometric one
+        let normal = match &self.normals {
+            None => ng,
+            Some(ns) => {
+                let n = interpolate(ns[idx[0] as usize], ns[idx[1] as usize], ns[idx[2] as usize]);
+                let n = if n.magnitude2() > 0. { n.normalize() } else { ng };
+                if dot(n, ng) < 0. { -n } else { n }
+            }
+        };
+        let (uv, dpdu, dpdv) = match &self.uvs {
+            None => (pt2(b[1], b[2]), p1 - p0, p2 - p0),
+            Some(uvs) => {
+                let (uv0, uv1, uv2) = (uvs[idx[0] as usize], uvs[idx[1] as usize], uvs[idx[2] as usize]);
+                let uv = Point2::from_vec(uv0.to_vec() * b[0] + uv1.to_vec() * b[1] + uv2.to_vec() * b[2]);
+                // solve [p1 - p0, p2 - p0] = [dpdu, dpdv] [uv1 - uv0, uv2 - uv0]
+                let (duv1, duv2) = (uv1 - uv0, uv2 - uv0);
+                let (dp1, dp2) = (p1 - p0, p2 - p0);
+                let det = duv1.x * duv2.y - duv1.y * duv2.x;
+                if det.abs() < 1e-12 { // degenerate uvs
+                    let frame = onb(ng);
+                    (uv, frame.x, frame.y)
+                } else {
+                    let inv_det = 1. / det;
+                    (uv, (dp1 * duv2.y - dp2 * duv1.y) * inv_det, (dp2 * duv1.x - dp1 * duv2.x) * inv_det)
+                }
+            }
+        };
+        let (pos, p_error) = barycentric_position([p0, p1, p2], b);
+        GeometryIntersection {
+            pos,
+            normal,
+            wi: -ray.dir,
+            t,
+            side,
+            uv,
+            dpdu,
+            dpdv,
+            p_error,
+        }
+    }
+}
+
+/// Area weighted averages of the normals of the faces around each vertex, `+z` for unused vertices
+pub(crate) fn vertex_normals(positions: &[Point3f], indices: &[[u32; 3]]) -> Vec<Vector3f> {
+    let mut normals = vec![Vector3f::zero(); positions.len()];
+    for &[a, b, c] in indices {
+        let n = cross(positions[b as usize] - positions[a as usize], positions[c as usize] - positions[a as usize]);
+        for &i in &[a, b, c] {
+            normals[i as usize] += n;
+        }
+    }
+    normals.into_iter().map(|n| if n.magnitude2() > 0. { n.normalize() } else { vec3(0., 0., 1.) }).collect()
+}
+
+impl Intersect for Mesh {
+    fn intersect(&self, ray: &Ray) -> Option<GeometryIntersection> {
+        self.bvh.nearest(ray, |i, ray| self.intersect_face(i, ray).map(|(t, b)| (t, (i, t, b))))
+            .map(|(i, t, b)| self.face_intersection(i, ray, t, b))
+    }
+
+    fn intersect_p(&self, ray: &Ray) -> bool {
+        self.bvh.any(ray, |i, ray| self.intersect_face(i, ray).is_some())
+    }
+}
+
+impl Geometry for Mesh {
+    fn bounds(&self) -> Bounds3f { self.bvh.bounds() }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::macros::*;
+
+    /// Unit quad on z = 0 made of two triangles
+    fn quad() -> Mesh {
+        Mesh::new(
+            vec![pt3(0., 0., 0.), pt3(1., 0., 0.), pt3(1., 1., 0.), pt3(0., 1., 0.)],
+            vec![[0, 1, 2], [0, 2, 3]],
+        )
+    }
+
+    #[test]
+    fn nearest_face() {
+        // two stacked quads
+        let mesh = Mesh::new(
+            vec![pt3(0., 0., 0.), pt3(1., 0., 0.), pt3(1., 1., 0.), pt3(0., 1., 0.),
+                 pt3(0., 0., 1.), pt3(1., 0., 1.), pt3(1., 1., 1.), pt3(0., 1., 1.)],
+            vec![[0, 1, 2], [0, 2, 3], [4, 5, 6], [4, 6, 7]],
+        );
+        let r = Ray::new(pt3(0.7, 0.2, 3.), vec3(0., 0., -1.));
+        let its = mesh.intersect(&r).unwrap();
+        assert_approx!(its.t, 2.);
+        let r = Ray::new(pt3(0.7, 0.2, -3.), vec3(0., 0., 1.));
+        let its = mesh.intersect(&r).unwrap();
+        assert_approx!(its.t, 3.);
+        assert_eq!(its.side, Side::Inside);
+        let r = Ray::new(pt3(1.7, 0.2, -3.), vec3(0., 0., 1.));
+        assert_eq!(mesh.intersect(&r), None);
+    }
+
+    #[test]
+    fn bvh_matches_brute_force() {
+        // a bumpy grid
+        let n = 40;
+        let positions = (0..=n).flat_map(|y| (0..=n).map(move |x| {
+            let (x, y) = (x as Float / n as Float, y as Float / n as Float);
+            pt3(x, y, 0.1 * (10. * x).sin() * (7. * y).cos())
+        })).collect();
+        let row = n + 1;
+        let indices = (0..n).flat_map(|y| (0..n).flat_map(move |x| {
+            let i = y * row + x;
+            vec![[i, i + 1, i + row + 1], [i, i + row + 1, i + row]]
+        })).collect();
+        let mesh = Mesh::new(positions, indices);
+        assert_eq!(mesh.bounds(), Bounds3f::from_points(mesh.positions().iter().cloned()));
+        for _ in 0..500 {
+            let org = pt3(rand::random(), rand::random(), 1.);
+            let dir = (pt3(rand::random(), rand::random(), 0.) - org).normalize();
+            let r = Ray::new(org, dir);
+            let expected = (0..mesh.n_triangles())
+                .filter_map(|i| mesh.triangle(i).intersect(&r))
+                .map(|its| its.t)
+                .fold(None, |t: Option<Float>, t1| Some(t.map_or(t1, |t| t.min(t1))));
+            assert_eq!(mesh.intersect(&r).map(|its| its.t), expected);
+            assert_eq!(mesh.intersect_p(&r), expected.is_some());
+        }
+    }
+
+    #[test]
+    fn interpolate_attributes() {
+        let mut mesh = quad();
+        mesh.set_uvs(vec![pt2(0., 0.), pt2(2., 0.), pt2(2., 2.), pt2(0., 2.)]);
+        mesh.set_normals(vec![vec3(-1., 0., 1.), vec3(1., 0., 1.), vec3(1., 0., 1.), vec3(-1., 0., 1.)]);
+        let r = Ray::new(pt3(0.5, 0.25, 1.), vec3(0., 0., -1.));
+        let its = mesh.intersect(&r).unwrap();
+        assert_approx!(its.uv.x, 1.);
+        assert_approx!(its.uv.y, 0.5);
+        assert_approx!((its.dpdu - vec3(0.5, 0., 0.)).magnitude(), 0.);
+        assert_approx!((its.dpdv - vec3(0., 0.5, 0.)).magnitude(), 0.);
+        assert_approx!((its.normal - vec3(0., 0., 1.)).magnitude(), 0.);
+        // shading normal stays on the ray's side
+        let r = Ray::new(pt3(0.5, 0.25, -1.), vec3(0., 0., 1.));
+        let its = mesh.intersect(&r).unwrap();
+        assert_le!(dot(its.normal, r.dir), 0.);
+    }
+
+    #[test]
+    fn primitive_wraps_mesh() {
+        let prim = Primitive::new(
+            quad(),
+            Arc::new(Material { bsdf: bsdf::Simple::default(), texture: texture::Uniform::default(), emission: Spectrum::black() }),
+            Matrix4::from_translation(vec3(0., 0., -5.)),
+        );
+        let r = Ray::new(pt3(0.9, 0.9, 0.), vec3(0., 0., -1.));
+        let its = prim.intersect(&r).unwrap();
+        assert_approx!(its.t, 5.);
+        assert_approx!((its.pos - pt3(0.9, 0.9, -5.)).magnitude(), 0.);
+    }
+}
@@ -2,18 +2,93 @@ use super::*;
 
 mod sphere;
 mod triangle;
+mod mesh;
+mod plane;
+mod rectangle;
+mod disk;
+mod cylinder;
+mod cone;
+mod paraboloid;
+mod torus;
+mod sdf;
+mod heightfield;
+mod csg;
+mod curve;
+mod subdivision;
+mod displacement;
 mod dynamic;
 
 pub use sphere::Sphere;
 pub use triangle::Triangle;
+pub use mesh::Mesh;
+pub use plane::Plane;
+pub use rectangle::Rectangle;
+pub use disk::Disk;
+pub use cylinder::Cylinder;
+pub use cone::Cone;
+pub use paraboloid::Paraboloid;
+pub use torus::Torus;
+pub use sdf::{Sdf, SdfGeometry};
+pub use heightfield::Heightfield;
+pub use csg::{Csg, CsgOp};
+pub use curve::{Curve, CurveType, Curves};
 pub use dynamic::DynamicGeometry;
 
 pub trait Geometry: Intersect + Send + Sync + 'static {
-    // Others...
+    /// Local space bounding box
+    fn bounds(&self) -> Bounds3f;
 }
 
 pub trait Intersect: Debug + Clone {
     /// Local ray to local intersection
     fn intersect(&self, ray: &Ray) -> Option<GeometryIntersection>;
+
+    /// Is there any hit within the ray range? Can be cheaper than `intersect`
+    fn intersect_p(&self, ray: &Ray) -> bool {
+        self.intersect(ray).is_some()
+    }
+}
+
+
+/// Shared geometry, e.g. `Arc<Mesh>`, lets many primitives instance it with their own transforms and materials
+///
+/// The scene bvh over the instances and the geometry's own acceleration structure, as in `Mesh`, form a two-level hierarchy.
+impl<G: Geometry> Intersect for Arc<G> {
+    #[inline]
+    fn intersect(&self, ray: &Ray) -> Option<GeometryIntersection> { (**self).intersect(ray) }
+
+    #[inline]
+    fn intersect_p(&self, ray: &Ray) -> bool { (**self).intersect_p(ray) }
+}
+
+impl<G: Geometry> Geometry for Arc<G> {
+    #[inline]
+    fn bounds(&self) -> Bounds3f { (**self).bounds() }
+}
+
+/// Angle of `pos` around the z-axis, in `[0, 2pi)`
+#[inline]
+fn azimuth(pos: Point3f) -> Float {
+    let phi = pos.y.atan2(pos.x);
+    if phi < 0. { phi + 2. * Float::PI() } else { phi }
 }
 
+/// Flip the outward `normal` to the side the ray comes from
+///
+/// `pos` is taken as computed from `ray.transport(t)`, possibly refined, for the error bound
+#[inline]
+fn facing_ray(ray: &Ray, t: Float, pos: Point3f, normal: Vector3f, uv: Point2f, dpdu: Vector3f, dpdv: Vector3f) -> GeometryIntersection {
+    let (normal, side) = if dot(normal, ray.dir) < 0. { (normal, Side::Outside) } else { (-normal, Side::Inside) };
+    let p_error = (ray.org.to_vec().map(Float::abs) + (ray.dir * t).map(Float::abs)) * gamma(5);
+    GeometryIntersection { pos, normal, wi: -ray.dir, t, side, uv, dpdu, dpdv, p_error }
+}
+
+/// Nearest root of `a t^2 + b t + c` within the ray range accepted by `hit`
+fn nearest_quadric_hit(ray: &Ray, a: f64, b: f64, c: f64, hit: impl Fn(Float, Point3f) -> Option<GeometryIntersection>) -> Option<GeometryIntersection> {
+    let (t0, t1) = solve_quadratic(a, b, c)?;
+    for &t in &[t0 as Float, t1 as Float] {
+        if t <= 0. || !ray.in_range(t) { continue; }
+        if let Some(its) = hit(t, ray.transport(t)) { return Some(its); }
+    }
+    None
+}
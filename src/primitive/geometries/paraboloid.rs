@@ -0,0 +1,92 @@
+use super::*;
+use crate::macros::*;
+
+#[derive(Debug, Clone)]
+/// Paraboloid `z = z_max (x^2 + y^2) / radius^2` around the z-axis,
+/// clipped to `z_min <= z <= z_max` and `phi <= phi_max`
+pub struct Paraboloid {
+    radius: Float,
+    z_min: Float,
+    z_max: Float,
+    phi_max: Float,
+}
+
+impl Paraboloid {
+    /// `radius` is at the rim `z = z_max`
+    pub fn new(radius: Float, z_min: Float, z_max: Float) -> Self {
+        debug_assert_gt!(radius, 0.);
+        debug_assert_ge!(z_min, 0.);
+        debug_assert_lt!(z_min, z_max);
+        Self { radius, z_min, z_max, phi_max: 2. * Float::PI() }
+    }
+    /// Keep only the sweep `0 <= phi <= phi_max` around the z-axis
+    pub fn with_phi_max<A>(mut self, phi_max: A) -> Self where A: Into<Radf> {
+        self.phi_max = num_traits::clamp(phi_max.into().0, 0., 2. * Float::PI());
+        self
+    }
+    pub fn radius(&self) -> Float { self.radius }
+}
+
+impl Intersect for Paraboloid {
+    /// `u = phi / phi_max`, `v` goes from `z_min` to `z_max`
+    fn intersect(&self, ray: &Ray) -> Option<GeometryIntersection> {
+        let (ox, oy, oz) = (ray.org.x as f64, ray.org.y as f64, ray.org.z as f64);
+        let (dx, dy, dz) = (ray.dir.x as f64, ray.dir.y as f64, ray.dir.z as f64);
+        let k = self.z_max as f64 / (self.radius as f64).powi(2);
+        let a = k * (dx * dx + dy * dy);
+        let b = 2. * k * (dx * ox + dy * oy) - dz;
+        let c = k * (ox * ox + oy * oy) - oz;
+        nearest_quadric_hit(ray, a, b, c, |t, pos| {
+            let phi = azimuth(pos);
+            if pos.z < self.z_min || pos.z > self.z_max || phi > self.phi_max { return None; }
+            let k = k as Float;
+            let dz = self.z_max - self.z_min;
+            // away from the axis, i.e. the convex side
+            let normal = vec3(2. * k * pos.x, 2. * k * pos.y, -1.).normalize();
+            let dpdv = if pos.z > 0. { vec3(pos.x / (2. * pos.z), pos.y / (2. * pos.z), 1.) * dz } else { vec3(0., 0., dz) };
+            Some(facing_ray(
+                ray, t, pos, normal,
+                pt2(phi / self.phi_max, (pos.z - self.z_min) / dz),
+                vec3(-pos.y, pos.x, 0.) * self.phi_max,
+                dpdv,
+            ))
+        })
+    }
+}
+
+impl Geometry for Paraboloid {
+    fn bounds(&self) -> Bounds3f {
+        Bounds3f::new(pt3(-self.radius, -self.radius, self.z_min), pt3(self.radius, self.radius, self.z_max))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn intersect() {
+        let bowl = Paraboloid::new(2., 0., 4.); // z = x^2 + y^2
+        let its = bowl.intersect(&Ray::new(pt3(1., 0., 5.), vec3(0., 0., -1.))).unwrap();
+        assert_approx!(its.pos.z, 1.);
+        assert_approx!(its.uv.y, 0.25);
+        assert_eq!(its.side, Side::Inside); // looking into the bowl
+        assert_approx!((its.normal - vec3(-2., 0., 1.).normalize()).magnitude(), 0.);
+        let its = bowl.intersect(&Ray::new(pt3(0., 0., -1.), vec3(0., 0., 1.))).unwrap(); // the vertex
+        assert_approx!(its.pos.z, 0.);
+        assert_eq!(its.side, Side::Outside);
+        // from the side, through both walls
+        let its = bowl.intersect(&Ray::new(pt3(-5., 0., 1.), vec3(1., 0., 0.))).unwrap();
+        assert_approx!(its.pos.x, -1.);
+        assert_eq!(its.side, Side::Outside);
+        assert_gt!(dot(its.dpdu.cross(its.dpdv), vec3(-1., 0., 0.)), 0.);
+        // clipped
+        let ring = Paraboloid::new(2., 2., 4.);
+        let its = ring.intersect(&Ray::new(pt3(1., 0., 5.), vec3(0., 0., -1.)));
+        assert_eq!(its, None);
+        assert_eq!(bowl.intersect(&Ray::new(pt3(-5., 0., 4.5), vec3(1., 0., 0.))), None);
+        let half = bowl.with_phi_max(Deg(180.));
+        assert!(half.intersect(&Ray::new(pt3(1., 0.1, 5.), vec3(0., 0., -1.))).is_some());
+        assert!(half.intersect(&Ray::new(pt3(1., -0.1, 5.), vec3(0., 0., -1.))).is_none());
+    }
+}
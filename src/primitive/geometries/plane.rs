@@ -0,0 +1,63 @@
+use super::*;
+
+#[derive(Debug, Clone, Default)]
+/// Infinite plane `z = 0`, facing +z
+pub struct Plane;
+
+/// Hit the plane `z = 0` within the ray range, return `(t, pos)`
+pub(super) fn intersect_plane(ray: &Ray) -> Option<(Float, Point3f)> {
+    if ray.dir.z == 0. { return None; } // parallel
+    let t = -ray.org.z / ray.dir.z;
+    if t <= 0. || !ray.in_range(t) { return None; }
+    let mut pos = ray.transport(t);
+    pos.z = 0.; // exactly on the plane, x and y are off along it only
+    Some((t, pos))
+}
+
+/// Normal and side of a hit on the plane `z = 0`
+#[inline]
+pub(super) fn plane_side(ray: &Ray) -> (Vector3f, Side) {
+    if ray.dir.z < 0. { (Vector3f::unit_z(), Side::Outside) } else { (-Vector3f::unit_z(), Side::Inside) }
+}
+
+impl Intersect for Plane {
+    /// `uv` is the local `(x, y)`
+    fn intersect(&self, ray: &Ray) -> Option<GeometryIntersection> {
+        let (t, pos) = intersect_plane(ray)?;
+        let (normal, side) = plane_side(ray);
+        Some(GeometryIntersection {
+            pos,
+            normal,
+            wi: -ray.dir,
+            t,
+            side,
+            uv: pt2(pos.x, pos.y),
+            dpdu: Vector3f::unit_x(),
+            dpdv: Vector3f::unit_y(),
+            p_error: Vector3f::zero(),
+        })
+    }
+}
+
+impl Geometry for Plane {
+    fn bounds(&self) -> Bounds3f {
+        Bounds3f::new(pt3(Float::neg_infinity(), Float::neg_infinity(), 0.), pt3(Float::infinity(), Float::infinity(), 0.))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn intersect() {
+        let its = Plane.intersect(&Ray::new(pt3(1e4, -3., 2.), vec3(0., 0.6, -0.8))).unwrap();
+        assert_approx!(its.t, 2.5);
+        assert_eq!(its.pos.z, 0.);
+        assert_eq!(its.uv, pt2(its.pos.x, its.pos.y));
+        assert_eq!(its.side, Side::Outside);
+        assert_eq!(Plane.intersect(&Ray::new(pt3(0., 0., 1.), vec3(1., 0., 0.))), None);
+        assert_eq!(Plane.intersect(&Ray::new_with_range(pt3(0., 0., 1.), vec3(0., 0., -1.), 0., 0.5)), None);
+        assert!(!Plane.bounds().is_finite());
+    }
+}
@@ -0,0 +1,77 @@
+use super::*;
+use super::plane::{intersect_plane, plane_side};
+use crate::macros::*;
+
+#[derive(Debug, Clone)]
+/// Rectangle on `z = 0` centered at the origin, facing +z
+pub struct Rectangle {
+    width: Float,
+    height: Float,
+}
+
+impl Rectangle {
+    /// `width` along x, `height` along y
+    pub fn new(width: Float, height: Float) -> Self {
+        debug_assert_gt!(width, 0.);
+        debug_assert_gt!(height, 0.);
+        Self { width, height }
+    }
+    pub fn width(&self) -> Float { self.width }
+    pub fn height(&self) -> Float { self.height }
+    pub fn area(&self) -> Float { self.width * self.height }
+}
+
+impl Intersect for Rectangle {
+    /// `uv` spans `[0, 1]^2` from the `(-x, -y)` corner
+    fn intersect(&self, ray: &Ray) -> Option<GeometryIntersection> {
+        let (t, pos) = intersect_plane(ray)?;
+        let (u, v) = (pos.x / self.width + 0.5, pos.y / self.height + 0.5);
+        if !(0. ..=1.).contains(&u) || !(0. ..=1.).contains(&v) { return None; }
+        let (normal, side) = plane_side(ray);
+        Some(GeometryIntersection {
+            pos,
+            normal,
+            wi: -ray.dir,
+            t,
+            side,
+            uv: pt2(u, v),
+            dpdu: vec3(self.width, 0., 0.),
+            dpdv: vec3(0., self.height, 0.),
+            p_error: Vector3f::zero(),
+        })
+    }
+}
+
+impl Geometry for Rectangle {
+    fn bounds(&self) -> Bounds3f {
+        let (x, y) = (0.5 * self.width, 0.5 * self.height);
+        Bounds3f::new(pt3(-x, -y, 0.), pt3(x, y, 0.))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn intersect() {
+        let rect = Rectangle::new(2., 1.);
+        let its = rect.intersect(&Ray::new(pt3(0.5, 0.25, 3.), vec3(0., 0., -1.))).unwrap();
+        assert_eq!(its.t, 3.);
+        assert_eq!(its.pos, pt3(0.5, 0.25, 0.));
+        assert_eq!(its.uv, pt2(0.75, 0.75));
+        assert_eq!(its.normal, vec3(0., 0., 1.));
+        assert_eq!(its.side, Side::Outside);
+        let its = rect.intersect(&Ray::new(pt3(-0.9, -0.4, -1.), vec3(0., 0., 1.))).unwrap();
+        assert_eq!(its.normal, vec3(0., 0., -1.));
+        assert_eq!(its.side, Side::Inside);
+        assert_eq!(rect.intersect(&Ray::new(pt3(0.5, 0.6, 3.), vec3(0., 0., -1.))), None);
+        assert_eq!(rect.intersect(&Ray::new(pt3(0.5, 0.25, 3.), vec3(0., 0., 1.))), None);
+        assert_eq!(rect.intersect(&Ray::new(pt3(0.5, 0.25, 3.), vec3(1., 0., 0.))), None);
+        // oblique
+        let dir = vec3(1., 1., -1.).normalize();
+        let its = rect.intersect(&Ray::new(pt3(-1., -1., 1.), dir)).unwrap();
+        assert_approx!((its.pos - pt3(0., 0., 0.)).magnitude(), 0.);
+        assert_approx!(its.t, (3. as Float).sqrt());
+    }
+}
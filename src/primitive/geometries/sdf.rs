@@ -0,0 +1,339 @@
+use super::*;
+use crate::macros::*;
+
+#[derive(Debug, Clone)]
+/// Composable signed distance function, negative inside
+///
+/// Build trees with the constructors and combinators, e.g.
+/// `Sdf::sphere(1.).smooth_subtract(Sdf::cuboid(vec3(2., 0.5, 0.5)), 0.1).repeat(vec3(4., 4., 0.))`
+pub enum Sdf {
+    Sphere { radius: Float },
+    /// Axis-aligned box centered at the origin
+    Cuboid { half_extents: Vector3f },
+    /// Around the z-axis, like `Torus`
+    Torus { major_radius: Float, minor_radius: Float },
+    /// Capped, around the z-axis with `-half_height <= z <= half_height`
+    Cylinder { radius: Float, half_height: Float },
+    /// The half space `z <= 0`
+    Plane,
+    /// Distance estimation of the Mandelbulb fractal, which fits in the radius 1.2 ball
+    Mandelbulb { power: Float, iterations: usize },
+    /// `smoothness` is the blending width, 0 for a sharp edge
+    Union(Box<Sdf>, Box<Sdf>, Float),
+    /// The first minus the second
+    Subtract(Box<Sdf>, Box<Sdf>, Float),
+    Intersection(Box<Sdf>, Box<Sdf>, Float),
+    Translate(Box<Sdf>, Vector3f),
+    Scale(Box<Sdf>, Float),
+    /// Infinite copies along the axes with nonzero periods, the cell at the origin is centered at it
+    Repeat(Box<Sdf>, Vector3f),
+}
+
+impl Sdf {
+    pub fn sphere(radius: Float) -> Self {
+        debug_assert_gt!(radius, 0.);
+        Sdf::Sphere { radius }
+    }
+    pub fn cuboid(half_extents: Vector3f) -> Self { Sdf::Cuboid { half_extents } }
+    pub fn torus(major_radius: Float, minor_radius: Float) -> Self { Sdf::Torus { major_radius, minor_radius } }
+    pub fn cylinder(radius: Float, half_height: Float) -> Self { Sdf::Cylinder { radius, half_height } }
+    pub fn mandelbulb(power: Float, iterations: usize) -> Self { Sdf::Mandelbulb { power, iterations } }
+
+    pub fn union(self, other: Sdf) -> Self { Sdf::Union(Box::new(self), Box::new(other), 0.) }
+    pub fn subtract(self, other: Sdf) -> Self { Sdf::Subtract(Box::new(self), Box::new(other), 0.) }
+    pub fn intersection(self, other: Sdf) -> Self { Sdf::Intersection(Box::new(self), Box::new(other), 0.) }
+    pub fn smooth_union(self, other: Sdf, smoothness: Float) -> Self { Sdf::Union(Box::new(self), Box::new(other), smoothness) }
+    pub fn smooth_subtract(self, other: Sdf, smoothness: Float) -> Self { Sdf::Subtract(Box::new(self), Box::new(other), smoothness) }
+    pub fn smooth_intersection(self, other: Sdf, smoothness: Float) -> Self { Sdf::Intersection(Box::new(self), Box::new(other), smoothness) }
+    pub fn translate(self, offset: Vector3f) -> Self { Sdf::Translate(Box::new(self), offset) }
+    pub fn scale(self, factor: Float) -> Self {
+        debug_assert_gt!(factor, 0.);
+        Sdf::Scale(Box::new(self), factor)
+    }
+    pub fn repeat(self, period: Vector3f) -> Self { Sdf::Repeat(Box::new(self), period) }
+
+    /// Signed distance from `p` to the surface, or a lower bound of it for smooth operators and fractals
+    pub fn distance(&self, p: Point3f) -> Float {
+        match self {
+            Sdf::Sphere { radius } => p.to_vec().magnitude() - radius,
+            Sdf::Cuboid { half_extents } => {
+                let q = vec3(p.x.abs(), p.y.abs(), p.z.abs()) - half_extents;
+                vec3(q.x.max(0.), q.y.max(0.), q.z.max(0.)).magnitude() + q.x.max(q.y).max(q.z).min(0.)
+            }
+            Sdf::Torus { major_radius, minor_radius } => {
+                let rho = (p.x * p.x + p.y * p.y).sqrt() - major_radius;
+                (rho * rho + p.z * p.z).sqrt() - minor_radius
+            }
+            Sdf::Cylinder { radius, half_height } => {
+                let d = vec2((p.x * p.x + p.y * p.y).sqrt() - radius, p.z.abs() - half_height);
+                vec2(d.x.max(0.), d.y.max(0.)).magnitude() + d.x.max(d.y).min(0.)
+            }
+            Sdf::Plane => p.z,
+            Sdf::Mandelbulb { power, iterations } => mandelbulb(p.to_vec(), *power, *iterations),
+            Sdf::Union(a, b, k) => smooth_min(a.distance(p), b.distance(p), *k),
+            Sdf::Subtract(a, b, k) => -smooth_min(-a.distance(p), b.distance(p), *k),
+            Sdf::Intersection(a, b, k) => -smooth_min(-a.distance(p), -b.distance(p), *k),
+            Sdf::Translate(a, offset) => a.distance(p - offset),
+            Sdf::Scale(a, s) => a.distance(Point3::from_vec(p.to_vec() / *s)) * s,
+            Sdf::Repeat(a, period) => {
+                let mut q = p;
+                for i in 0..3 {
+                    if period[i] > 0. { q[i] -= period[i] * (q[i] / period[i]).round(); }
+                }
+                a.distance(q)
+            }
+        }
+    }
+
+    /// A box containing the surface, not finite for planes and repetitions
+    pub fn bounds(&self) -> Bounds3f {
+        match self {
+            Sdf::Sphere { radius } => Bounds3f::new(pt3(-radius, -radius, -radius), pt3(*radius, *radius, *radius)),
+            Sdf::Cuboid { half_extents } => Bounds3f::new(Point3::from_vec(-*half_extents), Point3::from_vec(*half_extents)),
+            Sdf::Torus { major_radius, minor_radius } => {
+                let xy = major_radius + minor_radius;
+                Bounds3f::new(pt3(-xy, -xy, -minor_radius), pt3(xy, xy, *minor_radius))
+            }
+            Sdf::Cylinder { radius, half_height } => Bounds3f::new(pt3(-radius, -radius, -half_height), pt3(*radius, *radius, *half_height)),
+            Sdf::Plane => Bounds3f::new(pt3(Float::neg_infinity(), Float::neg_infinity(), Float::neg_infinity()), pt3(Float::infinity(), Float::infinity(), 0.)),
+            Sdf::Mandelbulb { .. } => Bounds3f::new(pt3(-1.2, -1.2, -1.2), pt3(1.2, 1.2, 1.2)),
+            Sdf::Union(a, b, k) => { // the blend bulges by at most k / 4
+                let b = a.bounds().union(&b.bounds());
+                Bounds3f { min: b.min - Vector3f::from_value(0.25 * k), max: b.max + Vector3f::from_value(0.25 * k) }
+            }
+            Sdf::Subtract(a, _, _) => a.bounds(),
+            Sdf::Intersection(a, b, _) => a.bounds().intersection(&b.bounds()),
+            Sdf::Translate(a, offset) => {
+                let b = a.bounds();
+                if b.is_empty() { b } else { Bounds3f { min: b.min + offset, max: b.max + offset } }
+            }
+            Sdf::Scale(a, s) => {
+                let b = a.bounds();
+                if b.is_empty() { b } else { Bounds3f { min: b.min * *s, max: b.max * *s } }
+            }
+            Sdf::Repeat(a, period) => {
+                let mut b = a.bounds();
+                for i in 0..3 {
+                    if period[i] > 0. {
+                        b.min[i] = Float::neg_infinity();
+                        b.max[i] = Float::infinity();
+                    }
+                }
+                b
+            }
+        }
+    }
+}
+
+/// Polynomial smooth minimum, exact `min` when `k == 0`
+#[inline]
+fn smooth_min(a: Float, b: Float, k: Float) -> Float {
+    if k <= 0. { return a.min(b); }
+    let h = (k - (a - b).abs()).max(0.) / k;
+    a.min(b) - h * h * k * 0.25
+}
+
+fn mandelbulb(c: Vector3f, power: Float, iterations: usize) -> Float {
+    let mut z = c;
+    let mut dr = 1.;
+    let mut r = z.magnitude();
+    for _ in 0..iterations {
+        if r > 2. { break; }
+        dr = r.powf(power - 1.) * power * dr + 1.;
+        z = if r > 0. {
+            let theta = (z.z / r).acos() * power;
+            let phi = z.y.atan2(z.x) * power;
+            vec3(theta.sin() * phi.cos(), theta.sin() * phi.sin(), theta.cos()) * r.powf(power) + c
+        } else { c };
+        r = z.magnitude();
+    }
+    if r > 0. { 0.5 * r.ln() * r / dr } else { 0. }
+}
+
+#[derive(Debug, Clone)]
+/// Surface `sdf = 0`, intersected by sphere tracing
+///
+/// There is no parameterization, `uv` is always zero and `dpdu, dpdv` are an arbitrary tangent frame.
+pub struct SdfGeometry {
+    sdf: Sdf,
+    bounds: Bounds3f,
+    /// Distances below it count as hits, also the step of gradient estimation
+    precision: Float,
+    max_steps: usize,
+}
+
+impl SdfGeometry {
+    pub fn new(sdf: Sdf) -> Self {
+        let bounds = sdf.bounds();
+        Self { sdf, bounds, precision: 1e-4, max_steps: 256 }
+    }
+    pub fn with_precision(mut self, precision: Float) -> Self {
+        debug_assert_gt!(precision, 0.);
+        self.precision = precision;
+        self
+    }
+    /// Rays escaping after so many steps count as misses, raise it for fractals and grazing views
+    pub fn with_max_steps(mut self, max_steps: usize) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+    pub fn sdf(&self) -> &Sdf { &self.sdf }
+
+    /// Central differences on a tetrahedron
+    fn gradient(&self, p: Point3f) -> Vector3f {
+        let h = self.precision;
+        [vec3(1., -1., -1.), vec3(-1., -1., 1.), vec3(-1., 1., -1.), vec3(1., 1., 1.)].iter()
+            .map(|k| k * self.sdf.distance(p + k * h))
+            .fold(Vector3f::zero(), |g, v| g + v)
+    }
+
+    /// March from the start of the clipped ray, return the hit time
+    fn march(&self, ray: &Ray) -> Option<Float> {
+        let inv_dir = vec3(1. / ray.dir.x, 1. / ray.dir.y, 1. / ray.dir.z);
+        let (t0, t1) = self.bounds.clip(ray, inv_dir)?;
+        let mut t = t0.max(0.);
+        // a surface the ray starts on is skipped, unless the start is moved up to the bounds which may touch it
+        let mut left_surface = t > ray.t_min.max(0.);
+        for _ in 0..self.max_steps {
+            if t > t1 { return None; }
+            let d = self.sdf.distance(ray.transport(t)).abs();
+            if d < self.precision {
+                if left_surface && t > 0. && ray.in_range(t) { return Some(t); }
+                t += self.precision;
+            } else {
+                left_surface = true;
+                t += d;
+            }
+        }
+        None
+    }
+}
+
+impl Intersect for SdfGeometry {
+    fn intersect(&self, ray: &Ray) -> Option<GeometryIntersection> {
+        let t = self.march(ray)?;
+        let pos = ray.transport(t);
+        let gradient = self.gradient(pos);
+        let normal = if gradient.magnitude2() > 0. { gradient.normalize() } else { -ray.dir };
+        let frame = onb(normal);
+        let mut its = facing_ray(ray, t, pos, normal, Point2::origin(), frame.x, frame.y);
+        // the march stops anywhere within `precision` of the surface
+        its.p_error += vec3(self.precision, self.precision, self.precision);
+        Some(its)
+    }
+
+    fn intersect_p(&self, ray: &Ray) -> bool {
+        self.march(ray).is_some()
+    }
+}
+
+impl Geometry for SdfGeometry {
+    fn bounds(&self) -> Bounds3f { self.bounds }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn distance() {
+        let p = pt3(2., 0., 0.);
+        assert_eq!(Sdf::sphere(1.).distance(p), 1.);
+        assert_eq!(Sdf::cuboid(vec3(1., 1., 1.)).distance(p), 1.);
+        assert_eq!(Sdf::cuboid(vec3(1., 1., 1.)).distance(pt3(0.5, 0., 0.)), -0.5);
+        assert_eq!(Sdf::torus(2., 0.5).distance(p), -0.5);
+        assert_eq!(Sdf::cylinder(1., 1.).distance(pt3(0., 0., 3.)), 2.);
+        let two = Sdf::sphere(1.).union(Sdf::sphere(1.).translate(vec3(3., 0., 0.)));
+        assert_eq!(two.distance(p), 0.);
+        assert_eq!(two.distance(pt3(1.5, 0., 0.)), 0.5);
+        // blended in the gap
+        let blend = Sdf::sphere(1.).smooth_union(Sdf::sphere(1.).translate(vec3(3., 0., 0.)), 1.);
+        assert_lt!(blend.distance(pt3(1.5, 0., 0.)), 0.5);
+        assert_eq!(blend.distance(pt3(-2., 0., 0.)), 1.);
+        let hollow = Sdf::sphere(2.).subtract(Sdf::sphere(1.));
+        assert_eq!(hollow.distance(Point3::origin()), 1.);
+        assert_eq!(hollow.distance(pt3(1.5, 0., 0.)), -0.5);
+        let lens = Sdf::sphere(2.).translate(vec3(1., 0., 0.)).intersection(Sdf::sphere(2.).translate(vec3(-1., 0., 0.)));
+        assert_eq!(lens.distance(Point3::origin()), -1.);
+        assert_eq!(Sdf::sphere(1.).scale(2.).distance(pt3(3., 0., 0.)), 1.);
+        let grid = Sdf::sphere(1.).repeat(vec3(4., 4., 0.));
+        assert_eq!(grid.distance(pt3(8., -4., 0.)), -1.);
+        assert_eq!(grid.distance(pt3(10., 0., 0.)), 1.);
+        assert_eq!(grid.distance(pt3(0., 0., 5.)), 4.);
+    }
+
+    #[test]
+    fn bounds() {
+        let sdf = Sdf::sphere(1.).union(Sdf::cuboid(vec3(1., 2., 3.)).translate(vec3(5., 0., 0.)));
+        assert_eq!(sdf.bounds(), Bounds3f::new(pt3(-1., -2., -3.), pt3(6., 2., 3.)));
+        assert_eq!(Sdf::sphere(1.).scale(2.).bounds(), Bounds3f::new(pt3(-2., -2., -2.), pt3(2., 2., 2.)));
+        let rows = Sdf::sphere(1.).repeat(vec3(4., 0., 0.)).bounds();
+        assert!(!rows.is_finite());
+        assert_eq!((rows.min.y, rows.max.y), (-1., 1.));
+    }
+
+    #[test]
+    fn matches_sphere() {
+        use rand::random;
+        let sdf = SdfGeometry::new(Sdf::sphere(1.));
+        let sphere = Sphere::new(1.);
+        for _ in 0..200 {
+            // inside or outside, clear of the surface, see `start_near_surface`
+            let radius = if random() { 0.9 * random::<Float>() } else { 1.1 + 2. * random::<Float>() };
+            let org = Point3::from_vec(vec3(random::<Float>() - 0.5, random::<Float>() - 0.5, random::<Float>() - 0.5).normalize() * radius);
+            let dir = vec3(random::<Float>() - 0.5, random::<Float>() - 0.5, random::<Float>() - 0.5).normalize();
+            let ray = Ray::new(org, dir);
+            match (sdf.intersect(&ray), sphere.intersect(&ray)) {
+                (Some(a), Some(b)) => {
+                    // the error is along the normal, larger in t for grazing rays
+                    assert_lt!((a.t - b.t).abs() * dot(b.normal, dir).abs(), 1e-3);
+                    let outward = a.pos.to_vec().normalize();
+                    assert_lt!((a.normal - if a.side == Side::Outside { outward } else { -outward }).magnitude(), 1e-2);
+                    assert_eq!(a.side, b.side);
+                }
+                (None, None) => {}
+                // grazing rays may differ
+                (a, b) => assert_lt!(dot(org.to_vec(), dir).powi(2) - org.to_vec().magnitude2() + 1., 1e-2, "{:?} {:?}", a, b),
+            }
+        }
+    }
+
+    #[test]
+    fn start_near_surface() {
+        // within the precision of the surface, the march skips it
+        let sdf = SdfGeometry::new(Sdf::sphere(1.));
+        for &offset in &[-5e-5, 0.] {
+            let ray = Ray::new(pt3(0., 1. + offset, 0.), vec3(0., -1., 0.));
+            let its = sdf.intersect(&ray).unwrap();
+            assert_lt!((its.t - 2.).abs(), 1e-3);
+            assert_eq!(its.side, Side::Inside);
+            // leaving it outward, nothing else is hit
+            assert_eq!(sdf.intersect(&Ray::new(pt3(0., 1. + offset, 0.), vec3(0., 1., 0.))), None);
+        }
+        // unless it starts outside the bounds, the march then starts on them and keeps the surface touching them
+        let its = sdf.intersect(&Ray::new(pt3(0., 1. + 5e-5, 0.), vec3(0., -1., 0.))).unwrap();
+        assert_lt!(its.t, 1e-4);
+        assert_eq!(its.side, Side::Outside);
+    }
+
+    #[test]
+    fn leave_surface() {
+        let sdf = SdfGeometry::new(Sdf::sphere(1.));
+        // from the surface outward
+        assert_eq!(sdf.intersect(&Ray::new(pt3(1., 0., 0.), vec3(1., 0., 0.))), None);
+        // from the surface inward, hit the far side
+        let its = sdf.intersect(&Ray::new(pt3(1., 0., 0.), vec3(-1., 0., 0.))).unwrap();
+        assert_lt!((its.t - 2.).abs(), 1e-3);
+        assert_eq!(its.side, Side::Inside);
+        // repetition, from the gap between two copies
+        let row = SdfGeometry::new(Sdf::sphere(1.).repeat(vec3(4., 0., 0.)));
+        let its = row.intersect(&Ray::new(pt3(-2., 0., 0.), vec3(1., 0., 0.))).unwrap();
+        assert_lt!((its.pos - pt3(-1., 0., 0.)).magnitude(), 1e-3);
+        assert!(!row.intersect_p(&Ray::new(pt3(-2., 0., 0.), vec3(0., 1., 0.))));
+        // hit the fractal
+        let bulb = SdfGeometry::new(Sdf::mandelbulb(8., 10)).with_max_steps(1000);
+        let its = bulb.intersect(&Ray::new(pt3(0., 0., 3.), vec3(0., 0., -1.))).unwrap();
+        assert_lt!(its.pos.z, 1.2);
+        assert_gt!(its.pos.z, 0.5);
+    }
+}
@@ -1,6 +1,5 @@
 use super::*;
 use crate::macros::*;
-use std::mem::MaybeUninit;
 
 #[derive(Debug, Clone)]
 pub struct Sphere { // todo: store global coordinates instead of local
@@ -18,6 +17,30 @@ impl Sphere {
         self.radius = new;
         self.rad2 = new * new;
     }
+
+    /// Spherical parameterization of a surface point:
+    /// `u = phi / 2pi` around the z-axis, `v = 1 - theta / pi` from the -z pole to the +z pole,
+    /// so that `dpdu x dpdv` points outward.
+    ///
+    /// Return `(uv, dpdu, dpdv)`
+    fn parameterize(&self, pos: Point3f) -> (Point2f, Vector3f, Vector3f) {
+        let mut phi = pos.y.atan2(pos.x);
+        if phi < 0. { phi += 2. * Float::PI(); }
+        let cos_theta = num_traits::clamp(pos.z / self.radius, -1., 1.);
+        let theta = cos_theta.acos();
+        let sin_theta = (1. - cos_theta * cos_theta).sqrt();
+        let (sin_phi, cos_phi) = phi.sin_cos();
+        let dpdu = vec3(-pos.y, pos.x, 0.) * (2. * Float::PI());
+        let dpdv = vec3(pos.z * cos_phi, pos.z * sin_phi, -self.radius * sin_theta) * -Float::PI();
+        (pt2(phi / (2. * Float::PI()), 1. - theta / Float::PI()), dpdu, dpdv)
+    }
+
+    /// Project a hit position back onto the surface, return it with its error bound
+    #[inline]
+    fn refine(&self, pos: Point3f) -> (Point3f, Vector3f) {
+        let pos = pos * (self.radius / pos.to_vec().magnitude());
+        (pos, pos.to_vec().map(Float::abs) * gamma(5))
+    }
 }
 
 impl Intersect for Sphere {
@@ -32,27 +55,35 @@ impl Intersect for Sphere {
         } else {
             let ds = delta.sqrt();
             let t = -b - ds;
-            if t > Float::epsilon() { // front?
-                let pos = ray.transport(t);
+            if t > 0. && ray.in_range(t) { // front?
+                let (pos, p_error) = self.refine(ray.transport(t));
+                let (uv, dpdu, dpdv) = self.parameterize(pos);
                 Some(GeometryIntersection {
                     pos,
                     normal: pos.to_vec().normalize(),
                     wi: -ray.dir, // todo slow
                     t,
                     side: Side::Outside,
-                    uv: unsafe { MaybeUninit::zeroed().assume_init() }
+                    uv,
+                    dpdu,
+                    dpdv,
+                    p_error,
                 })
             } else { // back?
                 let t = -b + ds;
-                if t > Float::epsilon() {
-                    let pos = ray.transport(t);
+                if t > 0. && ray.in_range(t) {
+                    let (pos, p_error) = self.refine(ray.transport(t));
+                    let (uv, dpdu, dpdv) = self.parameterize(pos);
                     Some(GeometryIntersection {
                         pos,
                         normal: -pos.to_vec().normalize(),
                         wi: -ray.dir,
                         t,
                         side: Side::Inside,
-                        uv: unsafe { MaybeUninit::zeroed().assume_init() }
+                        uv,
+                        dpdu,
+                        dpdv,
+                        p_error,
                     })
                 } else {
                     None
@@ -63,7 +94,9 @@ impl Intersect for Sphere {
 }
 
 impl Geometry for Sphere {
-    // todo
+    fn bounds(&self) -> Bounds3f {
+        Bounds3f::new(pt3(-self.radius, -self.radius, -self.radius), pt3(self.radius, self.radius, self.radius))
+    }
 }
 
 #[cfg(test)]
@@ -83,16 +116,20 @@ mod test {
     fn has_intersect() {
         let s = Sphere::new(1.0);
         let r = Ray::new(pt3(10., 0., 0.), vec3(-1., 0., 0.));
-        let mut its = s.intersect(&r).unwrap();
-        its.uv = pt2(0., 0.);
+        let its = s.intersect(&r).unwrap();
         assert_eq!(its, GeometryIntersection {
             pos: pt3(1., 0., 0.),
             normal: vec3(1., 0., 0.),
             wi: -r.dir,
             t: 9.0,
             side: Side::Outside,
-            uv: pt2(0., 0.)
+            uv: pt2(0., 0.5),
+            dpdu: its.dpdu,
+            dpdv: its.dpdv,
+            p_error: its.p_error,
         });
+        assert_approx!((its.dpdu - vec3(0., 2. * Float::PI(), 0.)).magnitude(), 0.);
+        assert_approx!((its.dpdv - vec3(0., 0., Float::PI())).magnitude(), 0.);
         let r = Ray::new(pt3(0., 0., 0.), vec3(1., 1., 0.).normalize());
         let x = (2.0 as Float).sqrt() / 2.0;
         let p = pt3(x, x, 0.);
@@ -102,4 +139,66 @@ mod test {
         assert_approx!(its.t - 1.0, 0.);
         assert_eq!(its.side, Side::Inside)
     }
+
+    #[test]
+    fn uv_and_derivatives() {
+        let s = Sphere::new(2.0);
+        let mut sampler = crate::sampler::Independent;
+        for _ in 0..1000 {
+            use crate::sampler::Sampler;
+            let dir = vec3(sampler.next() - 0.5, sampler.next() - 0.5, sampler.next() - 0.5).normalize();
+            let its = s.intersect(&Ray::new(Point3::origin(), dir)).unwrap();
+            assert_ge!(its.uv.x, 0.);
+            assert_le!(its.uv.x, 1.);
+            assert_ge!(its.uv.y, 0.);
+            assert_le!(its.uv.y, 1.);
+            // the position is recovered from uv
+            let (phi, theta) = (its.uv.x * 2. * Float::PI(), (1. - its.uv.y) * Float::PI());
+            let p = pt3(theta.sin() * phi.cos(), theta.sin() * phi.sin(), theta.cos()) * 2.;
+            assert_approx!((p - its.pos).magnitude(), 0.);
+            // the derivatives are tangent, and the frame is right-handed around the normal
+            assert_approx!(dot(its.dpdu, its.normal) / its.dpdu.magnitude(), 0.);
+            assert_approx!(dot(its.dpdv, its.normal) / its.dpdv.magnitude(), 0.);
+            let frame = its.shading_frame();
+            assert_approx!(frame.determinant(), 1.);
+            assert_eq!(frame.z, its.normal);
+        }
+        // cross(dpdu, dpdv) points outward
+        let its = s.intersect(&Ray::new(pt3(0., -10., 1.), vec3(0., 1., 0.))).unwrap();
+        assert_gt!(dot(its.dpdu.cross(its.dpdv), its.pos.to_vec()), 0.);
+    }
+
+    #[test]
+    fn ray_range() {
+        let s = Sphere::new(1.0);
+        let r = Ray::new_with_range(pt3(10., 0., 0.), vec3(-1., 0., 0.), 0., 5.);
+        assert_eq!(s.intersect(&r), None);
+        assert!(!s.intersect_p(&r));
+        // skip the front hit
+        let r = Ray::new_with_range(pt3(10., 0., 0.), vec3(-1., 0., 0.), 10., Float::infinity());
+        let its = s.intersect(&r).unwrap();
+        assert_approx!(its.t, 11.);
+        assert_eq!(its.side, Side::Inside);
+    }
+
+    #[test]
+    fn spawn_ray() {
+        use rand::random;
+        let s = Sphere::new(1e3);
+        for _ in 0..1000 {
+            let org = pt3(random::<Float>() - 0.5, random::<Float>() - 0.5, random::<Float>() - 0.5) * 1e4;
+            let its = match s.intersect(&Ray::new(org, (Point3::origin() - org).normalize())) {
+                Some(its) if its.side == Side::Outside => its,
+                _ => continue,
+            };
+            let dir = vec3(random::<Float>() - 0.5, random::<Float>() - 0.5, random::<Float>() - 0.5).normalize();
+            // leaving the convex outside never comes back, going in only hits the far side
+            let out = if dot(dir, its.normal) > 0. { dir } else { -dir };
+            assert_eq!(s.intersect(&its.spawn_ray(out)), None);
+            if dot(out, its.normal) > 1e-2 {
+                // the chord through the sphere is 2e3 * cos
+                assert_gt!(s.intersect(&its.spawn_ray(-out)).unwrap().t, 1e3 * dot(out, its.normal));
+            }
+        }
+    }
 }
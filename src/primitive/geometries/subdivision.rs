@@ -0,0 +1,344 @@
+use super::*;
+use super::mesh::vertex_normals;
+use std::collections::{HashMap, HashSet};
+
+/// An undirected edge, the smaller index first
+type Edge = (u32, u32);
+
+#[inline]
+fn edge(a: u32, b: u32) -> Edge { if a < b { (a, b) } else { (b, a) } }
+
+/// Weights of the old vertices making up a new one
+type Stencil = Vec<(u32, Float)>;
+
+#[inline]
+fn apply<V: VectorSpace<Scalar=Float>>(stencil: &[(u32, Float)], values: &[V]) -> V {
+    stencil.iter().fold(V::zero(), |sum, &(i, w)| sum + values[i as usize] * w)
+}
+
+impl Mesh {
+    /// Smooth a triangle control mesh by `levels` steps of Loop subdivision, then push it onto the limit surface
+    ///
+    /// `creases` are edges of the control mesh kept sharp, like the boundary edges. A vertex on two sharp edges
+    /// slides along them, one on three or more is a corner and stays put.
+    /// The normals are the limit normals, but averaged over the faces around crease and corner vertices.
+    /// The `uvs`, if any, are smoothed the same way as the positions, so they can't have seams.
+    pub fn subdivide(positions: &[Point3f], uvs: Option<&[Point2f]>, indices: &[[u32; 3]], creases: &[[u32; 2]], levels: u32) -> Mesh {
+        let n = positions.len();
+        assert!(indices.iter().flatten().all(|&i| (i as usize) < n), "Mesh index out of bounds!");
+        if let Some(uvs) = uvs {
+            assert_eq!(uvs.len(), n, "Mismatched uv buffer size!");
+        }
+        let mut level = Level {
+            positions: positions.iter().map(|p| p.to_vec()).collect(),
+            uvs: uvs.map(|uvs| uvs.iter().map(|uv| uv.to_vec()).collect()),
+            faces: indices.to_vec(),
+            creases: creases.iter().map(|&[a, b]| edge(a, b)).collect(),
+        };
+        for _ in 0..levels {
+            level = level.refine();
+        }
+        let (positions, uvs, normals) = level.limit();
+        let mut mesh = Mesh::new(positions, level.faces);
+        mesh.set_normals(normals);
+        if let Some(uvs) = uvs {
+            mesh.set_uvs(uvs);
+        }
+        mesh
+    }
+}
+
+/// The mesh at one level of the subdivision
+struct Level {
+    positions: Vec<Vector3f>,
+    uvs: Option<Vec<Vector2f>>,
+    faces: Vec<[u32; 3]>,
+    creases: HashSet<Edge>,
+}
+
+/// Connectivity of a `Level`
+struct Topology {
+    /// Each edge once, in the order of the faces, with the vertices opposite to it, one per adjacent face
+    edges: Vec<(Edge, Vec<u32>)>,
+    /// Position of an edge in `edges`
+    edge_index: HashMap<Edge, usize>,
+    /// Neighbors of each vertex
+    neighbors: Vec<Vec<u32>>,
+    /// Neighbors across sharp edges: boundary, crease or non-manifold ones
+    sharp: Vec<Vec<u32>>,
+}
+
+impl Topology {
+    /// An old vertex moved, or pushed to the limit
+    fn vertex_stencil(&self, v: usize, limit: bool) -> Stencil {
+        let (ring, sharp) = (&self.neighbors[v], &self.sharp[v]);
+        match sharp.len() {
+            0 | 1 if !ring.is_empty() => {
+                let n = ring.len();
+                let w = if limit { 1. / (n as Float + 3. / (8. * loop_beta(n))) } else { loop_beta(n) };
+                std::iter::once((v as u32, 1. - n as Float * w)).chain(ring.iter().map(|&i| (i, w))).collect()
+            }
+            2 if limit => vec![(v as u32, 0.6), (sharp[0], 0.2), (sharp[1], 0.2)],
+            2 => vec![(v as u32, 0.75), (sharp[0], 0.125), (sharp[1], 0.125)],
+            _ => vec![(v as u32, 1.)],
+        }
+    }
+}
+
+impl Level {
+    fn topology(&self) -> Topology {
+        let mut edges: Vec<(Edge, Vec<u32>)> = Vec::new();
+        let mut edge_index = HashMap::new();
+        for &[a, b, c] in &self.faces {
+            for &(e, o) in &[(edge(a, b), c), (edge(b, c), a), (edge(c, a), b)] {
+                let i = *edge_index.entry(e).or_insert_with(|| {
+                    edges.push((e, Vec::new()));
+                    edges.len() - 1
+                });
+                edges[i].1.push(o);
+            }
+        }
+        let mut neighbors = vec![Vec::new(); self.positions.len()];
+        let mut sharp = vec![Vec::new(); self.positions.len()];
+        for &((a, b), ref opp) in &edges {
+            neighbors[a as usize].push(b);
+            neighbors[b as usize].push(a);
+            if self.is_sharp((a, b), opp) {
+                sharp[a as usize].push(b);
+                sharp[b as usize].push(a);
+            }
+        }
+        Topology { edges, edge_index, neighbors, sharp }
+    }
+
+    #[inline]
+    fn is_sharp(&self, e: Edge, opposite: &[u32]) -> bool {
+        opposite.len() != 2 || self.creases.contains(&e)
+    }
+
+    /// One step: move the old vertices, insert one on each edge and split each face in 4
+    fn refine(&self) -> Level {
+        let topo = self.topology();
+        let mut stencils: Vec<_> = (0..self.positions.len()).map(|v| topo.vertex_stencil(v, false)).collect();
+        // the new vertices follow the old ones, in the order of the edges
+        let first_edge_vertex = stencils.len() as u32;
+        let mut creases = HashSet::new();
+        for &((a, b), ref opp) in &topo.edges {
+            if self.is_sharp((a, b), opp) {
+                stencils.push(vec![(a, 0.5), (b, 0.5)]);
+            } else {
+                stencils.push(vec![(a, 0.375), (b, 0.375), (opp[0], 0.125), (opp[1], 0.125)]);
+            }
+            if self.creases.contains(&(a, b)) {
+                let m = stencils.len() as u32 - 1;
+                creases.insert(edge(a, m));
+                creases.insert(edge(m, b));
+            }
+        }
+
+        let faces = self.faces.iter().flat_map(|&[a, b, c]| {
+            let mid = |u: u32, v: u32| first_edge_vertex + topo.edge_index[&edge(u, v)] as u32;
+            let (ab, bc, ca) = (mid(a, b), mid(b, c), mid(c, a));
+            vec![[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+        }).collect();
+        Level {
+            positions: stencils.iter().map(|s| apply(s, &self.positions)).collect(),
+            uvs: self.uvs.as_ref().map(|uvs| stencils.iter().map(|s| apply(s, uvs)).collect()),
+            faces,
+            creases,
+        }
+    }
+
+    /// Positions, uvs and normals on the limit surface
+    fn limit(&self) -> (Vec<Point3f>, Option<Vec<Point2f>>, Vec<Vector3f>) {
+        let topo = self.topology();
+        let p = |i: u32| self.positions[i as usize];
+        let stencils: Vec<_> = (0..self.positions.len()).map(|v| topo.vertex_stencil(v, true)).collect();
+        let positions: Vec<_> = stencils.iter().map(|s| Point3::from_vec(apply(s, &self.positions))).collect();
+        let uvs = self.uvs.as_ref().map(|uvs| stencils.iter().map(|s| Point2::from_vec(apply(s, uvs))).collect());
+
+        // area weighted face normals as the fallback, and to orient the limit ones
+        let face_normals = vertex_normals(&positions, &self.faces);
+        let mut fans = vec![Vec::new(); positions.len()];
+        for &[a, b, c] in &self.faces {
+            for &(v, next, prev) in &[(a, b, c), (b, c, a), (c, a, b)] {
+                fans[v as usize].push((next, prev));
+            }
+        }
+        let normals = (0..positions.len()).map(|v| {
+            let fallback = face_normals[v];
+            let ring = match ordered_ring(&fans[v]) {
+                Some(ring) if ring.len() == topo.neighbors[v].len() => ring,
+                _ => return fallback, // non-manifold
+            };
+            let pv = self.positions[v];
+            let pr: Vec<_> = ring.iter().map(|&i| p(i)).collect();
+            let k = pr.len();
+            let n = match (topo.sharp[v].len(), k == fans[v].len()) {
+                // interior: the tangents from the eigenvectors of the subdivision
+                (0, true) | (1, true) => {
+                    let (s, t) = pr.iter().enumerate().fold((Vector3f::zero(), Vector3f::zero()), |(s, t), (i, &q)| {
+                        let (sin, cos) = (2. * Float::PI() * i as Float / k as Float).sin_cos();
+                        (s + q * cos, t + q * sin)
+                    });
+                    s.cross(t)
+                }
+                // on the boundary, the ring runs from one boundary neighbor to the other
+                (2, false) => {
+                    let s = pr[k - 1] - pr[0];
+                    let t = match k {
+                        2 => pr[0] + pr[1] - pv * 2.,
+                        3 => pr[1] - pv,
+                        4 => -pr[0] + pr[1] * 2. + pr[2] * 2. - pr[3] - pv * 2.,
+                        _ => {
+                            let theta = Float::PI() / (k - 1) as Float;
+                            (1..k - 1).fold((pr[0] + pr[k - 1]) * theta.sin(), |t, i| {
+                                t + pr[i] * ((2. * theta.cos() - 2.) * (i as Float * theta).sin())
+                            })
+                        }
+                    };
+                    s.cross(t)
+                }
+                // creases and corners are not smooth
+                _ => return fallback,
+            };
+            if n.magnitude2() == 0. || !n.is_finite() {
+                fallback
+            } else if dot(n, fallback) < 0. {
+                -n.normalize()
+            } else {
+                n.normalize()
+            }
+        }).collect();
+        (positions, uvs, normals)
+    }
+}
+
+/// The neighbors in order around a vertex, from the `(next, prev)` pairs of the faces around it
+///
+/// Start from the boundary if there is one, `None` if the fan is not a single disk or half disk
+fn ordered_ring(fan: &[(u32, u32)]) -> Option<Vec<u32>> {
+    let start = fan.iter().find(|&&(next, _)| fan.iter().all(|&(_, prev)| prev != next)).unwrap_or(fan.first()?).0;
+    let mut ring = vec![start];
+    let mut cur = start;
+    while let Some(&(_, prev)) = fan.iter().find(|&&(next, _)| next == cur) {
+        if prev == start { break; }
+        if ring.len() > fan.len() { return None; }
+        ring.push(prev);
+        cur = prev;
+    }
+    Some(ring)
+}
+
+/// Weight of each neighbor in the smooth vertex rule, of Loop's original scheme but 3/16 for valence 3
+#[inline]
+fn loop_beta(valence: usize) -> Float {
+    if valence == 3 { 3. / 16. } else { 3. / (8. * valence as Float) }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::macros::*;
+    use rand::random;
+
+    fn octahedron() -> (Vec<Point3f>, Vec<[u32; 3]>) {
+        (
+            vec![pt3(1., 0., 0.), pt3(-1., 0., 0.), pt3(0., 1., 0.), pt3(0., -1., 0.), pt3(0., 0., 1.), pt3(0., 0., -1.)],
+            vec![[0, 2, 4], [2, 1, 4], [1, 3, 4], [3, 0, 4], [2, 0, 5], [1, 2, 5], [3, 1, 5], [0, 3, 5]],
+        )
+    }
+
+    fn tetrahedron() -> (Vec<Point3f>, Vec<[u32; 3]>) {
+        (
+            vec![pt3(0., 0., 0.), pt3(1., 0., 0.), pt3(0., 1., 0.), pt3(0., 0., 1.)],
+            vec![[0, 2, 1], [0, 1, 3], [0, 3, 2], [1, 2, 3]],
+        )
+    }
+
+    #[test]
+    fn smooth_closed() {
+        let (positions, indices) = octahedron();
+        let mesh = Mesh::subdivide(&positions, None, &indices, &[], 3);
+        // each level adds a vertex per edge, and splits each face in 4
+        assert_eq!(mesh.n_triangles(), 8 * 4 * 4 * 4);
+        assert_eq!(mesh.positions().len(), 2 + mesh.n_triangles() / 2);
+        // a rounded blob inside the cage, the normals point out and agree with the faces
+        let normals = mesh.normals().unwrap();
+        for (p, n) in mesh.positions().iter().zip(normals) {
+            assert_lt!(p.x.abs() + p.y.abs() + p.z.abs(), 1.);
+            assert_gt!(p.to_vec().magnitude(), 0.4);
+            assert_gt!(dot(p.to_vec().normalize(), *n), 0.9);
+        }
+        let mut around = vec![Vector3f::zero(); normals.len()];
+        for face in mesh.indices() {
+            let [p0, p1, p2] = [0, 1, 2].map(|k| mesh.positions()[face[k] as usize]);
+            let ng = cross(p1 - p0, p2 - p0);
+            for &i in face {
+                assert_gt!(dot(normals[i as usize], ng.normalize()), 0.85);
+                around[i as usize] += ng;
+            }
+        }
+        for (n, around) in normals.iter().zip(around) {
+            assert_gt!(dot(*n, around.normalize()), 0.999);
+        }
+        // and it is watertight
+        for _ in 0..200 {
+            let dir = vec3(random::<Float>() - 0.5, random::<Float>() - 0.5, random::<Float>() - 0.5).normalize();
+            assert_eq!(mesh.intersect(&Ray::new(Point3::origin(), dir)).unwrap().side, Side::Inside);
+        }
+    }
+
+    #[test]
+    fn boundary() {
+        // a 2x2 grid of squares
+        let positions: Vec<_> = (0..9).map(|i| pt3((i % 3) as Float, (i / 3) as Float, 0.)).collect();
+        let indices = vec![[0, 1, 4], [0, 4, 3], [1, 2, 5], [1, 5, 4], [3, 4, 7], [3, 7, 6], [4, 5, 8], [4, 8, 7]];
+        let flat = Mesh::subdivide(&positions, None, &indices, &[], 2);
+        for (p, n) in flat.positions().iter().zip(flat.normals().unwrap()) {
+            assert_eq!(p.z, 0.);
+            assert_approx!((n - vec3(0., 0., 1.)).magnitude(), 0.);
+            assert!(0. <= p.x && p.x <= 2. && 0. <= p.y && p.y <= 2., "{:?}", p);
+        }
+        // the boundary only depends on the boundary vertices, lifting the center leaves it in place
+        let mut bump = positions.clone();
+        bump[4].z = 1.;
+        let bump = Mesh::subdivide(&bump, None, &indices, &[], 2);
+        assert_eq!(bump.positions().len(), flat.positions().len());
+        let on_boundary = bump.positions().iter().filter(|p| p.z == 0.).count();
+        assert_eq!(on_boundary, 8 * 4);
+        for (p, n) in bump.positions().iter().zip(bump.normals().unwrap()) {
+            assert_ge!(p.z, 0.);
+            assert_lt!(p.z, 1.);
+            assert_gt!(n.z, 0.);
+        }
+    }
+
+    #[test]
+    fn creases() {
+        let (positions, indices) = tetrahedron();
+        let all_edges = [[0, 1], [0, 2], [0, 3], [1, 2], [1, 3], [2, 3]];
+        // with every edge sharp, the corners stay and the faces stay flat
+        let mesh = Mesh::subdivide(&positions, None, &indices, &all_edges, 3);
+        for p in &positions {
+            assert!(mesh.positions().contains(p));
+        }
+        for p in mesh.positions() {
+            let on_plane = [p.x, p.y, p.z, 1. - p.x - p.y - p.z].iter().any(|d| d.abs() < 1e-5);
+            assert!(on_plane, "{:?}", p);
+        }
+        // a crease loop around a face becomes a smooth curve in its plane, the corners slide along it,
+        // and the face inside stays flat: all the 9 * 10 / 2 vertices of its 8 x 8 subdivision
+        let mesh = Mesh::subdivide(&positions, None, &indices, &[[1, 2], [2, 3], [3, 1]], 3);
+        let on_face = mesh.positions().iter().filter(|p| (p.x + p.y + p.z - 1.).abs() < 1e-5).count();
+        assert_eq!(on_face, 45);
+        for p in &positions[1..] {
+            assert!(!mesh.positions().contains(p));
+        }
+        // without creases it shrinks away from all the corners
+        let mesh = Mesh::subdivide(&positions, None, &indices, &[], 3);
+        for p in &positions {
+            assert!(mesh.positions().iter().all(|q| (q - p).magnitude() > 0.1));
+        }
+    }
+}
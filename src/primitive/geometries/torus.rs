@@ -0,0 +1,122 @@
+use super::*;
+use crate::macros::*;
+
+#[derive(Debug, Clone)]
+/// Torus around the z-axis: a tube of `minor_radius` swept along the circle of `major_radius` on `z = 0`
+pub struct Torus {
+    major_radius: Float,
+    minor_radius: Float,
+}
+
+impl Torus {
+    pub fn new(major_radius: Float, minor_radius: Float) -> Self {
+        debug_assert_gt!(minor_radius, 0.);
+        debug_assert_gt!(major_radius, minor_radius);
+        Self { major_radius, minor_radius }
+    }
+    pub fn major_radius(&self) -> Float { self.major_radius }
+    pub fn minor_radius(&self) -> Float { self.minor_radius }
+    pub fn area(&self) -> Float { 4. * Float::PI() * Float::PI() * self.major_radius * self.minor_radius }
+}
+
+impl Intersect for Torus {
+    /// Solve the quartic `(|p|^2 + R^2 - r^2)^2 = 4 R^2 (x^2 + y^2)`
+    ///
+    /// `u = phi / 2pi` around the z-axis, `v = theta / 2pi` around the tube starting from the outer equator
+    fn intersect(&self, ray: &Ray) -> Option<GeometryIntersection> {
+        let (big_r, r) = (self.major_radius as f64, self.minor_radius as f64);
+        // start near the bounding sphere to keep the coefficients small
+        let t_start = (-dot(ray.org.to_vec(), ray.dir) as f64 - (big_r + r)).max(0.);
+        let org = ray.org.cast::<f64>().unwrap() + ray.dir.cast::<f64>().unwrap() * t_start;
+        let (o, d) = (org.to_vec(), ray.dir.cast::<f64>().unwrap());
+        let (dd, od) = (d.dot(d), o.dot(d));
+        let k = o.dot(o) + big_r * big_r - r * r;
+        let four_r2 = 4. * big_r * big_r;
+        let (roots, n) = solve_quartic([
+            dd * dd,
+            4. * dd * od,
+            4. * od * od + 2. * dd * k - four_r2 * (d.x * d.x + d.y * d.y),
+            4. * od * k - 2. * four_r2 * (o.x * d.x + o.y * d.y),
+            k * k - four_r2 * (o.x * o.x + o.y * o.y),
+        ]);
+        let t = roots[..n].iter()
+            .map(|&t| (t + t_start) as Float)
+            .find(|&t| t > 0. && ray.in_range(t))?;
+        let pos = ray.transport(t);
+        let phi = azimuth(pos);
+        let (sin_phi, cos_phi) = phi.sin_cos();
+        let rho = (pos.x * pos.x + pos.y * pos.y).sqrt();
+        let mut theta = pos.z.atan2(rho - self.major_radius);
+        if theta < 0. { theta += 2. * Float::PI(); }
+        let (sin_theta, cos_theta) = theta.sin_cos();
+        let normal = vec3(cos_theta * cos_phi, cos_theta * sin_phi, sin_theta);
+        let dpdv = vec3(-sin_theta * cos_phi, -sin_theta * sin_phi, cos_theta) * (2. * Float::PI() * self.minor_radius);
+        Some(facing_ray(
+            ray, t, pos, normal,
+            pt2(phi / (2. * Float::PI()), theta / (2. * Float::PI())),
+            vec3(-pos.y, pos.x, 0.) * (2. * Float::PI()),
+            dpdv,
+        ))
+    }
+}
+
+impl Geometry for Torus {
+    fn bounds(&self) -> Bounds3f {
+        let (xy, z) = (self.major_radius + self.minor_radius, self.minor_radius);
+        Bounds3f::new(pt3(-xy, -xy, -z), pt3(xy, xy, z))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn intersect() {
+        let torus = Torus::new(2., 0.5);
+        let its = torus.intersect(&Ray::new(pt3(-10., 0., 0.), vec3(1., 0., 0.))).unwrap();
+        assert_approx!(its.t, 7.5);
+        assert_approx!((its.normal - vec3(-1., 0., 0.)).magnitude(), 0.);
+        assert_eq!(its.side, Side::Outside);
+        assert_approx!(its.uv.x, 0.5);
+        assert_approx!(its.uv.y, 0.);
+        assert_gt!(dot(its.dpdu.cross(its.dpdv), its.normal), 0.);
+        // skip the first tube
+        let its = torus.intersect(&Ray::new_with_range(pt3(-10., 0., 0.), vec3(1., 0., 0.), 9., Float::infinity())).unwrap();
+        assert_approx!(its.t, 11.5);
+        assert_eq!(torus.intersect(&Ray::new(pt3(0., 0., 10.), vec3(0., 0., -1.))), None); // through the hole
+        let its = torus.intersect(&Ray::new(pt3(2., 0., 10.), vec3(0., 0., -1.))).unwrap();
+        assert_approx!(its.pos.z, 0.5);
+        assert_approx!(its.uv.y, 0.25);
+    }
+
+    #[test]
+    fn random_rays() {
+        use rand::random;
+        let torus = Torus::new(1., 0.3);
+        let mut n_hits = 0;
+        for _ in 0..1000 {
+            let org = pt3(random::<Float>() - 0.5, random::<Float>() - 0.5, random::<Float>() - 0.5) * 4.;
+            let dir = vec3(random::<Float>() - 0.5, random::<Float>() - 0.5, random::<Float>() - 0.5).normalize();
+            if let Some(its) = torus.intersect(&Ray::new(org, dir)) {
+                n_hits += 1;
+                // on the surface
+                let rho = (its.pos.x * its.pos.x + its.pos.y * its.pos.y).sqrt();
+                assert_approx!(((rho - 1.).powi(2) + its.pos.z * its.pos.z).sqrt(), 0.3);
+                assert_le!(dot(its.normal, dir), 0.);
+                // the same as sphere tracing the distance field
+                let mut t = 0.;
+                loop {
+                    let p = org + dir * t;
+                    let rho = (p.x * p.x + p.y * p.y).sqrt();
+                    let dist = (((rho - 1.).powi(2) + p.z * p.z).sqrt() - 0.3).abs();
+                    if dist < 1e-5 { break; }
+                    t += dist;
+                }
+                // measured along the normal, grazing rays stop early
+                assert_lt!((t - its.t).abs() * dot(its.normal, dir).abs(), 1e-3);
+            }
+        }
+        assert_gt!(n_hits, 50);
+    }
+}
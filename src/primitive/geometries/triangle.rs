@@ -2,15 +2,175 @@ use super::*;
 
 #[derive(Debug, Clone)]
 pub struct Triangle {
-    // Todo
+    /// Vertex positions, counter-clockwise viewed from the outside
+    vertices: [Point3f; 3],
+}
+
+impl Triangle {
+    pub fn new(p0: Point3f, p1: Point3f, p2: Point3f) -> Self {
+        Self { vertices: [p0, p1, p2] }
+    }
+    #[inline]
+    pub fn vertices(&self) -> &[Point3f; 3] { &self.vertices }
+    /// Unnormalized geometric normal, pointing to the `Outside`
+    pub fn face_normal(&self) -> Vector3f {
+        let [p0, p1, p2] = self.vertices;
+        cross(p1 - p0, p2 - p0)
+    }
+    pub fn area(&self) -> Float { 0.5 * self.face_normal().magnitude() }
+}
+
+/// Watertight ray-triangle test (Woop et al. 2013)
+///
+/// Return `(t, [b0, b1, b2])` where the hit position is `b0 p0 + b1 p1 + b2 p2`
+pub(crate) fn intersect_triangle(ray: &Ray, p0: Point3f, p1: Point3f, p2: Point3f) -> Option<(Float, [Float; 3])> {
+    // permute the components so that z is the dominant axis of ray.dir
+    let abs_dir = ray.dir.map(Float::abs);
+    let kz = if abs_dir.x > abs_dir.y && abs_dir.x > abs_dir.z { 0 } else if abs_dir.y > abs_dir.z { 1 } else { 2 };
+    let kx = (kz + 1) % 3;
+    let ky = (kx + 1) % 3;
+    let permute = |v: Vector3f| vec3(v[kx], v[ky], v[kz]);
+    let d = permute(ray.dir);
+    // translate vertices to the ray origin, then shear so that the ray points along +z
+    let (sx, sy, sz) = (-d.x / d.z, -d.y / d.z, 1. / d.z);
+    let shear = |p: Point3f| {
+        let p = permute(p - ray.org);
+        vec3(p.x + sx * p.z, p.y + sy * p.z, p.z)
+    };
+    let (p0t, p1t, p2t) = (shear(p0), shear(p1), shear(p2));
+    // edge functions
+    let mut e0 = p1t.x * p2t.y - p1t.y * p2t.x;
+    let mut e1 = p2t.x * p0t.y - p2t.y * p0t.x;
+    let mut e2 = p0t.x * p1t.y - p0t.y * p1t.x;
+    if e0 == 0. || e1 == 0. || e2 == 0. { // fall back to double precision on the edges
+        let edge = |a: Vector3f, b: Vector3f| (a.x as f64 * b.y as f64 - a.y as f64 * b.x as f64) as Float;
+        e0 = edge(p1t, p2t);
+        e1 = edge(p2t, p0t);
+        e2 = edge(p0t, p1t);
+    }
+    if (e0 < 0. || e1 < 0. || e2 < 0.) && (e0 > 0. || e1 > 0. || e2 > 0.) {
+        return None;
+    }
+    let det = e0 + e1 + e2;
+    if det == 0. { return None; }
+    let t_scaled = (e0 * p0t.z + e1 * p1t.z + e2 * p2t.z) * sz;
+    if det < 0. && t_scaled >= 0. || det > 0. && t_scaled <= 0. { // behind the ray origin
+        return None;
+    }
+    let inv_det = 1. / det;
+    let t = t_scaled * inv_det;
+    if t <= 0. || !ray.in_range(t) { return None; }
+    Some((t, [e0 * inv_det, e1 * inv_det, e2 * inv_det]))
+}
+
+/// `b0 p0 + b1 p1 + b2 p2` and its absolute error bound
+///
+/// Interpolating is more accurate than `ray.transport(t)`
+pub(crate) fn barycentric_position(p: [Point3f; 3], b: [Float; 3]) -> (Point3f, Vector3f) {
+    let terms = [p[0].to_vec() * b[0], p[1].to_vec() * b[1], p[2].to_vec() * b[2]];
+    let abs_sum = terms.iter().fold(Vector3f::zero(), |s, v| s + v.map(Float::abs));
+    (Point3::from_vec(terms[0] + terms[1] + terms[2]), abs_sum * gamma(7))
 }
 
 impl Intersect for Triangle {
-    fn intersect(&self, _ray: &Ray) -> Option<GeometryIntersection> {
-        unimplemented!()
+    /// `uv` is the barycentric coordinate `(b1, b2)` of the hit position
+    fn intersect(&self, ray: &Ray) -> Option<GeometryIntersection> {
+        let [p0, p1, p2] = self.vertices;
+        let (t, [b0, b1, b2]) = intersect_triangle(ray, p0, p1, p2)?;
+        let normal = self.face_normal().normalize();
+        debug_assert_approx!(b0 + b1 + b2, 1.);
+        let (pos, p_error) = barycentric_position(self.vertices, [b0, b1, b2]);
+        let (normal, side) = if dot(normal, ray.dir) < 0. {
+            (normal, Side::Outside)
+        } else {
+            (-normal, Side::Inside)
+        };
+        Some(GeometryIntersection {
+            pos,
+            normal,
+            wi: -ray.dir,
+            t,
+            side,
+            uv: pt2(b1, b2),
+            dpdu: p1 - p0,
+            dpdv: p2 - p0,
+            p_error,
+        })
     }
 }
 
 impl Geometry for Triangle {
-    // todo
+    fn bounds(&self) -> Bounds3f {
+        Bounds3f::from_points(self.vertices.iter().cloned())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn unit_triangle() -> Triangle {
+        Triangle::new(pt3(0., 0., 0.), pt3(1., 0., 0.), pt3(0., 1., 0.))
+    }
+
+    #[test]
+    fn no_intersect() {
+        let tri = unit_triangle();
+        let r = Ray::new(pt3(1., 1., 1.), vec3(0., 0., -1.));
+        assert_eq!(tri.intersect(&r), None);
+        let r = Ray::new(pt3(0.2, 0.2, 1.), vec3(0., 0., 1.)); // pointing away
+        assert_eq!(tri.intersect(&r), None);
+        let r = Ray::new(pt3(0.2, 0.2, 1.), vec3(1., 0., 0.)); // parallel
+        assert_eq!(tri.intersect(&r), None);
+    }
+
+    #[test]
+    fn has_intersect() {
+        let tri = unit_triangle();
+        let r = Ray::new(pt3(0.25, 0.5, 2.), vec3(0., 0., -1.));
+        let its = tri.intersect(&r).unwrap();
+        assert_approx!(its.t, 2.);
+        assert_approx!((its.pos - pt3(0.25, 0.5, 0.)).magnitude(), 0.);
+        assert_eq!(its.normal, vec3(0., 0., 1.));
+        assert_eq!(its.side, Side::Outside);
+        assert_approx!(its.uv.x, 0.25);
+        assert_approx!(its.uv.y, 0.5);
+        assert_eq!(its.shading_frame(), Matrix3::identity());
+
+        let r = Ray::new(pt3(0.25, 0.5, -2.), vec3(0., 0., 1.));
+        let its = tri.intersect(&r).unwrap();
+        assert_eq!(its.normal, vec3(0., 0., -1.));
+        assert_eq!(its.side, Side::Inside);
+    }
+
+    #[test]
+    fn watertight() {
+        // two triangles sharing the diagonal edge of a unit square
+        let a = Triangle::new(pt3(0., 0., 0.), pt3(1., 0., 0.), pt3(1., 1., 0.));
+        let b = Triangle::new(pt3(0., 0., 0.), pt3(1., 1., 0.), pt3(0., 1., 0.));
+        for i in 0..=100 {
+            let x = i as Float / 100.;
+            let r = Ray::new(pt3(x, x, 1.), vec3(0., 0., -1.));
+            assert!(a.intersect(&r).is_some() || b.intersect(&r).is_some(), "leaked at {}", x);
+        }
+    }
+
+    #[test]
+    fn spawn_ray() {
+        use rand::random;
+        // far from the origin, where a fixed epsilon is below the rounding error
+        let tri = Triangle::new(pt3(1e3, 2e3, -5e2), pt3(1.2e3, 2e3, -4e2), pt3(1e3, 2.3e3, -6e2));
+        let center = pt3(1.07e3, 2.1e3, -5e2);
+        for _ in 0..1000 {
+            let org = center + vec3(random::<Float>() - 0.5, random::<Float>() - 0.5, random::<Float>() - 0.5) * 1e3;
+            let target = center + vec3(random::<Float>() - 0.5, random::<Float>() - 0.5, random::<Float>() - 0.5) * 50.;
+            let its = match tri.intersect(&Ray::new(org, (target - org).normalize())) {
+                Some(its) => its,
+                None => continue,
+            };
+            let dir = vec3(random::<Float>() - 0.5, random::<Float>() - 0.5, random::<Float>() - 0.5).normalize();
+            assert_eq!(tri.intersect(&its.spawn_ray(dir)), None);
+            assert!(!tri.intersect_p(&its.spawn_ray_to(org)));
+        }
+    }
 }
@@ -0,0 +1,186 @@
+use super::*;
+
+#[derive(Debug, Clone)]
+/// Rough metal: microfacets reflecting like mirrors, Torrance-Sparrow
+///
+/// The reflectance follows the exact Fresnel equations of a complex index of refraction `eta + i k`, per RGB channel,
+/// so the texture is better left white. A smooth `distribution` makes it a mirror, sampled as a delta lobe.
+pub struct Conductor {
+    pub eta: Spectrum,
+    /// Absorption, the imaginary part of the index
+    pub k: Spectrum,
+    pub distribution: Microfacet,
+}
+
+impl Conductor {
+    pub fn new(eta: Spectrum, k: Spectrum, distribution: Microfacet) -> Self { Self { eta, k, distribution } }
+
+    /// Measured indices at 650, 550 and 450 nm
+    pub fn gold(distribution: Microfacet) -> Self {
+        Self::new(Spectrum::new(0.143_119, 0.374_957, 1.442_48), Spectrum::new(3.983_16, 2.385_72, 1.603_22), distribution)
+    }
+
+    pub fn copper(distribution: Microfacet) -> Self {
+        Self::new(Spectrum::new(0.200_438, 0.924_033, 1.102_21), Spectrum::new(3.912_95, 2.452_85, 2.142_19), distribution)
+    }
+
+    pub fn silver(distribution: Microfacet) -> Self {
+        Self::new(Spectrum::new(0.155_265, 0.116_723, 0.138_342), Spectrum::new(4.828_35, 3.122_25, 2.146_96), distribution)
+    }
+
+    pub fn aluminium(distribution: Microfacet) -> Self {
+        Self::new(Spectrum::new(1.657_46, 0.880_369, 0.521_229), Spectrum::new(9.223_87, 6.269_52, 4.837), distribution)
+    }
+
+    /// Reflectance at the angle of `cos_theta_i`
+    pub fn fresnel(&self, cos_theta_i: Float) -> Spectrum {
+        Spectrum::new(
+            fr_conductor(cos_theta_i, self.eta.r, self.k.r),
+            fr_conductor(cos_theta_i, self.eta.g, self.k.g),
+            fr_conductor(cos_theta_i, self.eta.b, self.k.b),
+        )
+    }
+
+    /// Local directions `wi` and `wo`, return `f x cos theta_o` and the pdf of sampling `wo`
+    fn eval_pdf(&self, wi: Vector3f, wo: Vector3f) -> (Spectrum, Float) {
+        if wi.z <= 0. || wo.z <= 0. { return (Spectrum::black(), 0.); }
+        let wh = wi + wo;
+        if wh.magnitude2() == 0. { return (Spectrum::black(), 0.); }
+        let wh = wh.normalize();
+        let d = self.distribution.d(wh);
+        let f = d * self.distribution.g(wi, wo) / (4. * wi.z);
+        let pdf = self.distribution.pdf(wi, wh) / (4. * dot(wi, wh));
+        (self.fresnel(dot(wi, wh)) * f, pdf)
+    }
+}
+
+impl BSDF for Conductor {
+    /// Sample the microfacet normals visible from `its.wi`, and reflect about them
+    fn sample(&self, its: &GeometryIntersection, samp: Point2f) -> SampleRecord {
+        if self.distribution.is_smooth() {
+            return SampleRecord {
+                wo: 2. * dot(its.wi, its.normal) * its.normal - its.wi,
+                weight: self.fresnel(dot(its.wi, its.normal)),
+                pdf: 1.,
+            };
+        }
+        let frame = its.shading_frame();
+        let wi = frame.transpose() * its.wi;
+        let wh = self.distribution.sample_wh(wi, samp);
+        let wo = 2. * dot(wi, wh) * wh - wi;
+        let (weight, pdf) = self.eval_pdf(wi, wo);
+        SampleRecord { wo: frame * wo, weight, pdf }
+    }
+
+    fn eval(&self, its: &GeometryIntersection, wo: Vector3f) -> Spectrum {
+        if self.distribution.is_smooth() { return Spectrum::black(); }
+        let frame = its.shading_frame().transpose();
+        self.eval_pdf(frame * its.wi, frame * wo).0
+    }
+
+    fn pdf(&self, its: &GeometryIntersection, wo: Vector3f) -> Float {
+        if self.distribution.is_smooth() { return 0.; }
+        let frame = its.shading_frame().transpose();
+        self.eval_pdf(frame * its.wi, frame * wo).1
+    }
+
+    fn is_delta(&self) -> bool { self.distribution.is_smooth() }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::test_util::*;
+    use crate::macros::*;
+    use rand::random;
+
+    #[test]
+    fn energy() {
+        // the long tail of GGX reflects more below the horizon
+        for &(ty, alpha, lost) in &[
+            (MicrofacetType::Beckmann, 0.05, 0.01), (MicrofacetType::Beckmann, 0.5, 0.1),
+            (MicrofacetType::GGX, 0.05, 0.01), (MicrofacetType::GGX, 0.5, 0.35),
+        ] {
+            let metal = Conductor::silver(Microfacet::isotropic(ty, alpha));
+            let its = its(vec3(0.3, 0., 1.).normalize(), Side::Outside);
+            let n = 20_000;
+            let mut sampled = 0.;
+            for _ in 0..n {
+                let rec = metal.sample(&its, pt2(random(), random()));
+                if rec.pdf == 0. { continue; }
+                assert_approx!(rec.wo.magnitude(), 1.);
+                assert_approx!(metal.eval(&its, rec.wo).r, rec.weight.r);
+                assert_approx!(metal.pdf(&its, rec.wo), rec.pdf);
+                // the masking of the way out, at most
+                assert_le!(rec.weight.r / rec.pdf, 1. + 1e-3);
+                sampled += rec.weight.r / rec.pdf;
+            }
+            let albedo = sampled / n as Float;
+            // only the multiple bounces between the microfacets are missing, and what silver absorbs
+            assert_le!(albedo, 1.);
+            assert_ge!(albedo, (1. - lost) * metal.fresnel(1.).r, "{:?} {}", ty, alpha);
+            if alpha > 0.1 {
+                // the same from uniform directions
+                let uniform: Float = (0..n).map(|_| metal.eval(&its, uniform_hemisphere()).r).sum();
+                assert_lt!((uniform * 2. * Float::PI() / n as Float - albedo).abs(), 0.03, "{:?}", ty);
+            }
+        }
+    }
+
+    #[test]
+    fn brushed() {
+        // smooth along x, rough along y
+        let metal = Conductor::aluminium(Microfacet::new(MicrofacetType::GGX, 0.02, 0.4));
+        let its = its(vec3(0., 0., 1.), Side::Outside);
+        let (mut sx, mut sy) = (0., 0.);
+        for _ in 0..10000 {
+            let wo = metal.sample(&its, pt2(random(), random())).wo;
+            sx += wo.x.abs();
+            sy += wo.y.abs();
+        }
+        assert_gt!(sy, 5. * sx);
+        // the highlight stretches across the brushing
+        let along = metal.eval(&its, vec3(0.2, 0., 1.).normalize()).r;
+        let across = metal.eval(&its, vec3(0., 0.2, 1.).normalize()).r;
+        assert_gt!(across, 10. * along);
+    }
+
+    #[test]
+    fn smooth() {
+        let mirror = Conductor::gold(Microfacet::isotropic(MicrofacetType::GGX, 0.));
+        assert!(mirror.is_delta());
+        let its = its(vec3(1., 0., 1.).normalize(), Side::Outside);
+        let rec = mirror.sample(&its, pt2(random(), random()));
+        assert_approx!((rec.wo - vec3(-1., 0., 1.).normalize()).magnitude(), 0.);
+        // a tinted mirror
+        assert_eq!(rec.weight, mirror.fresnel(Float::FRAC_1_SQRT_2()));
+        assert_lt!(rec.weight.b, 0.5 * rec.weight.r);
+        assert_eq!(mirror.eval(&its, rec.wo), Spectrum::black());
+        assert_eq!(mirror.pdf(&its, rec.wo), 0.);
+    }
+
+    #[test]
+    fn presets() {
+        let smooth = Microfacet::isotropic(MicrofacetType::GGX, 0.);
+        let (gold, copper, silver, aluminium) =
+            (Conductor::gold(smooth.clone()), Conductor::copper(smooth.clone()), Conductor::silver(smooth.clone()), Conductor::aluminium(smooth));
+        // head-on, ((n - 1)^2 + k^2) / ((n + 1)^2 + k^2)
+        for metal in &[&gold, &copper, &silver, &aluminium] {
+            let (n, k) = (metal.eta.g, metal.k.g);
+            assert_approx!(metal.fresnel(1.).g, ((n - 1.) * (n - 1.) + k * k) / ((n + 1.) * (n + 1.) + k * k));
+            // and all reflected at grazing
+            assert_gt!(metal.fresnel(1e-3).min(), 0.99);
+        }
+        let r0 = |metal: &Conductor| metal.fresnel(1.);
+        // yellow and orange
+        assert!(r0(&gold).r > r0(&gold).g && r0(&gold).g > r0(&gold).b);
+        assert_lt!(r0(&gold).b, 0.45);
+        assert!(r0(&copper).r > r0(&copper).g && r0(&copper).g > r0(&copper).b);
+        assert_lt!(r0(&copper).g, r0(&gold).g);
+        // nearly white
+        assert_gt!(r0(&silver).min(), 0.9);
+        assert_gt!(r0(&aluminium).min(), 0.85);
+        // darker at an angle before rising toward grazing
+        assert_lt!(aluminium.fresnel(0.2).r, r0(&aluminium).r);
+    }
+}
@@ -0,0 +1,78 @@
+use super::*;
+use super::simple::{Diffuse, Specular, Dielectric};
+use derive_more::*;
+
+#[derive(Debug, Clone, From)]
+/// Any of the BSDFs, so that different kinds can share a scene
+///
+/// Note: Performance can be worse than static ones
+pub enum DynamicBSDF {
+    Diffuse(Diffuse),
+    Specular(Specular),
+    Dielectric(Dielectric),
+    Hair(Hair),
+    Conductor(Conductor),
+    RoughDielectric(RoughDielectric),
+}
+
+impl Default for DynamicBSDF {
+    fn default() -> Self {
+        Diffuse.into()
+    }
+}
+
+impl From<Simple> for DynamicBSDF {
+    fn from(simple: Simple) -> Self {
+        match simple {
+            Simple::Diffuse(d) => d.into(),
+            Simple::Specular(s) => s.into(),
+            Simple::Dielectric(d) => d.into(),
+        }
+    }
+}
+
+impl BSDF for DynamicBSDF {
+    fn sample(&self, its: &GeometryIntersection, samp: Point2f) -> SampleRecord {
+        match self {
+            DynamicBSDF::Diffuse(d) => d.sample(its, samp),
+            DynamicBSDF::Specular(s) => s.sample(its, samp),
+            DynamicBSDF::Dielectric(d) => d.sample(its, samp),
+            DynamicBSDF::Hair(h) => h.sample(its, samp),
+            DynamicBSDF::Conductor(c) => c.sample(its, samp),
+            DynamicBSDF::RoughDielectric(d) => d.sample(its, samp),
+        }
+    }
+
+    fn eval(&self, its: &GeometryIntersection, wo: Vector3f) -> Spectrum {
+        match self {
+            DynamicBSDF::Diffuse(d) => d.eval(its, wo),
+            DynamicBSDF::Specular(s) => s.eval(its, wo),
+            DynamicBSDF::Dielectric(d) => d.eval(its, wo),
+            DynamicBSDF::Hair(h) => h.eval(its, wo),
+            DynamicBSDF::Conductor(c) => c.eval(its, wo),
+            DynamicBSDF::RoughDielectric(d) => d.eval(its, wo),
+        }
+    }
+
+    fn pdf(&self, its: &GeometryIntersection, wo: Vector3f) -> Float {
+        match self {
+            DynamicBSDF::Diffuse(d) => d.pdf(its, wo),
+            DynamicBSDF::Specular(s) => s.pdf(its, wo),
+            DynamicBSDF::Dielectric(d) => d.pdf(its, wo),
+            DynamicBSDF::Hair(h) => h.pdf(its, wo),
+            DynamicBSDF::Conductor(c) => c.pdf(its, wo),
+            DynamicBSDF::RoughDielectric(d) => d.pdf(its, wo),
+        }
+    }
+
+    fn is_delta(&self) -> bool {
+        match self {
+            DynamicBSDF::Diffuse(d) => d.is_delta(),
+            DynamicBSDF::Specular(s) => s.is_delta(),
+            DynamicBSDF::Dielectric(d) => d.is_delta(),
+            DynamicBSDF::Hair(h) => h.is_delta(),
+            DynamicBSDF::Conductor(c) => c.is_delta(),
+            DynamicBSDF::RoughDielectric(d) => d.is_delta(),
+        }
+    }
+}
@@ -0,0 +1,360 @@
+use super::*;
+
+/// Scattering lobes modeled one by one: R, TT and TRT, the rest are lumped into the last
+const P_MAX: usize = 3;
+const SQRT_PI_OVER_8: Float = 0.626_657_07;
+
+#[derive(Debug, Clone)]
+/// Hair scattering of Chiang et al. 2016, for `Curve`s
+///
+/// The fiber is a rough dielectric cylinder absorbing inside, with tilted cuticle scales.
+/// Local directions are taken in the shading frame: `x` along the fiber (`dpdu`), `z` the normal.
+/// The offset across the fiber comes from `uv.y`, which runs across the width of a curve.
+pub struct Hair {
+    eta: Float,
+    sigma_a: Spectrum,
+    /// Longitudinal and azimuthal roughness, in `[0, 1]`
+    beta_m: Float,
+    beta_n: Float,
+    /// Longitudinal variance of each lobe
+    v: [Float; P_MAX + 1],
+    /// Azimuthal logistic scale
+    s: Float,
+    /// `sin` and `cos` of 2, 4 and 8 times the scale tilt
+    sin_2k_alpha: [Float; 3],
+    cos_2k_alpha: [Float; 3],
+}
+
+impl Hair {
+    /// `sigma_a`: absorption coefficient per unit of the fiber radius
+    pub fn new(sigma_a: Spectrum, beta_m: Float, beta_n: Float) -> Self {
+        debug_assert!((0. ..=1.).contains(&beta_m) && (0. ..=1.).contains(&beta_n));
+        let v0 = (0.726 * beta_m + 0.812 * beta_m * beta_m + 3.7 * beta_m.powi(20)).powi(2);
+        let s = SQRT_PI_OVER_8 * (0.265 * beta_n + 1.194 * beta_n * beta_n + 5.372 * beta_n.powi(22));
+        Self {
+            eta: 1.55,
+            sigma_a,
+            beta_m,
+            beta_n,
+            v: [v0, 0.25 * v0, 4. * v0, 4. * v0],
+            s,
+            sin_2k_alpha: [0.; 3],
+            cos_2k_alpha: [1.; 3],
+        }.with_alpha(Deg(2.))
+    }
+
+    /// The color from the concentrations of the two pigments, brown-black and red-yellow
+    pub fn from_melanin(eumelanin: Float, pheomelanin: Float, beta_m: Float, beta_n: Float) -> Self {
+        let eu = Spectrum::new(0.419, 0.697, 1.37);
+        let pheo = Spectrum::new(0.187, 0.4, 1.05);
+        Self::new(eu * eumelanin + pheo * pheomelanin, beta_m, beta_n)
+    }
+
+    /// The absorption giving roughly `color` after multiple scattering, components in `(0, 1]`
+    pub fn from_reflectance(color: &Spectrum, beta_m: Float, beta_n: Float) -> Self {
+        let k = 5.969 - 0.215 * beta_n + 2.532 * beta_n.powi(2) - 10.73 * beta_n.powi(3)
+            + 5.574 * beta_n.powi(4) + 0.245 * beta_n.powi(5);
+        let sigma = |c: Float| (c.ln() / k).powi(2);
+        Self::new(Spectrum::new(sigma(color.r), sigma(color.g), sigma(color.b)), beta_m, beta_n)
+    }
+
+    /// Refraction index of the fiber, 1.55 by default
+    pub fn with_eta(mut self, eta: Float) -> Self {
+        self.eta = eta;
+        self
+    }
+
+    /// Tilt of the cuticle scales, 2 degrees by default
+    pub fn with_alpha(mut self, alpha: impl Into<Radf>) -> Self {
+        let (sin, cos) = alpha.into().0.sin_cos();
+        self.sin_2k_alpha[0] = sin;
+        self.cos_2k_alpha[0] = cos;
+        for i in 1..3 {
+            self.sin_2k_alpha[i] = 2. * self.cos_2k_alpha[i - 1] * self.sin_2k_alpha[i - 1];
+            self.cos_2k_alpha[i] = self.cos_2k_alpha[i - 1].powi(2) - self.sin_2k_alpha[i - 1].powi(2);
+        }
+        self
+    }
+
+    pub fn eta(&self) -> Float { self.eta }
+    pub fn sigma_a(&self) -> &Spectrum { &self.sigma_a }
+    pub fn beta_m(&self) -> Float { self.beta_m }
+    pub fn beta_n(&self) -> Float { self.beta_n }
+
+    /// `(gamma_t, transmittance)` of one pass through the fiber, entering at the offset `h`
+    fn transmit(&self, sin_theta_o: Float, cos_theta_o: Float, h: Float) -> (Float, Spectrum) {
+        let sin_theta_t = sin_theta_o / self.eta;
+        let cos_theta_t = safe_sqrt(1. - sin_theta_t * sin_theta_t);
+        // the modified index in the plane across the fiber
+        let etap = (self.eta * self.eta - sin_theta_o * sin_theta_o).sqrt() / cos_theta_o;
+        let sin_gamma_t = h / etap;
+        let cos_gamma_t = safe_sqrt(1. - sin_gamma_t * sin_gamma_t);
+        (safe_asin(sin_gamma_t), (&self.sigma_a * (-2. * cos_gamma_t / cos_theta_t)).exp())
+    }
+
+    /// Attenuation of each lobe
+    fn ap(&self, cos_theta_o: Float, h: Float, t: &Spectrum) -> [Spectrum; P_MAX + 1] {
+        let cos_gamma_o = safe_sqrt(1. - h * h);
+        let f = fr_dielectric(cos_theta_o * cos_gamma_o, self.eta);
+        if f >= 1. { // grazing, all reflected off the surface
+            return [Spectrum::white(), Spectrum::black(), Spectrum::black(), Spectrum::black()];
+        }
+        let tt = t * (1. - f) * (1. - f);
+        let trt = &tt * t * f;
+        let rest = &trt * t * f / (Spectrum::white() - t * f);
+        [Spectrum::uniform(f), tt, trt, rest]
+    }
+
+    /// `sin` and `|cos|` of theta_o, tilted by the scales as seen by the lobe `p`
+    fn tilt(&self, p: usize, sin_theta_o: Float, cos_theta_o: Float) -> (Float, Float) {
+        let (sin, cos) = match p {
+            0 => (sin_theta_o * self.cos_2k_alpha[1] - cos_theta_o * self.sin_2k_alpha[1],
+                  cos_theta_o * self.cos_2k_alpha[1] + sin_theta_o * self.sin_2k_alpha[1]),
+            1 => (sin_theta_o * self.cos_2k_alpha[0] + cos_theta_o * self.sin_2k_alpha[0],
+                  cos_theta_o * self.cos_2k_alpha[0] - sin_theta_o * self.sin_2k_alpha[0]),
+            2 => (sin_theta_o * self.cos_2k_alpha[2] + cos_theta_o * self.sin_2k_alpha[2],
+                  cos_theta_o * self.cos_2k_alpha[2] - sin_theta_o * self.sin_2k_alpha[2]),
+            _ => (sin_theta_o, cos_theta_o),
+        };
+        (sin, cos.abs())
+    }
+
+    /// `f x |cos theta_i|` and the sampling pdf, of local unit directions
+    fn eval_pdf(&self, wo: Vector3f, wi: Vector3f, h: Float) -> (Spectrum, Float) {
+        let sin_theta_o = wo.x;
+        let cos_theta_o = safe_sqrt(1. - sin_theta_o * sin_theta_o);
+        let sin_theta_i = wi.x;
+        let cos_theta_i = safe_sqrt(1. - sin_theta_i * sin_theta_i);
+        let phi = wi.z.atan2(wi.y) - wo.z.atan2(wo.y);
+        let gamma_o = safe_asin(h);
+        let (gamma_t, t) = self.transmit(sin_theta_o, cos_theta_o, h);
+        let ap = self.ap(cos_theta_o, h, &t);
+        let ap_pdf = ap_pdf(&ap);
+        let mut f = Spectrum::black();
+        let mut pdf = 0.;
+        for p in 0..P_MAX {
+            let (sin_theta_op, cos_theta_op) = self.tilt(p, sin_theta_o, cos_theta_o);
+            let mn = mp(cos_theta_i, cos_theta_op, sin_theta_i, sin_theta_op, self.v[p])
+                * np(phi, p, self.s, gamma_o, gamma_t);
+            f += &ap[p] * mn;
+            pdf += ap_pdf[p] * mn;
+        }
+        let m = mp(cos_theta_i, cos_theta_o, sin_theta_i, sin_theta_o, self.v[P_MAX]) / (2. * Float::PI());
+        f += &ap[P_MAX] * m;
+        pdf += ap_pdf[P_MAX] * m;
+        (f, pdf)
+    }
+}
+
+impl BSDF for Hair {
+    /// Pick a lobe by its attenuation, then sample its longitudinal and azimuthal parts
+    fn sample(&self, its: &GeometryIntersection, samp: Point2f) -> SampleRecord {
+        let h = -1. + 2. * its.uv.y;
+        let frame = its.shading_frame();
+        let wo = frame.transpose() * its.wi;
+        let sin_theta_o = wo.x;
+        let cos_theta_o = safe_sqrt(1. - sin_theta_o * sin_theta_o);
+        let phi_o = wo.z.atan2(wo.y);
+        let (gamma_t, t) = self.transmit(sin_theta_o, cos_theta_o, h);
+        let ap_pdf = ap_pdf(&self.ap(cos_theta_o, h, &t));
+
+        // 4 samples out of 2
+        let (u0, u1) = (demux(samp.x), demux(samp.y));
+        let mut u_lobe = u0.x;
+        let mut p = 0;
+        while p < P_MAX && u_lobe >= ap_pdf[p] {
+            u_lobe -= ap_pdf[p];
+            p += 1;
+        }
+        let (sin_theta_op, cos_theta_op) = self.tilt(p, sin_theta_o, cos_theta_o);
+        let u = u1.x.max(1e-5);
+        let cos_theta = 1. + self.v[p] * (u + (1. - u) * (-2. / self.v[p]).exp()).ln();
+        let sin_theta = safe_sqrt(1. - cos_theta * cos_theta);
+        let cos_phi = (2. * Float::PI() * u1.y).cos();
+        let sin_theta_i = -cos_theta * sin_theta_op + sin_theta * cos_phi * cos_theta_op;
+        let cos_theta_i = safe_sqrt(1. - sin_theta_i * sin_theta_i);
+        let dphi = if p < P_MAX {
+            phi(p, safe_asin(h), gamma_t) + sample_trimmed_logistic(u0.y, self.s, -Float::PI(), Float::PI())
+        } else {
+            2. * Float::PI() * u0.y
+        };
+        let phi_i = phi_o + dphi;
+        let wi = vec3(sin_theta_i, cos_theta_i * phi_i.cos(), cos_theta_i * phi_i.sin());
+        let (weight, pdf) = self.eval_pdf(wo, wi, h);
+        SampleRecord { wo: frame * wi, weight, pdf }
+    }
+
+    fn eval(&self, its: &GeometryIntersection, wo: Vector3f) -> Spectrum {
+        let frame = its.shading_frame().transpose();
+        self.eval_pdf(frame * its.wi, frame * wo, -1. + 2. * its.uv.y).0
+    }
+
+    fn pdf(&self, its: &GeometryIntersection, wo: Vector3f) -> Float {
+        let frame = its.shading_frame().transpose();
+        self.eval_pdf(frame * its.wi, frame * wo, -1. + 2. * its.uv.y).1
+    }
+}
+
+#[inline]
+fn safe_sqrt(x: Float) -> Float { x.max(0.).sqrt() }
+
+#[inline]
+fn safe_asin(x: Float) -> Float { num_traits::clamp(x, -1., 1.).asin() }
+
+/// Probabilities of picking the lobes, by their attenuation
+fn ap_pdf(ap: &[Spectrum; P_MAX + 1]) -> [Float; P_MAX + 1] {
+    let total: Float = ap.iter().map(Spectrum::sum).sum();
+    let mut pdf = [0.; P_MAX + 1];
+    for (pdf, a) in pdf.iter_mut().zip(ap) {
+        *pdf = a.sum() / total;
+    }
+    pdf
+}
+
+/// Modified Bessel function of the first kind, of order 0
+fn i0(x: Float) -> Float {
+    let mut val = 0.;
+    let mut x2i = 1.;
+    let mut ifact: i64 = 1;
+    let mut i4: i64 = 1;
+    for i in 0..10 {
+        if i > 1 { ifact *= i; }
+        val += x2i / (i4 as Float * (ifact * ifact) as Float);
+        x2i *= x * x;
+        i4 *= 4;
+    }
+    val
+}
+
+fn log_i0(x: Float) -> Float {
+    if x > 12. {
+        x + 0.5 * (-(2. * Float::PI()).ln() + (1. / x).ln() + 1. / (8. * x))
+    } else {
+        i0(x).ln()
+    }
+}
+
+/// Longitudinal scattering of variance `v`
+fn mp(cos_theta_i: Float, cos_theta_o: Float, sin_theta_i: Float, sin_theta_o: Float, v: Float) -> Float {
+    let a = cos_theta_i * cos_theta_o / v;
+    let b = sin_theta_i * sin_theta_o / v;
+    if v <= 0.1 {
+        // in the log space, not to overflow
+        (log_i0(a) - b - 1. / v + Float::LN_2() + (1. / (2. * v)).ln()).exp()
+    } else {
+        (-b).exp() * i0(a) / ((1. / v).sinh() * 2. * v)
+    }
+}
+
+/// Azimuthal deflection of the lobe `p`
+#[inline]
+fn phi(p: usize, gamma_o: Float, gamma_t: Float) -> Float {
+    let p = p as Float;
+    2. * p * gamma_t - 2. * gamma_o + p * Float::PI()
+}
+
+#[inline]
+fn logistic(x: Float, s: Float) -> Float {
+    let e = (-x.abs() / s).exp();
+    e / (s * (1. + e) * (1. + e))
+}
+
+#[inline]
+fn logistic_cdf(x: Float, s: Float) -> Float { 1. / (1. + (-x / s).exp()) }
+
+#[inline]
+fn trimmed_logistic(x: Float, s: Float, a: Float, b: Float) -> Float {
+    logistic(x, s) / (logistic_cdf(b, s) - logistic_cdf(a, s))
+}
+
+fn sample_trimmed_logistic(u: Float, s: Float, a: Float, b: Float) -> Float {
+    let k = logistic_cdf(b, s) - logistic_cdf(a, s);
+    let x = -s * (1. / (u * k + logistic_cdf(a, s)) - 1.).ln();
+    num_traits::clamp(x, a, b)
+}
+
+/// Azimuthal scattering of the lobe `p`, around its deflection
+fn np(phi_: Float, p: usize, s: Float, gamma_o: Float, gamma_t: Float) -> Float {
+    let mut dphi = phi_ - phi(p, gamma_o, gamma_t);
+    while dphi > Float::PI() { dphi -= 2. * Float::PI(); }
+    while dphi < -Float::PI() { dphi += 2. * Float::PI(); }
+    trimmed_logistic(dphi, s, -Float::PI(), Float::PI())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::test_util::{self, uniform_sphere};
+    use crate::macros::*;
+    use rand::random;
+
+    /// Across the strand at `v`
+    fn its(wi: Vector3f, v: Float) -> GeometryIntersection {
+        GeometryIntersection { uv: pt2(0.5, v), ..test_util::its(wi, Side::Outside) }
+    }
+
+    #[test]
+    fn white_furnace() {
+        // nothing is absorbed, and the lobes are sampled by their share
+        for &(beta_m, beta_n) in &[(0.1, 0.3), (0.3, 0.3), (0.7, 0.9), (1., 1.)] {
+            let hair = Hair::new(Spectrum::black(), beta_m, beta_n);
+            for _ in 0..1000 {
+                let rec = hair.sample(&its(uniform_sphere(), random()), pt2(random(), random()));
+                assert_approx!(rec.wo.magnitude(), 1.);
+                if rec.pdf > 0. {
+                    assert_lt!((rec.weight.r / rec.pdf - 1.).abs(), 1e-3);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn pdf_integrates_to_one() {
+        for &(beta_m, beta_n) in &[(0.3, 0.3), (0.6, 0.8)] {
+            let hair = Hair::new(Spectrum::black(), beta_m, beta_n);
+            let n = 100_000;
+            let (mut pdf, mut energy) = (0., 0.);
+            for _ in 0..n {
+                let (f, p) = hair.eval_pdf(uniform_sphere(), uniform_sphere(), -1. + 2. * random::<Float>());
+                pdf += p;
+                energy += f.r;
+            }
+            let scale = 4. * Float::PI() / n as Float;
+            assert_lt!((pdf * scale - 1.).abs(), 0.05);
+            // a white fiber reflects everything
+            assert_lt!((energy * scale - 1.).abs(), 0.05);
+        }
+    }
+
+    #[test]
+    fn eval_matches_sample() {
+        let hair = Hair::from_melanin(1.3, 0., 0.3, 0.4);
+        for _ in 0..1000 {
+            let its = its(uniform_sphere(), random());
+            let rec = hair.sample(&its, pt2(random(), random()));
+            let f = hair.eval(&its, rec.wo);
+            assert_approx!(f.r, rec.weight.r);
+            assert_approx!(f.b, rec.weight.b);
+            assert_approx!(hair.pdf(&its, rec.wo), rec.pdf);
+        }
+        assert!(!hair.is_delta());
+    }
+
+    #[test]
+    fn melanin_absorbs() {
+        let dark = Hair::from_melanin(8., 0., 0.3, 0.3);
+        let blond = Hair::from_melanin(0.3, 0., 0.3, 0.3);
+        let mean = |hair: &Hair| (0..10000).map(|_| {
+            let rec = hair.sample(&its(uniform_sphere(), random()), pt2(random(), random()));
+            if rec.pdf > 0. { rec.weight.sum() / rec.pdf } else { 0. }
+        }).sum::<Float>() / 10000.;
+        let (dark, blond) = (mean(&dark), mean(&blond));
+        assert_lt!(dark, blond);
+        assert_lt!(blond, 3.);
+        // the reflection off the surface stays
+        assert_gt!(dark, 0.1);
+        // more absorbed in red than in blue
+        let reddish = Hair::from_reflectance(&Spectrum::new(0.8, 0.4, 0.2), 0.3, 0.3);
+        assert_lt!(reddish.sigma_a().r, reddish.sigma_a().b);
+    }
+}
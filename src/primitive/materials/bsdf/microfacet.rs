@@ -0,0 +1,293 @@
+use super::*;
+
+/// Below this `alpha` a surface is taken as perfectly smooth, and sampled as a delta lobe
+const SMOOTH_ALPHA: Float = 1e-3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MicrofacetType {
+    /// Gaussian slopes, a sharp highlight
+    Beckmann,
+    /// Trowbridge-Reitz, a longer tail around the highlight
+    GGX,
+}
+
+#[derive(Debug, Clone)]
+/// Distribution of the microfacet normals of a rough surface, with Smith masking-shadowing
+///
+/// Directions are local to the shading frame: `z` the normal, `x` along `dpdu`.
+/// `alpha_x` and `alpha_y` are the roughness along `x` and `y`, they differ for anisotropic surfaces,
+/// e.g. brushed metal is smoother along the brushing.
+pub struct Microfacet {
+    ty: MicrofacetType,
+    alpha_x: Float,
+    alpha_y: Float,
+}
+
+impl Microfacet {
+    pub fn new(ty: MicrofacetType, alpha_x: Float, alpha_y: Float) -> Self {
+        debug_assert!(alpha_x >= 0. && alpha_y >= 0.);
+        Self { ty, alpha_x, alpha_y }
+    }
+
+    #[inline]
+    pub fn isotropic(ty: MicrofacetType, alpha: Float) -> Self { Self::new(ty, alpha, alpha) }
+
+    /// From a perceptual roughness in `[0, 1]` along `x` and `y`, `alpha` is its square
+    pub fn from_roughness(ty: MicrofacetType, roughness_x: Float, roughness_y: Float) -> Self {
+        Self::new(ty, roughness_x * roughness_x, roughness_y * roughness_y)
+    }
+
+    #[inline]
+    pub fn ty(&self) -> MicrofacetType { self.ty }
+    #[inline]
+    pub fn alpha_x(&self) -> Float { self.alpha_x }
+    #[inline]
+    pub fn alpha_y(&self) -> Float { self.alpha_y }
+
+    /// Too smooth to be sampled as a distribution, treat it as a mirror
+    #[inline]
+    pub fn is_smooth(&self) -> bool { self.alpha_x.max(self.alpha_y) < SMOOTH_ALPHA }
+
+    /// Density of the normals `wh` per projected area, `D(wh)`
+    pub fn d(&self, wh: Vector3f) -> Float {
+        let cos2 = wh.z * wh.z;
+        if cos2 <= 0. { return 0.; }
+        let tan2 = (1. - cos2) / cos2;
+        if !tan2.is_finite() { return 0.; }
+        let e = tan2 * self.stretch(wh);
+        let denom = Float::PI() * self.alpha_x * self.alpha_y * cos2 * cos2;
+        match self.ty {
+            MicrofacetType::Beckmann => (-e).exp() / denom,
+            MicrofacetType::GGX => 1. / (denom * (1. + e) * (1. + e)),
+        }
+    }
+
+    /// Smith's auxiliary function, the masked microfacet area per visible area
+    pub fn lambda(&self, w: Vector3f) -> Float {
+        let cos2 = w.z * w.z;
+        let tan2 = (1. - cos2) / cos2;
+        if !tan2.is_finite() { return 0.; }
+        // the roughness seen from `w`
+        let alpha2 = self.alpha2_toward(w);
+        match self.ty {
+            MicrofacetType::Beckmann => {
+                let a = 1. / (alpha2 * tan2).sqrt();
+                if a >= 1.6 { return 0.; }
+                (1. - 1.259 * a + 0.396 * a * a) / (3.535 * a + 2.181 * a * a)
+            }
+            MicrofacetType::GGX => ((1. + alpha2 * tan2).sqrt() - 1.) / 2.,
+        }
+    }
+
+    /// Fraction of the microfacets facing `w` that are visible from it
+    #[inline]
+    pub fn g1(&self, w: Vector3f) -> Float { 1. / (1. + self.lambda(w)) }
+
+    /// Fraction visible from both `wo` and `wi`, height-correlated
+    #[inline]
+    pub fn g(&self, wo: Vector3f, wi: Vector3f) -> Float { 1. / (1. + self.lambda(wo) + self.lambda(wi)) }
+
+    /// Sample a normal among the ones visible from `wo`, of density `pdf`
+    pub fn sample_wh(&self, wo: Vector3f, samp: Point2f) -> Vector3f {
+        // sampled on the side of `wo`
+        let flip = wo.z < 0.;
+        let wo = if flip { -wo } else { wo };
+        let wh = match self.ty {
+            MicrofacetType::Beckmann => self.sample_beckmann(wo, samp),
+            MicrofacetType::GGX => self.sample_ggx(wo, samp),
+        };
+        if flip { -wh } else { wh }
+    }
+
+    /// Solid angle density of `sample_wh` picking `wh` for `wo`
+    pub fn pdf(&self, wo: Vector3f, wh: Vector3f) -> Float {
+        if wo.z == 0. { return 0.; }
+        self.d(wh) * self.g1(wo) * dot(wo, wh).max(0.) / wo.z.abs()
+    }
+
+    /// `cos^2 phi / alpha_x^2 + sin^2 phi / alpha_y^2` of `w`
+    fn stretch(&self, w: Vector3f) -> Float {
+        let sin2 = w.x * w.x + w.y * w.y;
+        if sin2 == 0. { return 1. / (self.alpha_x * self.alpha_y); }
+        (w.x * w.x / (self.alpha_x * self.alpha_x) + w.y * w.y / (self.alpha_y * self.alpha_y)) / sin2
+    }
+
+    /// `cos^2 phi alpha_x^2 + sin^2 phi alpha_y^2` of `w`
+    fn alpha2_toward(&self, w: Vector3f) -> Float {
+        let sin2 = w.x * w.x + w.y * w.y;
+        if sin2 == 0. { return self.alpha_x * self.alpha_y; }
+        (w.x * w.x * self.alpha_x * self.alpha_x + w.y * w.y * self.alpha_y * self.alpha_y) / sin2
+    }
+
+    /// Heitz 2018, the visible normals of the stretched hemisphere are a disk warped by the horizon
+    fn sample_ggx(&self, wo: Vector3f, samp: Point2f) -> Vector3f {
+        let vh = vec3(self.alpha_x * wo.x, self.alpha_y * wo.y, wo.z).normalize();
+        let len2 = vh.x * vh.x + vh.y * vh.y;
+        let t1 = if len2 > 0. { vec3(-vh.y, vh.x, 0.) / len2.sqrt() } else { vec3(1., 0., 0.) };
+        let t2 = vh.cross(t1);
+        let r = samp.x.sqrt();
+        let phi = 2. * Float::PI() * samp.y;
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1. + vh.z);
+        let p2 = (1. - s) * (1. - p1 * p1).max(0.).sqrt() + s * r * phi.sin();
+        let nh = t1 * p1 + t2 * p2 + vh * (1. - p1 * p1 - p2 * p2).max(0.).sqrt();
+        vec3(self.alpha_x * nh.x, self.alpha_y * nh.y, nh.z.max(1e-6)).normalize()
+    }
+
+    /// Jakob's slope sampling of the stretched, isotropic distribution, as pbrt does
+    fn sample_beckmann(&self, wo: Vector3f, samp: Point2f) -> Vector3f {
+        let stretched = vec3(self.alpha_x * wo.x, self.alpha_y * wo.y, wo.z).normalize();
+        let (mut slope_x, mut slope_y) = beckmann_slopes(stretched.z, samp);
+        // rotate toward the azimuth of `wo`, then unstretch
+        let sin_theta = (1. - stretched.z * stretched.z).max(0.).sqrt();
+        let (cos_phi, sin_phi) = if sin_theta > 0. {
+            (num_traits::clamp(stretched.x / sin_theta, -1., 1.), num_traits::clamp(stretched.y / sin_theta, -1., 1.))
+        } else {
+            (1., 0.)
+        };
+        let tmp = cos_phi * slope_x - sin_phi * slope_y;
+        slope_y = sin_phi * slope_x + cos_phi * slope_y;
+        slope_x = tmp;
+        vec3(-self.alpha_x * slope_x, -self.alpha_y * slope_y, 1.).normalize()
+    }
+}
+
+/// Slopes of the visible normals of the unit Beckmann distribution, seen at `cos_theta` in the `xz` plane
+fn beckmann_slopes(cos_theta: Float, samp: Point2f) -> (Float, Float) {
+    if cos_theta > 0.9999 { // head-on, all normals are visible
+        let r = (-(1. - samp.x).ln()).sqrt();
+        let phi = 2. * Float::PI() * samp.y;
+        return (r * phi.cos(), r * phi.sin());
+    }
+    let sin_theta = (1. - cos_theta * cos_theta).max(0.).sqrt();
+    let tan_theta = sin_theta / cos_theta;
+    let cot_theta = 1. / tan_theta;
+    // invert the cdf of the x slope by Newton-bisection
+    let (mut a, mut c) = (-1., erf(cot_theta));
+    let u = samp.x.max(1e-6);
+    let theta = cos_theta.acos();
+    let fit = 1. + theta * (-0.876 + theta * (0.4265 - 0.0594 * theta));
+    let mut b = c - (1. + c) * (1. - u).powf(fit);
+    let inv_sqrt_pi = Float::FRAC_2_SQRT_PI() / 2.;
+    let normalization = 1. / (1. + c + inv_sqrt_pi * tan_theta * (-cot_theta * cot_theta).exp());
+    for _ in 0..10 {
+        if !(b >= a && b <= c) { b = 0.5 * (a + c); }
+        let inv_erf = erf_inv(b);
+        let value = normalization * (1. + b + inv_sqrt_pi * tan_theta * (-inv_erf * inv_erf).exp()) - u;
+        if value.abs() < 1e-5 { break; }
+        if value > 0. { c = b; } else { a = b; }
+        let derivative = normalization * (1. - inv_erf * tan_theta);
+        b -= value / derivative;
+    }
+    (erf_inv(b), erf_inv(2. * samp.y.max(1e-6) - 1.))
+}
+
+/// Abramowitz and Stegun 7.1.26
+fn erf(x: Float) -> Float {
+    let (a1, a2, a3, a4, a5, p) = (0.254_829_6, -0.284_496_74, 1.421_413_8, -1.453_152, 1.061_405_4, 0.327_591_1);
+    let t = 1. / (1. + p * x.abs());
+    let y = 1. - ((((a5 * t + a4) * t + a3) * t + a2) * t + a1) * t * (-x * x).exp();
+    y.copysign(x)
+}
+
+/// Giles' single precision approximation
+#[allow(clippy::excessive_precision)]
+fn erf_inv(x: Float) -> Float {
+    let x = num_traits::clamp(x, -0.99999, 0.99999);
+    let w = -((1. - x) * (1. + x)).ln();
+    let p = if w < 5. {
+        let w = w - 2.5;
+        [3.43273939e-07, -3.5233877e-06, -4.39150654e-06, 0.00021858087, -0.00125372503, -0.00417768164, 0.246640727, 1.50140941]
+            .iter().fold(2.81022636e-08, |p, &c| c + p * w)
+    } else {
+        let w = w.sqrt() - 3.;
+        [0.000100950558, 0.00134934322, -0.00367342844, 0.00573950773, -0.0076224613, 0.00943887047, 1.00167406, 2.83297682]
+            .iter().fold(-0.000200214257, |p, &c| c + p * w)
+    };
+    p * x
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::test_util::uniform_hemisphere;
+    use crate::macros::*;
+    use rand::random;
+
+    fn distributions() -> Vec<Microfacet> {
+        let mut all = Vec::new();
+        for &ty in &[MicrofacetType::Beckmann, MicrofacetType::GGX] {
+            all.push(Microfacet::isotropic(ty, 0.3));
+            all.push(Microfacet::isotropic(ty, 0.8));
+            all.push(Microfacet::new(ty, 0.15, 0.5));
+        }
+        all
+    }
+
+    #[test]
+    fn projected_area() {
+        // the microfacets add up to the macro surface
+        for m in distributions() {
+            let n = 200_000;
+            let sum: Float = (0..n).map(|_| {
+                let wh = uniform_hemisphere();
+                m.d(wh) * wh.z
+            }).sum();
+            assert_lt!((sum * 2. * Float::PI() / n as Float - 1.).abs(), 0.05, "{:?}", m);
+        }
+    }
+
+    #[test]
+    fn erf_roundtrip() {
+        for &x in &[-0.9, -0.3, 0., 0.2, 0.7, 0.99] {
+            assert_lt!((erf(erf_inv(x)) - x).abs(), 1e-4);
+        }
+        assert_lt!((erf(1.) - 0.842_700_8).abs(), 1e-6);
+    }
+
+    #[test]
+    fn visible_normals() {
+        for m in distributions() {
+            for &theta in &[0., 0.5, 1.2] {
+                let wo = vec3(Float::sin(theta) * 0.6, Float::sin(theta) * 0.8, Float::cos(theta));
+                // the pdf integrates to one, and gives the mean normal
+                let n = 200_000;
+                let (mut sum, mut mean) = (0., Vector3f::zero());
+                for _ in 0..n {
+                    let wh = uniform_hemisphere();
+                    let pdf = m.pdf(wo, wh);
+                    sum += pdf;
+                    mean += wh * pdf;
+                }
+                let scale = 2. * Float::PI() / n as Float;
+                assert_lt!((sum * scale - 1.).abs(), 0.05, "{:?} {}", m, theta);
+                // the same mean from the samples
+                let n = 20_000;
+                let mut sampled = Vector3f::zero();
+                for _ in 0..n {
+                    let wh = m.sample_wh(wo, pt2(random(), random()));
+                    assert_approx!(wh.magnitude(), 1.);
+                    assert_ge!(wh.z, 0.);
+                    sampled += wh;
+                }
+                assert_lt!((mean * scale - sampled / n as Float).magnitude(), 0.03, "{:?} {}", m, theta);
+            }
+        }
+    }
+
+    #[test]
+    fn anisotropic() {
+        let m = Microfacet::from_roughness(MicrofacetType::GGX, 0.2, 0.7);
+        assert_approx!(m.alpha_x(), 0.04);
+        let (mut sx, mut sy) = (0., 0.);
+        for _ in 0..10000 {
+            let wh = m.sample_wh(vec3(0., 0., 1.), pt2(random(), random()));
+            sx += wh.x.abs();
+            sy += wh.y.abs();
+        }
+        // wider spread across the smooth direction
+        assert_gt!(sy, 4. * sx);
+        assert!(Microfacet::isotropic(MicrofacetType::Beckmann, 1e-4).is_smooth());
+        assert!(!m.is_smooth());
+    }
+}
@@ -1,12 +1,33 @@
 use super::*;
 
 pub mod simple;
+pub mod hair;
+pub mod microfacet;
+pub mod conductor;
+pub mod rough_dielectric;
+mod dynamic;
 
 pub use simple::Simple;
+pub use hair::Hair;
+pub use microfacet::{Microfacet, MicrofacetType};
+pub use conductor::Conductor;
+pub use rough_dielectric::RoughDielectric;
+pub use dynamic::DynamicBSDF;
 
 pub trait BSDF: Debug + Clone + Send + Sync + 'static {
     /// Importance sample the BSDF, return the outgoing direction, weight and pdf
     fn sample(&self, its: &GeometryIntersection, samp: Point2f) -> SampleRecord;
+
+    /// The BSDF times `|cos|` of `wo`, for light between `its.wi` and `wo`, the same as the weight `sample` gives
+    ///
+    /// Zero for delta lobes, which a given direction hits with probability zero
+    fn eval(&self, its: &GeometryIntersection, wo: Vector3f) -> Spectrum;
+
+    /// Solid angle density of `sample` picking `wo` for `its.wi`, zero for delta lobes
+    fn pdf(&self, its: &GeometryIntersection, wo: Vector3f) -> Float;
+
+    /// Only delta lobes, e.g. a mirror? Then `eval` and `pdf` are always zero, and light sampling is useless
+    fn is_delta(&self) -> bool { false }
 }
 
 #[derive(Debug, Clone)]
@@ -18,3 +39,89 @@ pub struct SampleRecord {
     /// pdf in this sample
     pub pdf: Float,
 }
+
+/// Unpolarized Fresnel reflectance of a dielectric of relative index `eta`
+///
+/// Seen from the outside for a positive `cos_theta_i`, from the inside otherwise
+fn fr_dielectric(cos_theta_i: Float, eta: Float) -> Float {
+    let cos_theta_i = num_traits::clamp(cos_theta_i, -1., 1.);
+    let (eta_i, eta_t, cos_theta_i) = if cos_theta_i > 0. { (1., eta, cos_theta_i) } else { (eta, 1., -cos_theta_i) };
+    let sin_theta_t = eta_i / eta_t * (1. - cos_theta_i * cos_theta_i).max(0.).sqrt();
+    if sin_theta_t >= 1. { return 1.; }
+    let cos_theta_t = (1. - sin_theta_t * sin_theta_t).max(0.).sqrt();
+    let r_parl = (eta_t * cos_theta_i - eta_i * cos_theta_t) / (eta_t * cos_theta_i + eta_i * cos_theta_t);
+    let r_perp = (eta_i * cos_theta_i - eta_t * cos_theta_t) / (eta_i * cos_theta_i + eta_t * cos_theta_t);
+    0.5 * (r_parl * r_parl + r_perp * r_perp)
+}
+
+/// Unpolarized Fresnel reflectance of a conductor of complex index `eta + i k`, from the outside
+fn fr_conductor(cos_theta_i: Float, eta: Float, k: Float) -> Float {
+    let cos2 = num_traits::clamp(cos_theta_i * cos_theta_i, 0., 1.);
+    let sin2 = 1. - cos2;
+    let t0 = eta * eta - k * k - sin2;
+    let a2_plus_b2 = (t0 * t0 + 4. * eta * eta * k * k).sqrt();
+    let a = (0.5 * (a2_plus_b2 + t0)).max(0.).sqrt();
+    let t1 = a2_plus_b2 + cos2;
+    let t2 = 2. * cos_theta_i.abs() * a;
+    let r_perp = (t1 - t2) / (t1 + t2);
+    let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+    let t4 = t2 * sin2;
+    let r_parl = r_perp * (t3 - t4) / (t3 + t4);
+    0.5 * (r_parl + r_perp)
+}
+
+/// Keep every other bit
+#[inline]
+fn compact_1_by_1(mut x: u32) -> u32 {
+    x &= 0x5555_5555;
+    x = (x ^ (x >> 1)) & 0x3333_3333;
+    x = (x ^ (x >> 2)) & 0x0f0f_0f0f;
+    x = (x ^ (x >> 4)) & 0x00ff_00ff;
+    x = (x ^ (x >> 8)) & 0x0000_ffff;
+    x
+}
+
+/// Two samples out of the interleaved bits of one
+fn demux(f: Float) -> Point2f {
+    let v = (f as f64 * (1u64 << 32) as f64) as u64;
+    pt2(compact_1_by_1(v as u32) as Float / 65536., compact_1_by_1((v >> 1) as u32) as Float / 65536.)
+}
+
+/// Fixtures shared by the tests of the BSDFs
+#[cfg(test)]
+mod test_util {
+    use super::*;
+    use rand::random;
+
+    /// A surface at the origin facing `+z`, with `dpdu` along `x`, seen from `wi` on `side`
+    pub fn its(wi: Vector3f, side: Side) -> GeometryIntersection {
+        GeometryIntersection {
+            pos: Point3::origin(),
+            normal: vec3(0., 0., 1.),
+            wi,
+            t: 1.,
+            side,
+            uv: pt2(0.5, 0.5),
+            dpdu: vec3(1., 0., 0.),
+            dpdv: vec3(0., 1., 0.),
+            p_error: Vector3f::zero(),
+        }
+    }
+
+    /// Map `u` in `[0, 1)^2` uniformly onto the unit sphere, `u.x` picks the height
+    pub fn sphere_direction(u: Point2f) -> Vector3f {
+        let z = 1. - 2. * u.x;
+        let phi = 2. * Float::PI() * u.y;
+        let r = (1. - z * z).max(0.).sqrt();
+        vec3(r * phi.cos(), r * phi.sin(), z)
+    }
+
+    /// A random direction, of pdf `1 / 4pi`
+    pub fn uniform_sphere() -> Vector3f { sphere_direction(pt2(random(), random())) }
+
+    /// A random direction around `+z`, of pdf `1 / 2pi`
+    pub fn uniform_hemisphere() -> Vector3f {
+        let w = uniform_sphere();
+        vec3(w.x, w.y, w.z.abs())
+    }
+}
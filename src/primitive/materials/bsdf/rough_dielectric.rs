@@ -0,0 +1,193 @@
+use super::*;
+
+#[derive(Debug, Clone)]
+/// Frosted glass: microfacets reflecting and refracting like smooth glass, Walter et al. 2007
+///
+/// A smooth `distribution` makes it a smooth dielectric with exact Fresnel, sampled as delta lobes.
+/// The transmitted radiance is scaled by the squared index ratio, as it is compressed into the denser side.
+pub struct RoughDielectric {
+    /// Refraction index of the inside over the one of the outside, e.g. 1.5 for glass in air, 1.33 / 1.5 for water in glass
+    pub eta: Float,
+    pub distribution: Microfacet,
+}
+
+impl RoughDielectric {
+    pub fn new(eta: Float, distribution: Microfacet) -> Self { Self { eta, distribution } }
+
+    /// The index ratio across the surface, beyond it over the side of `its.wi`
+    fn eta(&self, its: &GeometryIntersection) -> Float {
+        match its.side {
+            Side::Outside => self.eta,
+            Side::Inside => 1. / self.eta,
+        }
+    }
+
+    /// Local directions, `wi` on the side of the normal, return `f x |cos theta_o|` and the pdf of sampling `wo`
+    fn eval_pdf(&self, wi: Vector3f, wo: Vector3f, eta: Float) -> (Spectrum, Float) {
+        let (cos_i, cos_o) = (wi.z, wo.z);
+        if cos_i <= 0. || cos_o == 0. { return (Spectrum::black(), 0.); }
+        let reflect = cos_o > 0.;
+        let etap = if reflect { 1. } else { eta };
+        // the generalized half vector, facing the normal
+        let wh = wi + wo * etap;
+        if wh.magnitude2() == 0. { return (Spectrum::black(), 0.); }
+        let wh = if wh.z < 0. { -wh.normalize() } else { wh.normalize() };
+        // neither side may see the microfacet from behind
+        if dot(wh, wi) <= 0. || dot(wh, wo) * cos_o <= 0. { return (Spectrum::black(), 0.); }
+        let r = fr_dielectric(dot(wi, wh), eta);
+        let dg = self.distribution.d(wh) * self.distribution.g(wi, wo);
+        let pdf_wh = self.distribution.pdf(wi, wh);
+        if reflect {
+            let f = dg * r / (4. * cos_i);
+            (Spectrum::uniform(f), pdf_wh * r / (4. * dot(wi, wh)))
+        } else {
+            // the jacobian of the refracted direction to the half vector
+            let denom = dot(wo, wh) + dot(wi, wh) / etap;
+            let dwh_dwo = dot(wo, wh).abs() / (denom * denom);
+            let f = dg * (1. - r) * dot(wi, wh) * dwh_dwo / (cos_i * etap * etap);
+            (Spectrum::uniform(f), pdf_wh * dwh_dwo * (1. - r))
+        }
+    }
+}
+
+impl BSDF for RoughDielectric {
+    /// Sample a visible microfacet normal, then reflect or refract by its Fresnel reflectance
+    fn sample(&self, its: &GeometryIntersection, samp: Point2f) -> SampleRecord {
+        let eta = self.eta(its);
+        let frame = its.shading_frame();
+        let wi = frame.transpose() * its.wi;
+        let u = demux(samp.x);
+        let wh = if self.distribution.is_smooth() {
+            vec3(0., 0., 1.)
+        } else {
+            self.distribution.sample_wh(wi, pt2(u.x, samp.y))
+        };
+        let r = fr_dielectric(dot(wi, wh), eta);
+        let reflect = u.y < r;
+        let wo = if reflect { Some(2. * dot(wi, wh) * wh - wi) } else { refract(wi, wh, eta) };
+        // off the steep microfacets, it may end up on the wrong side
+        let wo = match wo {
+            Some(wo) if (wo.z > 0.) == reflect => wo,
+            _ => return SampleRecord { wo: -its.wi, weight: Spectrum::black(), pdf: 0. },
+        };
+        if self.distribution.is_smooth() {
+            let (weight, pdf) = if reflect { (r, r) } else { ((1. - r) / (eta * eta), 1. - r) };
+            return SampleRecord { wo: frame * wo, weight: Spectrum::uniform(weight), pdf };
+        }
+        let (weight, pdf) = self.eval_pdf(wi, wo, eta);
+        SampleRecord { wo: frame * wo, weight, pdf }
+    }
+
+    fn eval(&self, its: &GeometryIntersection, wo: Vector3f) -> Spectrum {
+        if self.distribution.is_smooth() { return Spectrum::black(); }
+        let frame = its.shading_frame().transpose();
+        self.eval_pdf(frame * its.wi, frame * wo, self.eta(its)).0
+    }
+
+    fn pdf(&self, its: &GeometryIntersection, wo: Vector3f) -> Float {
+        if self.distribution.is_smooth() { return 0.; }
+        let frame = its.shading_frame().transpose();
+        self.eval_pdf(frame * its.wi, frame * wo, self.eta(its)).1
+    }
+
+    fn is_delta(&self) -> bool { self.distribution.is_smooth() }
+}
+
+/// Refract `wi` through the surface of normal `n` on its side and relative index `eta`, none if totally reflected
+fn refract(wi: Vector3f, n: Vector3f, eta: Float) -> Option<Vector3f> {
+    let cos_i = dot(wi, n);
+    let sin2_t = (1. - cos_i * cos_i).max(0.) / (eta * eta);
+    if sin2_t >= 1. { return None; }
+    let cos_t = (1. - sin2_t).sqrt();
+    Some(-wi / eta + n * (cos_i / eta - cos_t))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::test_util::*;
+    use crate::macros::*;
+    use rand::random;
+
+    #[test]
+    fn sample_matches_eval() {
+        for &ty in &[MicrofacetType::Beckmann, MicrofacetType::GGX] {
+            for &side in &[Side::Outside, Side::Inside] {
+                let glass = RoughDielectric::new(1.5, Microfacet::isotropic(ty, 0.3));
+                let its = its(vec3(0.5, 0.2, 1.).normalize(), side);
+                let eta = glass.eta(&its);
+                let n = 20_000;
+                let (mut valid, mut energy) = (0, 0.);
+                for _ in 0..n {
+                    let rec = glass.sample(&its, pt2(random(), random()));
+                    if rec.pdf == 0. { continue; }
+                    valid += 1;
+                    assert_approx!(rec.wo.magnitude(), 1.);
+                    assert_approx!(glass.eval(&its, rec.wo).r, rec.weight.r);
+                    assert_lt!((glass.pdf(&its, rec.wo) / rec.pdf - 1.).abs(), 1e-3);
+                    // undo the radiance scaling to count the energy
+                    let scale = if rec.wo.z < 0. { eta * eta } else { 1. };
+                    energy += rec.weight.r / rec.pdf * scale;
+                }
+                // the pdf covers the directions that can be sampled, summed over a jittered grid of the sphere
+                let k = 400;
+                let mut pdf = 0.;
+                for i in 0..k * k {
+                    let u = pt2((i / k) as Float + random::<Float>(), (i % k) as Float + random::<Float>()) / k as Float;
+                    pdf += glass.pdf(&its, sphere_direction(u));
+                }
+                let pdf = pdf * 4. * Float::PI() / (k * k) as Float;
+                assert_lt!((pdf - valid as Float / n as Float).abs(), 0.01, "{:?} {:?}", ty, side);
+                let energy = energy / n as Float;
+                assert_le!(energy, 1.01);
+                assert_ge!(energy, 0.85, "{:?} {:?}", ty, side);
+            }
+        }
+    }
+
+    #[test]
+    fn nearly_smooth() {
+        // sharp lobes around the mirror and snell directions
+        let glass = RoughDielectric::new(1.5, Microfacet::isotropic(MicrofacetType::GGX, 0.01));
+        assert!(!glass.is_delta());
+        let its = its(vec3(1., 0., 1.).normalize(), Side::Outside);
+        let snell = refract(its.wi, its.normal, 1.5).unwrap();
+        assert_approx!(snell.x, -(0.5 as Float).sqrt() / 1.5);
+        let (mut reflected, mut transmitted, mut sharp) = (0, 0, 0);
+        for _ in 0..1000 {
+            let rec = glass.sample(&its, pt2(random(), random()));
+            let ideal = if rec.wo.z > 0. {
+                reflected += 1;
+                vec3(-1., 0., 1.).normalize()
+            } else {
+                transmitted += 1;
+                snell
+            };
+            if dot(rec.wo, ideal) > 0.999 { sharp += 1; }
+        }
+        // but for the long tail of GGX
+        assert_gt!(sharp, 950);
+        // about 5% reflected at 45 degrees
+        assert_lt!(reflected, 150);
+        assert_gt!(transmitted, 850);
+    }
+
+    #[test]
+    fn smooth() {
+        let glass = RoughDielectric::new(1.5, Microfacet::isotropic(MicrofacetType::GGX, 0.));
+        assert!(glass.is_delta());
+        // beyond the critical angle from inside
+        let inside = its(vec3(1., 0., 0.5).normalize(), Side::Inside);
+        for _ in 0..100 {
+            let rec = glass.sample(&inside, pt2(random(), random()));
+            assert_approx!((rec.wo - vec3(-1., 0., 0.5).normalize()).magnitude(), 0.);
+            assert_eq!(rec.weight.r, rec.pdf);
+        }
+        assert_eq!(glass.eval(&inside, vec3(-1., 0., 0.5).normalize()), Spectrum::black());
+        // head-on from outside, 4% reflected
+        let outside = its(vec3(0., 0., 1.), Side::Outside);
+        let reflected = (0..10000).filter(|_| glass.sample(&outside, pt2(random(), random())).wo.z > 0.).count();
+        assert_gt!(reflected, 300);
+        assert_lt!(reflected, 500);
+    }
+}
@@ -23,6 +23,30 @@ impl BSDF for Simple {
             Simple::Dielectric(d) => d.sample(its, samp),
         }
     }
+
+    fn eval(&self, its: &GeometryIntersection, wo: Vector3f) -> Spectrum {
+        match self {
+            Simple::Diffuse(d) => d.eval(its, wo),
+            Simple::Specular(s) => s.eval(its, wo),
+            Simple::Dielectric(d) => d.eval(its, wo),
+        }
+    }
+
+    fn pdf(&self, its: &GeometryIntersection, wo: Vector3f) -> Float {
+        match self {
+            Simple::Diffuse(d) => d.pdf(its, wo),
+            Simple::Specular(s) => s.pdf(its, wo),
+            Simple::Dielectric(d) => d.pdf(its, wo),
+        }
+    }
+
+    fn is_delta(&self) -> bool {
+        match self {
+            Simple::Diffuse(d) => d.is_delta(),
+            Simple::Specular(s) => s.is_delta(),
+            Simple::Dielectric(d) => d.is_delta(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Default)]
@@ -48,12 +72,23 @@ impl BSDF for Diffuse {
     fn sample(&self, its: &GeometryIntersection, samp: Point2f) -> SampleRecord {
         let samp = cosine_on_hemisphere(samp);
         let basis = onb(its.normal);
+        // cosine-ly sampled, the pdf cancels the weight
+        let pdf = samp.z * Float::FRAC_1_PI();
         SampleRecord {
             wo: basis * samp.to_vec(),
-            weight: Spectrum::white(), // assume no attenuation
-            pdf: 1., // since we just cosine-ly sampled the diffuse surface
+            weight: Spectrum::uniform(pdf), // assume no attenuation
+            pdf,
         }
     }
+
+    /// Lambertian, `cos / pi` on the side of the normal
+    fn eval(&self, its: &GeometryIntersection, wo: Vector3f) -> Spectrum {
+        Spectrum::uniform(self.pdf(its, wo))
+    }
+
+    fn pdf(&self, its: &GeometryIntersection, wo: Vector3f) -> Float {
+        dot(wo, its.normal).max(0.) * Float::FRAC_1_PI()
+    }
 }
 
 impl BSDF for Specular {
@@ -65,6 +100,12 @@ impl BSDF for Specular {
             pdf: 1.,
         }
     }
+
+    fn eval(&self, _its: &GeometryIntersection, _wo: Vector3f) -> Spectrum { Spectrum::black() }
+
+    fn pdf(&self, _its: &GeometryIntersection, _wo: Vector3f) -> Float { 0. }
+
+    fn is_delta(&self) -> bool { true }
 }
 
 #[allow(non_snake_case)]
@@ -116,11 +157,18 @@ impl BSDF for Dielectric {
             }
         }
     }
+
+    fn eval(&self, _its: &GeometryIntersection, _wo: Vector3f) -> Spectrum { Spectrum::black() }
+
+    fn pdf(&self, _its: &GeometryIntersection, _wo: Vector3f) -> Float { 0. }
+
+    fn is_delta(&self) -> bool { true }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use super::super::test_util::*;
     use rand::random;
     use crate::sampler::Independent;
     use crate::Sampler;
@@ -129,21 +177,41 @@ mod test {
     #[test]
     fn diffuse_sample() {
         let mut sampler = Independent;
-        let its = GeometryIntersection {
-            pos: pt3(1., 1., 1.),
-            normal: vec3(1., 0., 0.),
-            wi: vec3(random(), random(), random()),
-            t: random(),
-            side: Side::Outside,
-            uv: pt2(random(), random()),
-        };
+        let its = its(vec3(random(), random(), random()), Side::Outside);
         let diffuse = Diffuse;
         for _ in 0..10000 {
             let rc = diffuse.sample(&its, sampler.next2d());
-            assert_eq!(rc.weight, Spectrum::uniform(1.));
-            assert_eq!(rc.pdf, 1.);
+            assert_eq!(rc.weight, Spectrum::uniform(rc.pdf));
+            assert_approx!(rc.pdf, dot(rc.wo, its.normal) / Float::PI());
             assert_approx!(rc.wo.magnitude(), 1.);
             assert_ge!(dot(rc.wo, its.normal), 0.);
+            assert_eq!(diffuse.eval(&its, rc.wo), rc.weight);
+            assert_approx!(diffuse.pdf(&its, rc.wo), rc.pdf);
+        }
+        assert!(!diffuse.is_delta());
+    }
+
+    #[test]
+    fn diffuse_pdf_integrates_to_one() {
+        let its = its(vec3(0., 0., 1.), Side::Outside);
+        // uniformly over the sphere, of pdf 1 / 4pi
+        let n = 100000;
+        let sum: Float = (0..n).map(|_| Diffuse.pdf(&its, uniform_sphere())).sum();
+        assert_lt!((sum / n as Float * 4. * Float::PI() - 1.).abs(), 0.02);
+        assert_eq!(Diffuse.pdf(&its, vec3(0., 0., -1.)), 0.);
+    }
+
+    #[test]
+    fn delta_lobes() {
+        let its = its(vec3(1., 0., 1.).normalize(), Side::Outside);
+        let mirror = vec3(-1., 0., 1.).normalize();
+        assert_approx!((Specular.sample(&its, pt2(0.5, 0.5)).wo - mirror).magnitude(), 0.);
+        for bsdf in &[Simple::from(Specular), Dielectric::default().into()] {
+            assert!(bsdf.is_delta());
+            // even toward the reflected direction
+            assert_eq!(bsdf.eval(&its, mirror), Spectrum::black());
+            assert_eq!(bsdf.pdf(&its, mirror), 0.);
         }
+        assert!(!Simple::default().is_delta());
     }
 }
@@ -0,0 +1,24 @@
+use super::*;
+use derive_more::*;
+
+#[derive(Debug, Clone, From)]
+/// Any of the textures, so that different kinds can share a scene
+pub enum DynamicTexture {
+    Uniform(Uniform),
+    Image(Image),
+}
+
+impl Default for DynamicTexture {
+    fn default() -> Self {
+        Uniform::default().into()
+    }
+}
+
+impl Texture for DynamicTexture {
+    fn at(&self, uv: Point2f) -> &Spectrum {
+        match self {
+            DynamicTexture::Uniform(u) => u.at(uv),
+            DynamicTexture::Image(i) => i.at(uv),
+        }
+    }
+}
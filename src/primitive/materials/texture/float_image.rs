@@ -0,0 +1,86 @@
+use super::*;
+use image::{DynamicImage, GenericImageView};
+
+#[derive(Debug, Clone)]
+/// Grid of values over the unit square, interpolated bilinearly between the texel centers and repeated beyond
+///
+/// The first row is at `v = 0`
+pub struct FloatImage {
+    width: usize,
+    height: usize,
+    values: Vec<Float>,
+}
+
+impl FloatImage {
+    pub fn new(width: usize, height: usize, values: Vec<Float>) -> Self {
+        assert!(width > 0 && height > 0, "Empty image!");
+        assert_eq!(values.len(), width * height, "Mismatched image size!");
+        Self { width, height, values }
+    }
+
+    /// The luma of `img` in `[0, 1]`, the top row of the image at `v = 1`
+    pub fn from_image(img: &DynamicImage) -> Self {
+        let (width, height) = (img.width() as usize, img.height() as usize);
+        let luma: Vec<Float> = match img {
+            DynamicImage::ImageLuma16(buf) => buf.pixels().map(|p| p[0] as Float / 65535.).collect(),
+            img => img.to_luma().pixels().map(|p| p[0] as Float / 255.).collect(),
+        };
+        // flip the rows, image rows go downward
+        let values = (0..width * height).map(|k| luma[(height - 1 - k / width) * width + k % width]).collect();
+        Self::new(width, height, values)
+    }
+
+    #[inline]
+    pub fn width(&self) -> usize { self.width }
+    #[inline]
+    pub fn height(&self) -> usize { self.height }
+    /// Row by row, the first row at `v = 0`
+    #[inline]
+    pub fn into_values(self) -> Vec<Float> { self.values }
+
+    #[inline]
+    fn texel(&self, x: i64, y: i64) -> Float {
+        let x = x.rem_euclid(self.width as i64) as usize;
+        let y = y.rem_euclid(self.height as i64) as usize;
+        self.values[y * self.width + x]
+    }
+}
+
+impl FloatTexture for FloatImage {
+    fn value(&self, uv: Point2f) -> Float {
+        let x = uv.x * self.width as Float - 0.5;
+        let y = uv.y * self.height as Float - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+        lerp(
+            lerp(self.texel(x0, y0), self.texel(x0 + 1, y0), fx),
+            lerp(self.texel(x0, y0 + 1), self.texel(x0 + 1, y0 + 1), fx),
+            fy,
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn bilinear() {
+        let img = FloatImage::new(2, 2, vec![0., 1., 2., 3.]);
+        // texel centers
+        assert_approx!(img.value(pt2(0.25, 0.25)), 0.);
+        assert_approx!(img.value(pt2(0.75, 0.75)), 3.);
+        // halfway
+        assert_approx!(img.value(pt2(0.5, 0.25)), 0.5);
+        assert_approx!(img.value(pt2(0.5, 0.5)), 1.5);
+        // repeated, the edge blends with the other side
+        assert_approx!(img.value(pt2(1.25, -0.75)), 0.);
+        assert_approx!(img.value(pt2(0., 0.25)), 0.5);
+
+        let gray = image::GrayImage::from_fn(1, 2, |_, y| image::Luma([if y == 0 { 255 } else { 0 }]));
+        let img = FloatImage::from_image(&DynamicImage::ImageLuma8(gray));
+        assert_eq!(img.value(pt2(0.5, 0.75)), 1.);
+        assert_eq!(img.value(pt2(0.5, 0.25)), 0.);
+    }
+}
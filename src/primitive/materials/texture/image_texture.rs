@@ -0,0 +1,65 @@
+use super::*;
+use image::{DynamicImage, GenericImageView};
+
+#[derive(Debug, Clone)]
+/// Colors over the unit square, looked up at the nearest texel and repeated beyond
+///
+/// The first row is at `v = 0`
+pub struct Image {
+    width: usize,
+    height: usize,
+    texels: Vec<Spectrum>,
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize, texels: Vec<Spectrum>) -> Self {
+        assert!(width > 0 && height > 0, "Empty image!");
+        assert_eq!(texels.len(), width * height, "Mismatched image size!");
+        Self { width, height, texels }
+    }
+
+    /// The colors of `img` made linear, the top row of the image at `v = 1`
+    pub fn from_image(img: &DynamicImage) -> Self {
+        let (width, height) = (img.width() as usize, img.height() as usize);
+        let rgb = img.to_rgb();
+        // undo the display gamma, which the film applies again on output
+        let linear = |c: u8| (c as Float / 255.).powf(2.2);
+        let texels = (0..width * height).map(|k| {
+            let p = rgb.get_pixel((k % width) as u32, (height - 1 - k / width) as u32);
+            Spectrum::new(linear(p[0]), linear(p[1]), linear(p[2]))
+        }).collect();
+        Self::new(width, height, texels)
+    }
+
+    #[inline]
+    pub fn width(&self) -> usize { self.width }
+    #[inline]
+    pub fn height(&self) -> usize { self.height }
+}
+
+impl Texture for Image {
+    fn at(&self, uv: Point2f) -> &Spectrum {
+        let x = ((uv.x * self.width as Float).floor() as i64).rem_euclid(self.width as i64) as usize;
+        let y = ((uv.y * self.height as Float).floor() as i64).rem_euclid(self.height as i64) as usize;
+        &self.texels[y * self.width + x]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn lookup() {
+        let red = image::RgbImage::from_fn(2, 1, |x, _| image::Rgb([255, if x == 0 { 0 } else { 255 }, 0]));
+        let img = Image::from_image(&DynamicImage::ImageRgb8(red));
+        assert_eq!(img.at(pt2(0.2, 0.5)), &Spectrum::new(1., 0., 0.));
+        assert_eq!(img.at(pt2(0.7, 0.5)), &Spectrum::new(1., 1., 0.));
+        // repeated
+        assert_eq!(img.at(pt2(-0.3, 7.5)), &Spectrum::new(1., 1., 0.));
+        // the top row at v = 1
+        let img = Image::from_image(&DynamicImage::ImageLuma8(image::GrayImage::from_fn(1, 2, |_, y| image::Luma([y as u8 * 255]))));
+        assert_eq!(img.at(pt2(0.5, 0.9)).r, 0.);
+        assert_eq!(img.at(pt2(0.5, 0.1)).r, 1.);
+    }
+}
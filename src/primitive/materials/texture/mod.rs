@@ -1,9 +1,24 @@
 use super::*;
 
 mod uniform;
+mod image_texture;
+mod float_image;
+mod dynamic;
 
 pub use uniform::Uniform;
+pub use image_texture::Image;
+pub use float_image::FloatImage;
+pub use dynamic::DynamicTexture;
 
 pub trait Texture: Debug + Send + Sync + 'static {
     fn at(&self, uv: Point2f) -> &Spectrum;
 }
+
+/// Real valued texture, e.g. the heights for `Mesh::displace`
+pub trait FloatTexture {
+    fn value(&self, uv: Point2f) -> Float;
+}
+
+impl<F: Fn(Point2f) -> Float> FloatTexture for F {
+    fn value(&self, uv: Point2f) -> Float { self(uv) }
+}
@@ -3,7 +3,7 @@ use std::fmt::Debug;
 use std::sync::{Mutex, Arc};
 use lazy_static::*;
 
-pub use geometries::{Sphere, Geometry};
+pub use geometries::{Sphere, Triangle, Mesh, Plane, Rectangle, Disk, Cylinder, Cone, Paraboloid, Torus, Sdf, SdfGeometry, Heightfield, Csg, CsgOp, Curve, CurveType, Curves, DynamicGeometry, Geometry, Intersect};
 pub use materials::Material;
 pub use materials::{bsdf::{self, BSDF}, texture::{self, Texture}};
 
@@ -16,14 +16,16 @@ pub struct Primitive<G: Geometry, B: BSDF, T: Texture> {
     pub label: String,
     /// Geometric instance: Sphere, Triangle, ..
     ///
-    /// Owned by the primitive itself
+    /// Owned by the primitive itself, or shared by instances as `Arc<G>`
     pub geometry: G,
     /// Physical material: bsdf + texture
     ///
     /// Material is sharable across multiple threads
     pub material: Arc<Material<B, T>>,
-    world_to_local: Matrix4f,
-    local_to_world: Matrix4f,
+    /// local_to_world, with world_to_local cached
+    transform: Transform,
+    /// Overrides the fixed transform above when moving
+    animation: Option<AnimatedTransform>,
 }
 
 lazy_static! {
@@ -46,35 +48,72 @@ impl<G, B, T> Primitive<G, B, T> where G: Geometry, B: BSDF, T: Texture {
             label,
             geometry,
             material,
-            world_to_local: transform.inverse_transform()
-                .unwrap_or_else(|| panic!(format!("Singular transform {:?}", transform))),
-            local_to_world: transform,
+            transform: Transform::new(transform),
+            animation: None,
         }
     }
+    /// Intersect at `ray_world.time`
     pub fn intersect(&self, ray_world: &Ray) -> Option<GeometryIntersection> {
-        let ray = self.world_to_local.transform(ray_world);
+        match &self.animation {
+            None => self.intersect_with(ray_world, &self.transform),
+            Some(animation) => self.intersect_with(ray_world, &animation.transform_at(ray_world.time)),
+        }
+    }
+    fn intersect_with(&self, ray_world: &Ray, local_to_world: &Transform) -> Option<GeometryIntersection> {
+        let ray = local_to_world.inverse().transform(ray_world);
         self.geometry.intersect(&ray).map(|its| {
             debug_assert_approx!(its.normal.magnitude(), 1.0);
-            self.local_to_world.transform(&its)
+            local_to_world.transform(&its)
         })
     }
+    /// Is the world ray blocked by the primitive?
+    pub fn intersect_p(&self, ray_world: &Ray) -> bool {
+        match &self.animation {
+            None => self.geometry.intersect_p(&self.transform.inverse().transform(ray_world)),
+            Some(animation) => self.geometry.intersect_p(&animation.transform_at(ray_world.time).inverse().transform(ray_world)),
+        }
+    }
     /// Set local_to_world transform, auto-set the counterpart
+    ///
+    /// The primitive stops moving if it was animated
     pub fn set_transform(&mut self, transform: Matrix4f) {
-        self.world_to_local = transform.inverse_transform()
-            .unwrap_or_else(|| panic!(format!("Singular transform {:?}", transform)));
-        self.local_to_world = transform;
+        self.transform = Transform::new(transform);
+        self.animation = None;
+    }
+    /// Move the primitive with a keyframed local_to_world transform
+    ///
+    /// The fixed transforms are set to the one at the start
+    pub fn set_animation(&mut self, animation: AnimatedTransform) {
+        self.set_transform(animation.at(animation.time_range().0));
+        self.animation = Some(animation).filter(|a| a.is_animated());
+    }
+    #[inline]
+    pub fn animation(&self) -> Option<&AnimatedTransform> {
+        self.animation.as_ref()
+    }
+    /// The fixed local_to_world transform
+    #[inline]
+    pub fn transform(&self) -> &Transform {
+        &self.transform
     }
     #[inline]
     pub fn world_to_local(&self) -> &Matrix4f {
-        &self.world_to_local
+        self.transform.inverse_matrix()
     }
     #[inline]
     pub fn local_to_world(&self) -> &Matrix4f {
-        &self.local_to_world
+        self.transform.matrix()
+    }
+    /// World space bounding box, over the whole motion if animated
+    pub fn world_bounds(&self) -> Bounds3f {
+        match &self.animation {
+            None => self.transform.transform(&self.geometry.bounds()),
+            Some(animation) => animation.motion_bounds(&self.geometry.bounds()),
+        }
     }
     /// Get world center
     #[inline]
     pub fn center(&self) -> Point3f {
-        self.local_to_world.transform_point(Point3::origin())
+        self.transform.transform_point(Point3::origin())
     }
 }
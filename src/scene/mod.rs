@@ -1,17 +1,64 @@
 use crate::core::*;
 use crate::primitive::*;
+use crate::accel::Bvh;
 use std::ops::{Deref, DerefMut};
 
 #[derive(Clone, Debug)]
 pub struct Scene<G: Geometry, B: BSDF, T: Texture> {
     primitives: Vec<Primitive<G, B, T>>,
+    /// Over the world bounds of `primitives`, dropped whenever they are mutably accessed
+    bvh: Option<Bvh>,
 }
 
+/// A scene of any geometries, BSDFs and textures, e.g. loaded at runtime
+///
+/// Dispatches on enums, a `Scene` of concrete types is faster when one kind suffices
+pub type DynamicScene = Scene<DynamicGeometry, bsdf::DynamicBSDF, texture::DynamicTexture>;
+
 impl<G, B, T> Scene<G, B, T> where G: Geometry, B: BSDF, T: Texture {
     #[inline]
-    pub fn new() -> Self { Self { primitives: Vec::new() } }
+    pub fn new() -> Self { Self { primitives: Vec::new(), bvh: None } }
+
+    /// Build the acceleration structure over the current primitives
+    ///
+    /// Call it after the scene is set up, otherwise `nearest_hit` falls back to the brute-force search
+    pub fn build_bvh(&mut self) {
+        let bounds: Vec<_> = self.primitives.iter().map(|prim| prim.world_bounds()).collect();
+        self.bvh = Some(Bvh::build(&bounds));
+    }
+
+    #[inline]
+    pub fn has_bvh(&self) -> bool { self.bvh.is_some() }
+
+    /// World space bounding box of all primitives
+    pub fn bounds(&self) -> Bounds3f {
+        self.primitives.iter().fold(Bounds3f::empty(), |b, prim| b.union(&prim.world_bounds()))
+    }
+
+    pub fn nearest_hit(&self, ray_world: &Ray) -> Option<Intersection<'_, G, B, T>> {
+        match &self.bvh {
+            None => self.nearest_hit_brute_force(ray_world),
+            Some(bvh) => bvh.nearest(ray_world, |i, ray| {
+                let prim = &self.primitives[i];
+                prim.intersect(ray).map(|its| (its.t, Intersection(its, prim)))
+            }),
+        }
+    }
 
-    pub fn nearest_hit(&self, ray_world: &Ray) -> Option<Intersection<G, B, T>> {
+    /// Is anything hit by the ray within `(ray.t_min, t_max)`? Stops at the first hit found
+    ///
+    /// Use it for visibility tests, e.g. shadow rays toward a light at distance `t_max`
+    pub fn occluded(&self, ray_world: &Ray, t_max: Float) -> bool {
+        let ray = Ray::new_with_range(ray_world.org, ray_world.dir, ray_world.t_min, t_max.min(ray_world.t_max))
+            .with_time(ray_world.time);
+        match &self.bvh {
+            None => self.primitives.iter().any(|prim| prim.intersect_p(&ray)),
+            Some(bvh) => bvh.any(&ray, |i, ray| self.primitives[i].intersect_p(ray)),
+        }
+    }
+
+    /// Test every primitive, serves as the reference of `nearest_hit`
+    pub fn nearest_hit_brute_force(&self, ray_world: &Ray) -> Option<Intersection<'_, G, B, T>> {
         let mut isect: Option<Intersection<G, B, T>> = None;
         for prim in &self.primitives {
             let new_isect = prim.intersect(ray_world);
@@ -41,7 +88,208 @@ impl<G, B, T> Deref for Scene<G, B, T> where G: Geometry, B: BSDF, T: Texture {
 }
 
 impl<G, B, T> DerefMut for Scene<G, B, T> where G: Geometry, B: BSDF, T: Texture {
+    /// Primitives may be moved, so the bvh is no longer valid
     fn deref_mut(&mut self) -> &mut Self::Target {
+        self.bvh = None;
         &mut self.primitives
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::random;
+    use std::sync::Arc;
+
+    fn material() -> Arc<Material<bsdf::Simple, texture::Uniform>> {
+        Arc::new(Material { bsdf: bsdf::Simple::default(), texture: texture::Uniform::default(), emission: Spectrum::black() })
+    }
+
+    #[test]
+    fn bvh_matches_brute_force() {
+        let mut scene = Scene::new();
+        let material = material();
+        for _ in 0..200 {
+            let pos = vec3(random(), random(), random()) * 100.;
+            scene.push(Primitive::new(Sphere::new(random::<Float>() * 5. + 0.1), material.clone(), Matrix4::from_translation(pos)));
+        }
+        scene.build_bvh();
+        assert!(scene.has_bvh());
+        for _ in 0..1000 {
+            let org = pt3(random(), random(), random()) * 100.;
+            let dir = vec3(random::<Float>() - 0.5, random::<Float>() - 0.5, random::<Float>() - 0.5).normalize();
+            let ray = Ray::new(org, dir);
+            let expected = scene.nearest_hit_brute_force(&ray).map(|its| (its.0.t, its.1.label.clone()));
+            let actual = scene.nearest_hit(&ray).map(|its| (its.0.t, its.1.label.clone()));
+            assert_eq!(actual, expected);
+            let t_max = random::<Float>() * 50.;
+            let occluded = expected.map_or(false, |(t, _)| t < t_max);
+            assert_eq!(scene.occluded(&ray, t_max), occluded);
+        }
+        scene.push(Primitive::new(Sphere::new(1.), material, Matrix4::identity()));
+        assert!(!scene.has_bvh());
+    }
+
+    #[test]
+    fn bounds() {
+        let mut scene: Scene<DynamicGeometry, _, _> = Scene::new();
+        assert!(scene.bounds().is_empty());
+        let material = material();
+        scene.push(Primitive::new(
+            Sphere::new(1.).into(),
+            material.clone(),
+            Matrix4::from_translation(vec3(10., 0., 0.)) * Matrix4::from_nonuniform_scale(2., 1., 1.),
+        ));
+        assert_eq!(scene[0].world_bounds(), Bounds3f::new(pt3(8., -1., -1.), pt3(12., 1., 1.)));
+        scene.push(Primitive::new(
+            Triangle::new(pt3(0., 0., 0.), pt3(1., 0., 0.), pt3(0., 1., 0.)).into(),
+            material,
+            Matrix4::from_translation(vec3(0., 0., -5.)),
+        ));
+        assert_eq!(scene[1].geometry.bounds(), Bounds3f::new(pt3(0., 0., 0.), pt3(1., 1., 0.)));
+        assert_eq!(scene.bounds(), Bounds3f::new(pt3(0., -1., -5.), pt3(12., 1., 1.)));
+    }
+
+    #[test]
+    fn non_uniform_scale() {
+        let mut scene = Scene::new();
+        let material = material();
+        // the ellipsoid (x / 4)^2 + y^2 + (z / 0.5)^2 = 1 around (10, 0, 0)
+        scene.push(Primitive::new(
+            Sphere::new(1.),
+            material,
+            Matrix4::from_translation(vec3(10., 0., 0.)) * Matrix4::from_nonuniform_scale(4., 1., 0.5),
+        ));
+        let center = pt3(10., 0., 0.);
+        for _ in 0..1000 {
+            let dir = vec3(random::<Float>() - 0.5, random::<Float>() - 0.5, random::<Float>() - 0.5).normalize();
+            let org = center - dir * 10.;
+            let ray = Ray::new(org, dir);
+            let its = scene.nearest_hit(&ray).unwrap().0;
+            // world distance, and the gradient of the implicit surface
+            assert_approx!(its.t, (its.pos - org).magnitude());
+            let p = its.pos - center;
+            let gradient = vec3(p.x / 16., p.y, p.z / 0.25).normalize();
+            assert_approx!((its.normal - gradient).magnitude(), 0.);
+            assert_approx!(its.normal.magnitude(), 1.);
+            assert_approx!((its.wi + dir).magnitude(), 0.);
+            assert!(scene.occluded(&ray, its.t + 1e-3));
+            assert!(!scene.occluded(&ray, its.t - 1e-3));
+        }
+    }
+
+    #[test]
+    fn instancing() {
+        // a tetrahedron shared by a 10 x 10 x 10 grid of instances
+        let mesh = Arc::new(Mesh::new(
+            vec![pt3(0., 0., 0.), pt3(1., 0., 0.), pt3(0., 1., 0.), pt3(0., 0., 1.)],
+            vec![[0, 2, 1], [0, 1, 3], [0, 3, 2], [1, 2, 3]],
+        ));
+        let material = material();
+        let mut scene = Scene::new();
+        for i in 0..1000 {
+            let offset = vec3((i % 10) as Float, (i / 10 % 10) as Float, (i / 100) as Float) * 3.;
+            scene.push(Primitive::new(mesh.clone(), material.clone(), Matrix4::from_translation(offset)));
+        }
+        scene.build_bvh();
+        assert_eq!(Arc::strong_count(&mesh), 1001);
+        assert_eq!(scene.bounds(), Bounds3f::new(pt3(0., 0., 0.), pt3(28., 28., 28.)));
+        let its = scene.nearest_hit(&Ray::new(pt3(3.2, 6.2, -1.), vec3(0., 0., 1.))).unwrap();
+        assert_approx!(its.0.t, 1.);
+        assert_eq!(its.0.side, Side::Outside);
+        for _ in 0..200 {
+            let org = pt3(random(), random(), random()) * 30.;
+            let dir = vec3(random::<Float>() - 0.5, random::<Float>() - 0.5, random::<Float>() - 0.5).normalize();
+            let ray = Ray::new(org, dir);
+            let expected = scene.nearest_hit_brute_force(&ray).map(|its| (its.0.t, its.1.label.clone()));
+            assert_eq!(scene.nearest_hit(&ray).map(|its| (its.0.t, its.1.label.clone())), expected);
+        }
+    }
+
+    #[test]
+    fn heterogeneous() {
+        let mut scene = DynamicScene::new();
+        let glass = Arc::new(Material { bsdf: bsdf::Simple::Dielectric(Default::default()).into(), texture: texture::Uniform(Spectrum::uniform(1.)).into(), emission: Spectrum::black() });
+        let checker = texture::Image::new(2, 2, vec![Spectrum::uniform(1.), Spectrum::black(), Spectrum::black(), Spectrum::uniform(1.)]);
+        let checkered = Arc::new(Material { bsdf: bsdf::DynamicBSDF::default(), texture: checker.into(), emission: Spectrum::black() });
+        let mut quad = Mesh::new(
+            vec![pt3(0., 0., 0.), pt3(1., 0., 0.), pt3(1., 1., 0.), pt3(0., 1., 0.)],
+            vec![[0, 1, 2], [0, 2, 3]],
+        );
+        quad.set_uvs(vec![pt2(0., 0.), pt2(1., 0.), pt2(1., 1.), pt2(0., 1.)]);
+        let quad = Arc::new(DynamicGeometry::from(quad));
+        scene.push(Primitive::new(Sphere::new(1.).into(), glass, Matrix4::from_translation(vec3(0., 0., 5.))));
+        for i in 0..2 {
+            scene.push(Primitive::new(quad.clone().into(), checkered.clone(), Matrix4::from_translation(vec3(3. * i as Float, 0., 0.))));
+        }
+        scene.build_bvh();
+        assert_eq!(scene.bounds(), Bounds3f::new(pt3(-1., -1., 0.), pt3(4., 1., 6.)));
+        let down = |x: Float, y: Float| scene.nearest_hit(&Ray::new(pt3(x, y, 10.), vec3(0., 0., -1.))).unwrap();
+        let Intersection(its, prim) = down(0., 0.);
+        assert_approx!(its.t, 4.);
+        assert!(matches!(prim.material.bsdf, bsdf::DynamicBSDF::Dielectric(_)));
+        let Intersection(its, prim) = down(3.7, 0.2);
+        assert!(matches!(prim.geometry, DynamicGeometry::Shared(_)));
+        assert!(matches!(prim.material.bsdf, bsdf::DynamicBSDF::Diffuse(_)));
+        assert_eq!(prim.material.texture.at(its.uv), &Spectrum::black());
+    }
+
+    #[test]
+    fn motion() {
+        let mut scene = Scene::new();
+        let mut prim = Primitive::new(Sphere::new(1.), material(), Matrix4::identity());
+        prim.set_animation(AnimatedTransform::between(
+            0., Matrix4::identity(),
+            1., Matrix4::from_translation(vec3(10., 0., 0.)),
+        ));
+        scene.push(prim);
+        scene.build_bvh();
+        assert_eq!(scene.bounds(), Bounds3f::new(pt3(-1., -1., -1.), pt3(11., 1., 1.)));
+        let ray = |x: Float, time: Float| Ray::new(pt3(x, 0., -5.), vec3(0., 0., 1.)).with_time(time);
+        assert!(scene.nearest_hit(&ray(0., 0.)).is_some());
+        assert!(scene.nearest_hit(&ray(0., 0.5)).is_none());
+        let its = scene.nearest_hit(&ray(5., 0.5)).unwrap();
+        assert_approx!(its.0.t, 4.);
+        assert!(scene.occluded(&ray(10., 1.), Float::infinity()));
+        assert!(!scene.occluded(&ray(10., 0.5), Float::infinity()));
+        // stops moving
+        scene[0].set_transform(Matrix4::identity());
+        assert!(scene[0].animation().is_none());
+        assert!(scene.nearest_hit_brute_force(&ray(0., 0.5)).is_some());
+    }
+
+    #[test]
+    fn motion_bvh() {
+        let mut scene: Scene<DynamicGeometry, _, _> = Scene::new();
+        let material = material();
+        for i in 0..20 {
+            let pos = vec3((i % 5) as Float * 4. - 8., (i / 5) as Float * 4. - 8., -3.);
+            scene.push(Primitive::new(Sphere::new(1.).into(), material.clone(), Matrix4::from_translation(pos)));
+        }
+        // a square swinging around the origin, the corners sweep arcs
+        let mut square = Primitive::new(
+            Mesh::new(vec![pt3(99., -1., 0.), pt3(101., -1., 0.), pt3(101., 1., 0.), pt3(99., 1., 0.)], vec![[0, 1, 2], [0, 2, 3]]).into(),
+            material,
+            Matrix4::identity(),
+        );
+        square.set_animation(AnimatedTransform::between(0., Matrix4::identity(), 1., Matrix4::from_angle_z(Deg(120.))));
+        scene.push(square);
+        scene.build_bvh();
+        // a corner at the top of its arc, in between the poses sampled for the bounds
+        let time = (90. - (1. as Float / 101.).atan().to_degrees()) / 120.;
+        let top = (101. * 101. + 1. as Float).sqrt();
+        let ray = Ray::new(pt3(0.05, top - 1e-3, 1.), vec3(0., 0., -1.)).with_time(time);
+        let its = scene.nearest_hit(&ray).unwrap();
+        assert_approx!(its.0.t, 1.);
+        assert!(scene.occluded(&ray, Float::infinity()));
+        for _ in 0..1000 {
+            let org = pt3(random::<Float>() * 2. - 1., random::<Float>() * 2. - 1., 0.01) * 110.;
+            let dir = vec3(random::<Float>() - 0.5, random::<Float>() - 0.5, -1.).normalize();
+            let ray = Ray::new(org, dir).with_time(random());
+            let expected = scene.nearest_hit_brute_force(&ray).map(|its| (its.0.t, its.1.label.clone()));
+            let actual = scene.nearest_hit(&ray).map(|its| (its.0.t, its.1.label.clone()));
+            assert_eq!(actual, expected);
+            assert_eq!(scene.occluded(&ray, Float::infinity()), expected.is_some());
+        }
+    }
+}